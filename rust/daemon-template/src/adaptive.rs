@@ -0,0 +1,308 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::config::Config;
+
+/// Limits concurrent work units, adapting the limit to how work is going
+/// (additive increase, multiplicative decrease).
+///
+/// Every `limit` fast, successful completions in a row raise the limit by
+/// one, up to `max`. A failure, or a unit slower than the latency target,
+/// halves it, down to `min`. Without `adaptive_concurrency` the limit is
+/// fixed at `max_concurrent_work`. The limit is published as the
+/// `worker_concurrency_limit` gauge.
+///
+/// With `slow_start` the limit is further capped by a ramp from 1 at
+/// startup to `max` once the warmup window has passed, in even steps.
+pub struct ConcurrencyLimiter {
+    min: usize,
+    max: usize,
+    latency_target: Option<Duration>,
+    /// When the limiter was created, and the length of the warmup window.
+    warmup: Option<(Instant, Duration)>,
+    state: Mutex<State>,
+    released: Notify,
+}
+
+struct State {
+    limit: usize,
+    in_flight: usize,
+    good_streak: usize,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(config: &Config) -> Arc<Self> {
+        let max = config.max_concurrent_work;
+        let (min, latency_target) = if config.adaptive_concurrency {
+            (
+                config.min_concurrent_work.min(max),
+                Some(config.adaptive_latency_target),
+            )
+        } else {
+            (max, None)
+        };
+        let limiter = Arc::new(Self {
+            min,
+            max,
+            latency_target,
+            warmup: config.slow_start.map(|window| (Instant::now(), window)),
+            state: Mutex::new(State {
+                limit: min,
+                in_flight: 0,
+                good_streak: 0,
+            }),
+            released: Notify::new(),
+        });
+        limiter.publish(min);
+        limiter
+    }
+
+    /// The current limit on concurrent work units, including the slow-start
+    /// ramp.
+    #[cfg(test)]
+    pub fn limit(&self) -> usize {
+        let limit = self.state.lock().unwrap().limit;
+        limit.min(self.ramp())
+    }
+
+    /// The most units the slow-start ramp allows right now: 1 at startup,
+    /// one more every `window / (max - 1)`, and `max` after the window.
+    fn ramp(&self) -> usize {
+        let Some((started, window)) = self.warmup else {
+            return self.max;
+        };
+        let elapsed = started.elapsed();
+        if elapsed >= window {
+            return self.max;
+        }
+        let steps = (self.max - 1) as u128 * elapsed.as_nanos() / window.as_nanos();
+        1 + steps as usize
+    }
+
+    /// When the slow-start ramp next allows one more unit, if it is still
+    /// ramping.
+    fn next_ramp_step(&self) -> Option<Instant> {
+        let (started, window) = self.warmup?;
+        let ramp = self.ramp();
+        if ramp >= self.max {
+            return None;
+        }
+        let nanos = (window.as_nanos() * ramp as u128).div_ceil((self.max - 1) as u128);
+        Some(started + Duration::from_nanos(nanos as u64))
+    }
+
+    /// Takes a slot if one is free.
+    pub fn try_acquire(self: &Arc<Self>) -> Option<Permit> {
+        let mut state = self.state.lock().unwrap();
+        if state.in_flight >= state.limit.min(self.ramp()) {
+            return None;
+        }
+        state.in_flight += 1;
+        Some(Permit {
+            limiter: self.clone(),
+            started: Instant::now(),
+            outcome: None,
+        })
+    }
+
+    /// Waits for a free slot, freed either by a release or by the
+    /// slow-start ramp.
+    pub async fn acquire(self: &Arc<Self>) -> Permit {
+        loop {
+            let released = self.released.notified();
+            if let Some(permit) = self.try_acquire() {
+                return permit;
+            }
+            match self.next_ramp_step() {
+                Some(step) => {
+                    let _ = tokio::time::timeout_at(step, released).await;
+                }
+                None => released.await,
+            }
+        }
+    }
+
+    fn release(&self, outcome: Option<(Duration, bool)>) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight -= 1;
+
+        if let (Some(target), Some((latency, success))) = (self.latency_target, outcome) {
+            let before = state.limit;
+            if success && latency <= target {
+                state.good_streak += 1;
+                if state.good_streak >= state.limit && state.limit < self.max {
+                    state.limit += 1;
+                    state.good_streak = 0;
+                }
+            } else {
+                state.limit = (state.limit / 2).max(self.min);
+                state.good_streak = 0;
+            }
+
+            if state.limit != before {
+                if state.limit < before {
+                    info!(
+                        "Work is failing or slow ({:?}), concurrency {} -> {}",
+                        latency, before, state.limit
+                    );
+                } else {
+                    debug!("Concurrency {} -> {}", before, state.limit);
+                }
+                self.publish(state.limit);
+            }
+        }
+
+        drop(state);
+        self.released.notify_waiters();
+    }
+
+    fn publish(&self, limit: usize) {
+        metrics::gauge!("worker_concurrency_limit").set(limit as f64);
+    }
+}
+
+/// One work unit's slot, released when dropped. Call [`Permit::finish`] to
+/// also feed its outcome into the adaptive limit.
+pub struct Permit {
+    limiter: Arc<ConcurrencyLimiter>,
+    started: Instant,
+    /// Latency and success, once [`Permit::finish`] has recorded them.
+    outcome: Option<(Duration, bool)>,
+}
+
+impl Permit {
+    pub fn finish(mut self, success: bool) {
+        self.outcome = Some((self.started.elapsed(), success));
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.release(self.outcome.take());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::advance;
+
+    fn adaptive(min: usize, max: usize) -> Arc<ConcurrencyLimiter> {
+        ConcurrencyLimiter::new(&Config {
+            adaptive_concurrency: true,
+            min_concurrent_work: min,
+            max_concurrent_work: max,
+            adaptive_latency_target: Duration::from_millis(100),
+            ..Config::default()
+        })
+    }
+
+    /// Runs `limit` units at once, each taking `latency`.
+    async fn round(limiter: &Arc<ConcurrencyLimiter>, latency: Duration, success: bool) {
+        let permits: Vec<Permit> = (0..limiter.limit())
+            .map(|_| limiter.try_acquire().unwrap())
+            .collect();
+        assert!(limiter.try_acquire().is_none(), "limit is enforced");
+        advance(latency).await;
+        for permit in permits {
+            permit.finish(success);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backs_off_on_slow_or_failing_work_and_recovers() {
+        let limiter = adaptive(1, 8);
+        assert_eq!(limiter.limit(), 1);
+
+        for _ in 0..7 {
+            round(&limiter, Duration::from_millis(10), true).await;
+        }
+        assert_eq!(limiter.limit(), 8);
+        round(&limiter, Duration::from_millis(10), true).await;
+        assert_eq!(limiter.limit(), 8, "capped at max");
+
+        // The first slow completion halves the limit
+        let permit = limiter.try_acquire().unwrap();
+        advance(Duration::from_millis(500)).await;
+        permit.finish(true);
+        assert_eq!(limiter.limit(), 4);
+
+        round(&limiter, Duration::from_millis(10), false).await;
+        assert_eq!(limiter.limit(), 1, "floored at min");
+
+        for _ in 0..3 {
+            round(&limiter, Duration::from_millis(10), true).await;
+        }
+        assert_eq!(limiter.limit(), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fixed_limit_without_adaptive_concurrency() {
+        let limiter = ConcurrencyLimiter::new(&Config {
+            max_concurrent_work: 3,
+            ..Config::default()
+        });
+        round(&limiter, Duration::from_secs(5), false).await;
+        assert_eq!(limiter.limit(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_start_ramps_up_to_the_limit() {
+        let limiter = ConcurrencyLimiter::new(&Config {
+            max_concurrent_work: 5,
+            slow_start: Some(Duration::from_secs(40)),
+            ..Config::default()
+        });
+
+        // One more slot every 10s
+        let mut limits = vec![limiter.limit()];
+        for _ in 0..5 {
+            advance(Duration::from_secs(10)).await;
+            limits.push(limiter.limit());
+        }
+        assert_eq!(limits, [1, 2, 3, 4, 5, 5]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_waits_for_the_ramp() {
+        let limiter = ConcurrencyLimiter::new(&Config {
+            max_concurrent_work: 3,
+            slow_start: Some(Duration::from_secs(20)),
+            ..Config::default()
+        });
+        let _held = limiter.try_acquire().unwrap();
+        assert!(limiter.try_acquire().is_none());
+
+        // Nothing is released; the second slot opens 10s in
+        let started = Instant::now();
+        let _second = limiter.acquire().await;
+        assert_eq!(started.elapsed(), Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_for_a_released_slot() {
+        let limiter = adaptive(1, 1);
+        let held = limiter.try_acquire().unwrap();
+
+        let waiter = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.finish(true) }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+
+        drop(held);
+        waiter.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn finished_permits_release_the_limiter() {
+        let limiter = ConcurrencyLimiter::new(&Config::default());
+        limiter.try_acquire().unwrap().finish(true);
+        drop(limiter.try_acquire().unwrap());
+        assert_eq!(Arc::strong_count(&limiter), 1);
+    }
+}
@@ -0,0 +1,196 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::Json,
+    routing::post,
+    Router,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tokio::net::TcpListener;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::worker::{Outcome, Worker};
+
+#[derive(Clone)]
+struct AdminState {
+    worker: Arc<dyn Worker>,
+    token: Option<Arc<str>>,
+}
+
+/// Result of an on-demand work run.
+#[derive(Serialize, Debug)]
+pub struct RunReport {
+    pub success: bool,
+    pub outcome: Option<Outcome>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Operator endpoints served on `ADMIN_ADDR`. Every request must carry
+/// `Authorization: Bearer <token>`; without a `token` they are all refused.
+pub fn router(worker: Arc<dyn Worker>, token: Option<String>) -> Router {
+    Router::new()
+        .route("/admin/run", post(run_now))
+        .with_state(AdminState {
+            worker,
+            token: token.map(Into::into),
+        })
+}
+
+/// Serves `router` until `shutdown` is cancelled.
+pub async fn serve(
+    listener: TcpListener,
+    router: Router,
+    shutdown: CancellationToken,
+) -> std::io::Result<()> {
+    info!("Admin server listening on {}", listener.local_addr()?);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+}
+
+/// A report for a run that was refused before it started.
+fn refused(status: StatusCode, error: &str) -> (StatusCode, Json<RunReport>) {
+    (
+        status,
+        Json(RunReport {
+            success: false,
+            outcome: None,
+            error: Some(error.to_string()),
+            duration_ms: 0,
+        }),
+    )
+}
+
+/// Runs one `perform_work` iteration immediately and reports how it went.
+/// The regular tick schedule is unaffected; the worker sees iteration `0`.
+///
+/// Refused with 403 unless `ADMIN_TOKEN` is configured, so an address left
+/// reachable can't be used to run work by anyone. The token is compared in
+/// constant time, so response timing doesn't reveal how much of a guess
+/// was right.
+async fn run_now(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<RunReport>) {
+    let Some(expected) = &state.token else {
+        return refused(
+            StatusCode::FORBIDDEN,
+            "Running work over HTTP requires ADMIN_TOKEN to be set",
+        );
+    };
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let matches =
+        provided.is_some_and(|provided| provided.as_bytes().ct_eq(expected.as_bytes()).into());
+    if !matches {
+        return refused(StatusCode::UNAUTHORIZED, "Admin credentials required");
+    }
+
+    info!("Manual work run requested");
+    let started = Instant::now();
+    let result = state.worker.perform_work(0).await;
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(outcome) => (
+            StatusCode::OK,
+            Json(RunReport {
+                success: true,
+                outcome: Some(outcome),
+                error: None,
+                duration_ms,
+            }),
+        ),
+        Err(e) => {
+            warn!("Manual work run failed: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(RunReport {
+                    success: false,
+                    outcome: None,
+                    error: Some(e.to_string()),
+                    duration_ms,
+                }),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::WorkError;
+    use async_trait::async_trait;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use tower::ServiceExt;
+
+    #[derive(Default)]
+    struct CountingWorker {
+        runs: AtomicU64,
+        last_iteration: AtomicU64,
+    }
+
+    #[async_trait]
+    impl Worker for CountingWorker {
+        async fn perform_work(&self, iteration: u64) -> Result<Outcome, WorkError> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            self.last_iteration.store(iteration, Ordering::SeqCst);
+            Ok(Outcome::Idle)
+        }
+    }
+
+    fn run_request(token: Option<&str>) -> Request<Body> {
+        let mut request = Request::post("/admin/run");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn run_endpoint_performs_one_iteration_and_reports_it() {
+        let worker = Arc::new(CountingWorker::default());
+        let app = router(worker.clone(), Some("t0ken".to_string()));
+
+        let response = app.clone().oneshot(run_request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app
+            .clone()
+            .oneshot(run_request(Some("t0kem")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(worker.runs.load(Ordering::SeqCst), 0);
+
+        let response = app.oneshot(run_request(Some("t0ken"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["success"], true);
+        assert_eq!(body["outcome"], "idle");
+
+        assert_eq!(worker.runs.load(Ordering::SeqCst), 1);
+        assert_eq!(worker.last_iteration.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn runs_are_refused_without_a_configured_token() {
+        let worker = Arc::new(CountingWorker::default());
+        let app = router(worker.clone(), None);
+
+        for token in [None, Some("anything")] {
+            let response = app.clone().oneshot(run_request(token)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        assert_eq!(worker.runs.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,19 @@
+use std::time::SystemTime;
+
+/// Source of wall-clock time, injectable so time-dependent scheduling can be
+/// tested without waiting on the real clock.
+///
+/// Only tick alignment (`TICK_ALIGN`) reads it. Intervals, backoff and
+/// timeouts all run on tokio's monotonic `Instant`, which a wall clock step
+/// can't move.
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> SystemTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
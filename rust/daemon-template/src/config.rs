@@ -0,0 +1,262 @@
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// What the scheduler does with a tick when every work slot is busy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for a slot to free up.
+    #[default]
+    Queue,
+    /// Drop the tick; the schedule fires again next interval.
+    #[allow(dead_code)] // Chosen in code by daemons built on `Scheduler`
+    Skip,
+}
+
+/// Runtime configuration, read from environment variables at startup.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Time between work ticks (`TICK_INTERVAL_SECS`).
+    pub tick_interval: Duration,
+    /// Align ticks to wall-clock multiples of the interval, e.g. the top of
+    /// every minute for a 60s interval (`TICK_ALIGN`). When off, ticks are
+    /// relative to process start.
+    pub tick_align: bool,
+    /// Wait this long after startup before the first tick
+    /// (`STARTUP_DELAY_SECS`), e.g. to let dependencies settle or to stagger
+    /// instances. Zero ticks immediately.
+    pub startup_delay: Duration,
+    /// Exit cleanly after this many consecutive idle ticks
+    /// (`IDLE_SHUTDOWN_TICKS`), so an orchestrator can start the daemon again
+    /// on demand. `None` keeps it running forever.
+    pub idle_shutdown_ticks: Option<u32>,
+    /// Give up with exit code 2 after this many consecutive failed ticks
+    /// (`MAX_CONSECUTIVE_FAILURES`). `None` keeps retrying forever.
+    pub max_consecutive_failures: Option<u32>,
+    /// Most work units the scheduler runs at once across all schedules; at
+    /// least 1. This and the settings down to `work_overflow` configure
+    /// [`Scheduler`](crate::scheduler::Scheduler) only, so they aren't read
+    /// from the environment: the loop `main` runs does one work unit at a
+    /// time. A daemon built on `Scheduler` sets them in code.
+    pub max_concurrent_work: usize,
+    /// Adapt the scheduler's concurrency between `min_concurrent_work` and
+    /// `max_concurrent_work` to how work is going.
+    pub adaptive_concurrency: bool,
+    /// Floor, and starting point, of the adaptive limit.
+    pub min_concurrent_work: usize,
+    /// Work units slower than this count against the adaptive limit like
+    /// failures do.
+    pub adaptive_latency_target: Duration,
+    /// Ramp the scheduler's concurrency from 1 up to its limit over this
+    /// long after startup, while dependencies warm up. `None` starts at full
+    /// concurrency.
+    pub slow_start: Option<Duration>,
+    /// Whether ticks beyond `max_concurrent_work` wait or are skipped.
+    pub work_overflow: OverflowPolicy,
+    /// Address for the embedded admin HTTP server (`ADMIN_ADDR`). `None`
+    /// leaves it off.
+    pub admin_addr: Option<String>,
+    /// Bearer token required by the admin server (`ADMIN_TOKEN`); without
+    /// one it refuses every request.
+    pub admin_token: Option<String>,
+    /// Address for the health probe server (`HEALTH_ADDR`). `None` leaves
+    /// it off.
+    pub health_addr: Option<String>,
+    /// How long, from the first shutdown request, the health server and the
+    /// work loop together have to stop (`SHUTDOWN_TIMEOUT_SECS`).
+    pub shutdown_timeout: Duration,
+    /// Broker work results are published to (`BROKER_URL`, currently
+    /// `redis://host:port`). `None` disables publishing.
+    pub broker_url: Option<String>,
+    /// Channel results are published on (`BROKER_SUBJECT`).
+    pub broker_subject: String,
+    /// Queue to take jobs from instead of ticking on a schedule
+    /// (`JOB_SOURCE`, currently `redis://host:port/<list>`). `None` ticks.
+    pub job_source: Option<String>,
+    /// File the daemon keeps its state in across restarts (`STATE_FILE`).
+    /// `None` keeps no state.
+    pub state_file: Option<PathBuf>,
+    /// Refuse to start on a corrupt state file instead of backing it up and
+    /// starting fresh (`STRICT_STATE`).
+    pub strict_state: bool,
+    /// Where to write the JSON shutdown report, in addition to logging it
+    /// (`SHUTDOWN_REPORT_FILE`).
+    pub shutdown_report_file: Option<PathBuf>,
+    /// Refuse to start when a file the daemon writes (`STATE_FILE`,
+    /// `SHUTDOWN_REPORT_FILE`) is on a read-only filesystem or otherwise
+    /// unwritable, instead of running without it (`STRICT_WRITES`).
+    pub strict_writes: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tick_interval: Duration::from_secs(10),
+            tick_align: false,
+            startup_delay: Duration::ZERO,
+            idle_shutdown_ticks: None,
+            max_consecutive_failures: None,
+            max_concurrent_work: 4,
+            adaptive_concurrency: false,
+            min_concurrent_work: 1,
+            adaptive_latency_target: Duration::from_secs(1),
+            slow_start: None,
+            work_overflow: OverflowPolicy::Queue,
+            admin_addr: None,
+            admin_token: None,
+            health_addr: None,
+            shutdown_timeout: Duration::from_secs(30),
+            broker_url: None,
+            broker_subject: "daemon.results".to_string(),
+            job_source: None,
+            state_file: None,
+            strict_state: false,
+            shutdown_report_file: None,
+            strict_writes: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        Self {
+            tick_interval: env_parse::<u64>("TICK_INTERVAL_SECS")
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.tick_interval),
+            tick_align: env_parse("TICK_ALIGN").unwrap_or(defaults.tick_align),
+            startup_delay: env_parse::<u64>("STARTUP_DELAY_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.startup_delay),
+            idle_shutdown_ticks: env_parse::<u32>("IDLE_SHUTDOWN_TICKS").filter(|n| *n > 0),
+            max_consecutive_failures: env_parse::<u32>("MAX_CONSECUTIVE_FAILURES")
+                .filter(|n| *n > 0),
+            max_concurrent_work: defaults.max_concurrent_work,
+            adaptive_concurrency: defaults.adaptive_concurrency,
+            min_concurrent_work: defaults.min_concurrent_work,
+            adaptive_latency_target: defaults.adaptive_latency_target,
+            slow_start: defaults.slow_start,
+            work_overflow: defaults.work_overflow,
+            admin_addr: env::var("ADMIN_ADDR").ok().filter(|a| !a.is_empty()),
+            admin_token: env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+            health_addr: env::var("HEALTH_ADDR").ok().filter(|a| !a.is_empty()),
+            shutdown_timeout: env_parse::<u64>("SHUTDOWN_TIMEOUT_SECS")
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.shutdown_timeout),
+            broker_url: env::var("BROKER_URL").ok().filter(|u| !u.is_empty()),
+            broker_subject: env::var("BROKER_SUBJECT").unwrap_or(defaults.broker_subject),
+            job_source: env::var("JOB_SOURCE").ok().filter(|s| !s.is_empty()),
+            state_file: env::var_os("STATE_FILE")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            strict_state: env_parse("STRICT_STATE").unwrap_or(defaults.strict_state),
+            shutdown_report_file: env::var_os("SHUTDOWN_REPORT_FILE")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            strict_writes: env_parse("STRICT_WRITES").unwrap_or(defaults.strict_writes),
+        }
+    }
+}
+
+/// The current [`Config`], replaceable while the daemon runs (e.g. by a
+/// config reload).
+///
+/// Readers take a snapshot with [`SharedConfig::load`] and use it for a whole
+/// unit of work, so a reload in the middle of a tick is seen by the next tick
+/// rather than half-applied to this one. Swapping the `Arc` under the lock
+/// means a snapshot is always one complete config, never a mix of old and new
+/// fields.
+#[derive(Debug, Clone)]
+pub struct SharedConfig(Arc<RwLock<Arc<Config>>>);
+
+impl SharedConfig {
+    pub fn new(config: Config) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(config))))
+    }
+
+    /// The config as of now. Later reloads don't change the returned value.
+    pub fn load(&self) -> Arc<Config> {
+        self.0.read().unwrap().clone()
+    }
+
+    /// Replaces the config seen by every later [`SharedConfig::load`].
+    #[allow(dead_code)] // called by config reloads; nothing reloads yet
+    pub fn store(&self, config: Config) {
+        *self.0.write().unwrap() = Arc::new(config);
+    }
+}
+
+impl From<Config> for SharedConfig {
+    fn from(config: Config) -> Self {
+        Self::new(config)
+    }
+}
+
+/// Parses an environment variable, ignoring it (with a warning) if it is
+/// present but malformed.
+fn env_parse<T: FromStr>(key: &str) -> Option<T> {
+    let raw = env::var(key).ok()?;
+    match raw.trim().parse() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            tracing::warn!("Ignoring invalid value for {}: {:?}", key, raw);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    /// A config whose fields all encode `generation`, so a torn read shows up
+    /// as fields that disagree.
+    fn generation(generation: u64) -> Config {
+        Config {
+            tick_interval: Duration::from_secs(generation + 1),
+            startup_delay: Duration::from_secs(generation + 1),
+            max_consecutive_failures: Some(generation as u32 + 1),
+            broker_subject: format!("gen-{generation}"),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn readers_never_see_a_half_reloaded_config() {
+        let shared = SharedConfig::new(generation(0));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let reader = thread::spawn({
+            let (shared, done) = (shared.clone(), done.clone());
+            move || {
+                let mut reads = 0u64;
+                let mut last = 0;
+                while !done.load(Ordering::Relaxed) {
+                    let config = shared.load();
+                    let n = config.tick_interval.as_secs() - 1;
+                    assert_eq!(config.startup_delay.as_secs() - 1, n);
+                    assert_eq!(config.max_consecutive_failures, Some(n as u32 + 1));
+                    assert_eq!(config.broker_subject, format!("gen-{n}"));
+                    assert!(n >= last, "went back from generation {last} to {n}");
+                    last = n;
+                    reads += 1;
+                }
+                reads
+            }
+        });
+
+        for n in 1..=20_000 {
+            shared.store(generation(n));
+        }
+        done.store(true, Ordering::Relaxed);
+        assert!(reader.join().unwrap() > 0);
+        assert_eq!(shared.load().broker_subject, "gen-20000");
+    }
+}
@@ -0,0 +1,897 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::{interval, interval_at, sleep, Instant, Interval, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::clock::Clock;
+use crate::config::{Config, SharedConfig};
+use crate::source::JobSource;
+use crate::worker::{Outcome, Progress, Worker};
+
+/// Decides when the next tick fires.
+enum Schedule {
+    /// Every `period`, counted from process start.
+    Relative(Interval),
+    /// On wall-clock multiples of `period` since the Unix epoch.
+    Aligned {
+        period: Duration,
+        clock: Arc<dyn Clock>,
+        last: Option<u128>,
+    },
+}
+
+impl Schedule {
+    fn new(config: &Config, clock: Arc<dyn Clock>) -> Self {
+        if config.tick_align {
+            Self::Aligned {
+                period: config.tick_interval,
+                clock,
+                last: None,
+            }
+        } else {
+            let mut tick_interval = interval(config.tick_interval);
+            tick_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            Self::Relative(tick_interval)
+        }
+    }
+
+    async fn tick(&mut self) {
+        match self {
+            Self::Relative(tick_interval) => {
+                tick_interval.tick().await;
+            }
+            Self::Aligned {
+                period,
+                clock,
+                last,
+            } => loop {
+                // Stepped back behind the last tick (while its work ran):
+                // the boundaries in between would all be skipped as already
+                // fired, so align to the new reading instead
+                let behind = last.map_or(0, |last| last as i128 - unix_millis(clock.now()) as i128);
+                if behind > CLOCK_JUMP_TOLERANCE.as_millis() as i128 {
+                    report_clock_jump(-behind, "since the last tick");
+                    *last = None;
+                }
+
+                let (boundary, delay) = next_boundary(clock.now(), *period, *last);
+                sleep(delay).await;
+
+                let drift = unix_millis(clock.now()) as i128 - boundary as i128;
+                if drift.unsigned_abs() <= CLOCK_JUMP_TOLERANCE.as_millis() {
+                    *last = Some(boundary);
+                    return;
+                }
+                // The boundary we slept towards no longer matches the wall
+                // clock; wait for the next one by the new reading instead of
+                // firing now and again right after.
+                report_clock_jump(drift, "while waiting for a tick");
+                *last = None;
+            },
+        }
+    }
+}
+
+/// Logs and counts a wall clock jump of `drift_ms` (negative: backward)
+/// that aligned ticks are realigning after.
+fn report_clock_jump(drift_ms: i128, when: &str) {
+    warn!(
+        "Wall clock jumped {} by {:?} {}, realigning",
+        if drift_ms > 0 { "forward" } else { "back" },
+        Duration::from_millis(drift_ms.unsigned_abs() as u64),
+        when
+    );
+    metrics::counter!("daemon_clock_jumps_total").increment(1);
+}
+
+/// How far the wall clock may be off an aligned boundary on waking before
+/// it counts as having jumped (NTP step, VM resume) rather than ordinary
+/// timer lateness.
+const CLOCK_JUMP_TOLERANCE: Duration = Duration::from_secs(2);
+
+fn unix_millis(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// Returns the next aligned boundary (in ms since the epoch) strictly after
+/// `last`, and how long to wait from `now` until it.
+fn next_boundary(now: SystemTime, period: Duration, last: Option<u128>) -> (u128, Duration) {
+    let now_ms = unix_millis(now);
+    let period_ms = period.as_millis().max(1);
+
+    let mut boundary = now_ms.div_ceil(period_ms) * period_ms;
+    if last.is_some_and(|last| boundary <= last) {
+        boundary += period_ms;
+    }
+
+    (boundary, Duration::from_millis((boundary - now_ms) as u64))
+}
+
+/// First delay between failed `Worker::start` attempts, doubled up to
+/// [`STARTUP_RETRY_MAX`].
+const STARTUP_RETRY_BASE: Duration = Duration::from_secs(1);
+const STARTUP_RETRY_MAX: Duration = Duration::from_secs(30);
+
+/// Calls `Worker::start` until it succeeds, backing off between attempts.
+/// Returns `false` if shutdown is requested first, abandoning any attempt in
+/// progress.
+async fn start_worker(worker: &dyn Worker, shutdown: &CancellationToken) -> bool {
+    let mut delay = STARTUP_RETRY_BASE;
+
+    loop {
+        let result = tokio::select! {
+            biased;
+            _ = shutdown.cancelled() => return false,
+            result = worker.start() => result,
+        };
+        match result {
+            Ok(()) => return true,
+            Err(e) => warn!("Startup failed, retrying in {:?}: {}", delay, e),
+        }
+
+        tokio::select! {
+            biased;
+            _ = shutdown.cancelled() => return false,
+            _ = sleep(delay) => {}
+        }
+        delay = (delay * 2).min(STARTUP_RETRY_MAX);
+    }
+}
+
+/// Why the work loop returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stopped {
+    /// The shutdown token was cancelled.
+    Shutdown,
+    /// The worker was idle for `idle_shutdown_ticks` ticks in a row.
+    Idle,
+    /// The worker failed `max_consecutive_failures` ticks in a row.
+    Failing,
+}
+
+/// Work loop counters, shared so other tasks (the health server, say) can
+/// read them while the loop runs. Updates are lock-free atomic increments.
+#[derive(Default)]
+pub struct TickCounters {
+    ticks: AtomicU64,
+    succeeded: AtomicU64,
+    failed: AtomicU64,
+    progress: Progress,
+}
+
+/// Point-in-time copy of [`TickCounters`].
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickSnapshot {
+    pub ticks: u64,
+    /// Ticks whose work succeeded, idle ones included.
+    pub succeeded: u64,
+    pub failed: u64,
+    /// Percent complete of the tick in progress, if its worker reports it.
+    pub progress: Option<u8>,
+}
+
+impl TickCounters {
+    pub fn snapshot(&self) -> TickSnapshot {
+        TickSnapshot {
+            ticks: self.ticks.load(Ordering::Relaxed),
+            succeeded: self.succeeded.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            progress: self.progress.percent(),
+        }
+    }
+}
+
+/// How often progress of a long-running tick is logged.
+const PROGRESS_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Runs one tick's work, logging its reported progress every
+/// [`PROGRESS_LOG_INTERVAL`] while it runs.
+async fn perform_tick(
+    worker: &dyn Worker,
+    counter: u64,
+    progress: &Progress,
+) -> Result<Outcome, crate::worker::WorkError> {
+    progress.clear();
+    let work = worker.perform_work_with_progress(counter, progress);
+    tokio::pin!(work);
+    let mut log_progress = interval_at(
+        Instant::now() + PROGRESS_LOG_INTERVAL,
+        PROGRESS_LOG_INTERVAL,
+    );
+
+    let result = loop {
+        tokio::select! {
+            result = &mut work => break result,
+            _ = log_progress.tick() => {
+                if let Some(percent) = progress.percent() {
+                    info!("Daemon tick #{} is {}% done", counter, percent);
+                }
+            }
+        }
+    };
+    progress.clear();
+    result
+}
+
+/// Consecutive idle and failed iterations, checked against the stop limits
+/// after each one.
+#[derive(Default)]
+struct Streaks {
+    idle: u32,
+    failures: u32,
+}
+
+impl Streaks {
+    /// Counts the result of an iteration in `counters` and the streaks.
+    fn record(
+        &mut self,
+        counters: &TickCounters,
+        result: Result<Outcome, crate::worker::WorkError>,
+    ) {
+        let outcome = if result.is_ok() {
+            &counters.succeeded
+        } else {
+            &counters.failed
+        };
+        outcome.fetch_add(1, Ordering::Relaxed);
+
+        match result {
+            Ok(Outcome::Worked) => {
+                self.idle = 0;
+                self.failures = 0;
+                info!("Work completed successfully");
+            }
+            Ok(Outcome::Idle) => self.record_idle(),
+            Err(e) => {
+                self.idle = 0;
+                self.failures += 1;
+                error!("Work failed: {}", e);
+            }
+        }
+    }
+
+    fn record_idle(&mut self) {
+        self.idle += 1;
+        self.failures = 0;
+        info!("Nothing to do ({} idle ticks in a row)", self.idle);
+    }
+
+    /// Whether a streak has reached its limit in `config`.
+    fn stop(&self, config: &Config) -> Option<Stopped> {
+        if config
+            .max_consecutive_failures
+            .is_some_and(|limit| self.failures >= limit)
+        {
+            error!("Work failed {} times in a row, giving up", self.failures);
+            return Some(Stopped::Failing);
+        }
+        if config
+            .idle_shutdown_ticks
+            .is_some_and(|limit| self.idle >= limit)
+        {
+            info!("Idle for {} ticks, shutting down", self.idle);
+            return Some(Stopped::Idle);
+        }
+        None
+    }
+}
+
+/// Starts `worker` and waits out `startup_delay`. Returns `false` if
+/// shutdown is requested first.
+async fn prepare(worker: &dyn Worker, config: &Config, shutdown: &CancellationToken) -> bool {
+    if !start_worker(worker, shutdown).await {
+        info!("Shutdown requested during startup, exiting without running work");
+        return false;
+    }
+
+    if !config.startup_delay.is_zero() {
+        info!("Waiting {:?} before the first tick", config.startup_delay);
+        tokio::select! {
+            biased;
+            _ = shutdown.cancelled() => {
+                info!("Shutdown requested during the startup delay, exiting without running work");
+                return false;
+            }
+            _ = sleep(config.startup_delay) => {}
+        }
+    }
+    true
+}
+
+/// Main daemon work loop: starts `worker`, waits out `startup_delay`, then
+/// runs it on the configured schedule, counting ticks in `counters`, until
+/// `shutdown` is cancelled, until the worker has been idle for
+/// `idle_shutdown_ticks` ticks in a row, or until it has failed
+/// `max_consecutive_failures` ticks in a row. An iteration already in
+/// progress is allowed to finish.
+///
+/// The delay and schedule come from `config` at startup; the stop limits are
+/// re-read from a fresh snapshot after every tick, so a reload takes effect
+/// from the next tick on.
+pub async fn run(
+    worker: Arc<dyn Worker>,
+    config: SharedConfig,
+    clock: Arc<dyn Clock>,
+    counters: Arc<TickCounters>,
+    shutdown: CancellationToken,
+) -> Stopped {
+    let startup = config.load();
+    if !prepare(worker.as_ref(), &startup, &shutdown).await {
+        return Stopped::Shutdown;
+    }
+
+    let mut schedule = Schedule::new(&startup, clock);
+    let mut streaks = Streaks::default();
+
+    info!("Daemon is running...");
+
+    loop {
+        tokio::select! {
+            _ = schedule.tick() => {
+                let counter = counters.ticks.fetch_add(1, Ordering::Relaxed) + 1;
+                info!("Daemon tick #{} - performing work...", counter);
+
+                let result = perform_tick(worker.as_ref(), counter, &counters.progress).await;
+                streaks.record(&counters, result);
+                if let Some(stopped) = streaks.stop(&config.load()) {
+                    return stopped;
+                }
+            }
+            _ = shutdown.cancelled() => {
+                info!("Shutdown signal received, stopping daemon...");
+                return Stopped::Shutdown;
+            }
+        }
+    }
+}
+
+/// First wait before polling a [`JobSource`] again after finding it empty
+/// (or failing to reach it), doubled up to [`POLL_BACKOFF_MAX`].
+const POLL_BACKOFF_BASE: Duration = Duration::from_millis(100);
+const POLL_BACKOFF_MAX: Duration = Duration::from_secs(5);
+
+/// Work loop for queue mode (`JOB_SOURCE`): like [`run`], but instead of
+/// ticking on a schedule it takes jobs from `source` one at a time, runs
+/// each as the worker's iteration and acknowledges it once it succeeded.
+/// Failed jobs are left unacknowledged for the source to redeliver.
+///
+/// Polls that find the queue empty back off from [`POLL_BACKOFF_BASE`] to
+/// [`POLL_BACKOFF_MAX`] and count as idle ticks towards
+/// `idle_shutdown_ticks`. The schedule settings don't apply.
+pub async fn run_jobs(
+    worker: Arc<dyn Worker>,
+    source: Arc<dyn JobSource>,
+    config: SharedConfig,
+    counters: Arc<TickCounters>,
+    shutdown: CancellationToken,
+) -> Stopped {
+    if !prepare(worker.as_ref(), &config.load(), &shutdown).await {
+        return Stopped::Shutdown;
+    }
+
+    let mut streaks = Streaks::default();
+    let mut backoff = POLL_BACKOFF_BASE;
+
+    info!("Daemon is consuming jobs...");
+
+    loop {
+        let fetched = tokio::select! {
+            biased;
+            _ = shutdown.cancelled() => {
+                info!("Shutdown signal received, stopping daemon...");
+                return Stopped::Shutdown;
+            }
+            fetched = source.fetch() => fetched,
+        };
+
+        let got_job = matches!(fetched, Ok(Some(_)));
+        match fetched {
+            Ok(Some(job)) => {
+                backoff = POLL_BACKOFF_BASE;
+                counters.ticks.fetch_add(1, Ordering::Relaxed);
+                info!("Running job {}...", job);
+
+                let result = perform_tick(worker.as_ref(), job, &counters.progress).await;
+                if result.is_ok() {
+                    if let Err(e) = source.ack(job).await {
+                        warn!("Failed to acknowledge job {}: {}", job, e);
+                    }
+                }
+                streaks.record(&counters, result);
+            }
+            Ok(None) => streaks.record_idle(),
+            Err(e) => warn!("Failed to fetch a job, retrying in {:?}: {}", backoff, e),
+        }
+        if let Some(stopped) = streaks.stop(&config.load()) {
+            return stopped;
+        }
+
+        if !got_job {
+            tokio::select! {
+                biased;
+                _ = shutdown.cancelled() => {
+                    info!("Shutdown signal received, stopping daemon...");
+                    return Stopped::Shutdown;
+                }
+                _ = sleep(backoff) => {}
+            }
+            backoff = (backoff * 2).min(POLL_BACKOFF_MAX);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::WorkError;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use tokio::sync::mpsc;
+
+    /// Wall clock that starts at `start` and advances with tokio's (paused)
+    /// test time.
+    struct FakeClock {
+        start: SystemTime,
+        origin: tokio::time::Instant,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> SystemTime {
+            self.start + self.origin.elapsed()
+        }
+    }
+
+    /// [`FakeClock`] that can be stepped forward or back, like an NTP
+    /// correction.
+    struct JumpingClock {
+        inner: FakeClock,
+        jumped: std::sync::Mutex<Duration>,
+        rewound: std::sync::Mutex<Duration>,
+    }
+
+    impl Clock for JumpingClock {
+        fn now(&self) -> SystemTime {
+            self.inner.now() + *self.jumped.lock().unwrap() - *self.rewound.lock().unwrap()
+        }
+    }
+
+    /// Records ticks like [`TickRecorder`], stepping `clock` back by
+    /// `rewind` during the first one.
+    struct RewindingRecorder {
+        ticks: mpsc::UnboundedSender<tokio::time::Instant>,
+        clock: Arc<JumpingClock>,
+        rewind: Duration,
+    }
+
+    #[async_trait]
+    impl Worker for RewindingRecorder {
+        async fn perform_work(&self, iteration: u64) -> Result<Outcome, WorkError> {
+            if iteration == 1 {
+                *self.clock.rewound.lock().unwrap() = self.rewind;
+            }
+            let _ = self.ticks.send(tokio::time::Instant::now());
+            Ok(Outcome::Worked)
+        }
+    }
+
+    struct TickRecorder(mpsc::UnboundedSender<tokio::time::Instant>);
+
+    #[async_trait]
+    impl Worker for TickRecorder {
+        async fn perform_work(&self, _iteration: u64) -> Result<Outcome, WorkError> {
+            let _ = self.0.send(tokio::time::Instant::now());
+            Ok(Outcome::Worked)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn aligned_ticks_fire_on_wall_clock_boundaries() {
+        let origin = tokio::time::Instant::now();
+        // 125s past a minute boundary
+        let clock = Arc::new(FakeClock {
+            start: UNIX_EPOCH + Duration::from_secs(60 * 1_000_000 + 125),
+            origin,
+        });
+        let config = Config {
+            tick_interval: Duration::from_secs(60),
+            tick_align: true,
+            ..Config::default()
+        };
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let shutdown = CancellationToken::new();
+
+        let task = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move {
+                run(
+                    Arc::new(TickRecorder(tx)),
+                    config.into(),
+                    clock,
+                    Arc::default(),
+                    shutdown,
+                )
+                .await
+            }
+        });
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first - origin, Duration::from_secs(55));
+        let second = rx.recv().await.unwrap();
+        assert_eq!(second - origin, Duration::from_secs(115));
+
+        shutdown.cancel();
+        task.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_clock_jump_realigns_without_catch_up_ticks() {
+        let origin = tokio::time::Instant::now();
+        // 125s past a minute boundary
+        let clock = Arc::new(JumpingClock {
+            inner: FakeClock {
+                start: UNIX_EPOCH + Duration::from_secs(60 * 1_000_000 + 125),
+                origin,
+            },
+            jumped: Default::default(),
+            rewound: Default::default(),
+        });
+        let config = Config {
+            tick_interval: Duration::from_secs(60),
+            tick_align: true,
+            ..Config::default()
+        };
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let shutdown = CancellationToken::new();
+
+        let task = tokio::spawn({
+            let (clock, shutdown) = (clock.clone(), shutdown.clone());
+            async move {
+                run(
+                    Arc::new(TickRecorder(tx)),
+                    config.into(),
+                    clock,
+                    Arc::default(),
+                    shutdown,
+                )
+                .await
+            }
+        });
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first - origin, Duration::from_secs(55));
+        // Ten and a half minutes forward while waiting for the next boundary
+        sleep(Duration::from_secs(1)).await;
+        *clock.jumped.lock().unwrap() = Duration::from_secs(630);
+
+        // Waking at the old boundary finds the clock 30s past a new one; the
+        // next tick waits for the one after rather than firing twice
+        let ticks = [rx.recv().await.unwrap(), rx.recv().await.unwrap()];
+        assert_eq!(
+            ticks.map(|tick| tick - origin),
+            [Duration::from_secs(145), Duration::from_secs(205)]
+        );
+
+        shutdown.cancel();
+        task.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_backward_jump_realigns_without_skipping_ticks() {
+        let origin = tokio::time::Instant::now();
+        // 125s past a minute boundary
+        let clock = Arc::new(JumpingClock {
+            inner: FakeClock {
+                start: UNIX_EPOCH + Duration::from_secs(60 * 1_000_000 + 125),
+                origin,
+            },
+            jumped: Default::default(),
+            rewound: Default::default(),
+        });
+        let config = Config {
+            tick_interval: Duration::from_secs(60),
+            tick_align: true,
+            ..Config::default()
+        };
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let shutdown = CancellationToken::new();
+        // Ten and a half minutes back during the first tick
+        let worker = Arc::new(RewindingRecorder {
+            ticks: tx,
+            clock: clock.clone(),
+            rewind: Duration::from_secs(630),
+        });
+
+        let task = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { run(worker, config.into(), clock, Arc::default(), shutdown).await }
+        });
+
+        // The clock reads 30s before a boundary after the first tick; the
+        // next tick fires on it instead of waiting out the ten minutes
+        // already ticked through, or the boundary after
+        let ticks = [
+            rx.recv().await.unwrap(),
+            rx.recv().await.unwrap(),
+            rx.recv().await.unwrap(),
+        ];
+        assert_eq!(
+            ticks.map(|tick| tick - origin),
+            [55, 85, 145].map(Duration::from_secs)
+        );
+
+        shutdown.cancel();
+        task.await.unwrap();
+    }
+
+    #[test]
+    fn boundary_is_strictly_after_the_last_tick() {
+        let period = Duration::from_secs(60);
+        let on_boundary = UNIX_EPOCH + Duration::from_secs(120);
+
+        assert_eq!(
+            next_boundary(on_boundary, period, None),
+            (120_000, Duration::ZERO)
+        );
+        assert_eq!(
+            next_boundary(on_boundary, period, Some(120_000)),
+            (180_000, Duration::from_secs(60))
+        );
+    }
+
+    /// Has work for the first `busy_until` iterations, then goes idle.
+    struct DrainingWorker {
+        busy_until: u64,
+        calls: AtomicU64,
+    }
+
+    #[async_trait]
+    impl Worker for DrainingWorker {
+        async fn perform_work(&self, iteration: u64) -> Result<Outcome, WorkError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(if iteration <= self.busy_until {
+                Outcome::Worked
+            } else {
+                Outcome::Idle
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_after_configured_idle_ticks() {
+        let worker = Arc::new(DrainingWorker {
+            busy_until: 2,
+            calls: AtomicU64::new(0),
+        });
+        let config = Config {
+            tick_interval: Duration::from_secs(1),
+            idle_shutdown_ticks: Some(3),
+            ..Config::default()
+        };
+
+        // Returns without the shutdown token ever being cancelled
+        let stopped = run(
+            worker.clone(),
+            config.into(),
+            Arc::new(crate::clock::SystemClock),
+            Arc::default(),
+            CancellationToken::new(),
+        )
+        .await;
+        assert_eq!(stopped, Stopped::Idle);
+
+        assert_eq!(worker.calls.load(Ordering::SeqCst), 5);
+    }
+
+    struct FailingWorker;
+
+    #[async_trait]
+    impl Worker for FailingWorker {
+        async fn perform_work(&self, _iteration: u64) -> Result<Outcome, WorkError> {
+            Err("downstream unavailable".into())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_after_configured_consecutive_failures() {
+        let config = Config {
+            tick_interval: Duration::from_secs(1),
+            max_consecutive_failures: Some(3),
+            ..Config::default()
+        };
+        let started = tokio::time::Instant::now();
+
+        let stopped = run(
+            Arc::new(FailingWorker),
+            config.into(),
+            Arc::new(crate::clock::SystemClock),
+            Arc::default(),
+            CancellationToken::new(),
+        )
+        .await;
+        assert_eq!(stopped, Stopped::Failing);
+        // Ticks at 0s, 1s and 2s
+        assert_eq!(started.elapsed(), Duration::from_secs(2));
+    }
+
+    /// Never manages to start; counts attempts and any work it is given.
+    #[derive(Default)]
+    struct UnreachableDependency {
+        start_attempts: AtomicU64,
+        work_runs: AtomicU64,
+    }
+
+    #[async_trait]
+    impl Worker for UnreachableDependency {
+        async fn start(&self) -> Result<(), WorkError> {
+            self.start_attempts.fetch_add(1, Ordering::SeqCst);
+            Err("connection refused".into())
+        }
+
+        async fn perform_work(&self, _iteration: u64) -> Result<Outcome, WorkError> {
+            self.work_runs.fetch_add(1, Ordering::SeqCst);
+            Ok(Outcome::Worked)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_during_startup_exits_without_running_work() {
+        let worker = Arc::new(UnreachableDependency::default());
+        let shutdown = CancellationToken::new();
+        let config = Config::default();
+
+        let task = tokio::spawn({
+            let worker = worker.clone();
+            let shutdown = shutdown.clone();
+            async move {
+                run(
+                    worker,
+                    config.into(),
+                    Arc::new(crate::clock::SystemClock),
+                    Arc::default(),
+                    shutdown,
+                )
+                .await
+            }
+        });
+
+        // Attempts at 0s, 1s and 3s; shut down while backing off after that
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        shutdown.cancel();
+        tokio::time::timeout(Duration::from_secs(1), task)
+            .await
+            .expect("daemon should exit promptly")
+            .unwrap();
+
+        assert_eq!(worker.start_attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(worker.work_runs.load(Ordering::SeqCst), 0);
+    }
+
+    /// Fails every third iteration.
+    struct FailsEveryThird;
+
+    #[async_trait]
+    impl Worker for FailsEveryThird {
+        async fn perform_work(&self, iteration: u64) -> Result<Outcome, WorkError> {
+            if iteration.is_multiple_of(3) {
+                Err("every third".into())
+            } else {
+                Ok(Outcome::Worked)
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn counters_are_readable_from_other_tasks() {
+        let counters = Arc::new(TickCounters::default());
+        let shutdown = CancellationToken::new();
+        let config = Config {
+            tick_interval: Duration::from_secs(1),
+            ..Config::default()
+        };
+
+        let task = tokio::spawn({
+            let counters = counters.clone();
+            let shutdown = shutdown.clone();
+            async move {
+                run(
+                    Arc::new(FailsEveryThird),
+                    config.into(),
+                    Arc::new(crate::clock::SystemClock),
+                    counters,
+                    shutdown,
+                )
+                .await
+            }
+        });
+
+        // Ticks at 0s..=6s
+        tokio::time::sleep(Duration::from_millis(6_500)).await;
+        let snapshot = tokio::spawn({
+            let counters = counters.clone();
+            async move { counters.snapshot() }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            snapshot,
+            TickSnapshot {
+                ticks: 7,
+                succeeded: 5,
+                failed: 2,
+                progress: None,
+            }
+        );
+
+        shutdown.cancel();
+        task.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn first_tick_waits_for_the_startup_delay() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let config = Config {
+            startup_delay: Duration::from_secs(30),
+            ..Config::default()
+        };
+        let started = tokio::time::Instant::now();
+        let shutdown = CancellationToken::new();
+
+        let task = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move {
+                run(
+                    Arc::new(TickRecorder(tx)),
+                    config.into(),
+                    Arc::new(crate::clock::SystemClock),
+                    Arc::default(),
+                    shutdown,
+                )
+                .await
+            }
+        });
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first - started, Duration::from_secs(30));
+
+        shutdown.cancel();
+        task.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_during_the_startup_delay_runs_no_work() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let config = Config {
+            startup_delay: Duration::from_secs(30),
+            ..Config::default()
+        };
+        let shutdown = CancellationToken::new();
+
+        let task = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move {
+                run(
+                    Arc::new(TickRecorder(tx)),
+                    config.into(),
+                    Arc::new(crate::clock::SystemClock),
+                    Arc::default(),
+                    shutdown,
+                )
+                .await
+            }
+        });
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        shutdown.cancel();
+        let stopped = tokio::time::timeout(Duration::from_secs(1), task)
+            .await
+            .expect("daemon should exit promptly")
+            .unwrap();
+        assert_eq!(stopped, Stopped::Shutdown);
+        assert!(rx.recv().await.is_none(), "no work may run");
+    }
+}
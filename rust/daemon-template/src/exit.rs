@@ -0,0 +1,67 @@
+use std::process::ExitCode;
+
+use crate::daemon::Stopped;
+
+// Exit codes per [`Termination`]; change them here if a supervisor expects
+// others. Keep the README's Exit Codes table in step.
+pub const EXIT_CLEAN: u8 = 0;
+pub const EXIT_CONFIG_ERROR: u8 = 1;
+pub const EXIT_REPEATED_FAILURES: u8 = 2;
+pub const EXIT_FORCED: u8 = 3;
+
+/// How the daemon ended, which decides the exit code init systems and batch
+/// schedulers see.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    /// Stopped on request or because it ran out of work.
+    Clean,
+    /// Could not start because of bad configuration or an unusable resource
+    /// it names, such as an admin address that cannot be bound.
+    ConfigError,
+    /// The worker failed `MAX_CONSECUTIVE_FAILURES` times in a row.
+    RepeatedFailures,
+    /// Shutdown was forced: a second signal cut the drain short, or the
+    /// work loop ran past `SHUTDOWN_TIMEOUT_SECS` and was abandoned.
+    Forced,
+}
+
+impl Termination {
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Clean => EXIT_CLEAN,
+            Self::ConfigError => EXIT_CONFIG_ERROR,
+            Self::RepeatedFailures => EXIT_REPEATED_FAILURES,
+            Self::Forced => EXIT_FORCED,
+        }
+    }
+}
+
+impl From<Stopped> for Termination {
+    fn from(stopped: Stopped) -> Self {
+        match stopped {
+            Stopped::Shutdown | Stopped::Idle => Self::Clean,
+            Stopped::Failing => Self::RepeatedFailures,
+        }
+    }
+}
+
+impl From<Termination> for ExitCode {
+    fn from(termination: Termination) -> Self {
+        ExitCode::from(termination.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_termination_maps_to_its_exit_code() {
+        let code = |stopped: Stopped| Termination::from(stopped).code();
+        assert_eq!(code(Stopped::Shutdown), 0);
+        assert_eq!(code(Stopped::Idle), 0);
+        assert_eq!(code(Stopped::Failing), 2);
+        assert_eq!(Termination::ConfigError.code(), 1);
+        assert_eq!(Termination::Forced.code(), 3);
+    }
+}
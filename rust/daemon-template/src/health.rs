@@ -0,0 +1,140 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json},
+    routing::get,
+    Router,
+};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::daemon::{TickCounters, TickSnapshot};
+
+#[derive(Clone)]
+struct HealthState {
+    counters: Arc<TickCounters>,
+    draining: CancellationToken,
+}
+
+/// Probe endpoint served on `HEALTH_ADDR`. `GET /healthz` answers `200 ok`
+/// until `draining` is cancelled, then `503 draining`, so load balancers
+/// move away while the work loop finishes. `GET /stats` reports the work
+/// loop's tick counters.
+pub fn router(counters: Arc<TickCounters>, draining: CancellationToken) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/stats", get(stats))
+        .with_state(HealthState { counters, draining })
+}
+
+async fn healthz(State(state): State<HealthState>) -> impl IntoResponse {
+    if state.draining.is_cancelled() {
+        (StatusCode::SERVICE_UNAVAILABLE, "draining")
+    } else {
+        (StatusCode::OK, "ok")
+    }
+}
+
+async fn stats(State(state): State<HealthState>) -> Json<TickSnapshot> {
+    Json(state.counters.snapshot())
+}
+
+/// Serves `router` until `stop` is cancelled, then stops accepting
+/// connections and waits for in-flight probes.
+pub async fn serve(
+    listener: TcpListener,
+    router: Router,
+    stop: CancellationToken,
+) -> std::io::Result<()> {
+    info!("Health server listening on {}", listener.local_addr()?);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(stop.cancelled_owned())
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::worker::{Outcome, Progress, WorkError, Worker};
+    use async_trait::async_trait;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use std::time::Duration;
+    use tokio::sync::Notify;
+    use tower::ServiceExt;
+
+    /// Reports halfway, then waits to be released before finishing.
+    #[derive(Default)]
+    struct HalfwayWorker {
+        halfway: Notify,
+        release: Notify,
+    }
+
+    #[async_trait]
+    impl Worker for HalfwayWorker {
+        async fn perform_work(&self, iteration: u64) -> Result<Outcome, WorkError> {
+            self.perform_work_with_progress(iteration, &Progress::default())
+                .await
+        }
+
+        async fn perform_work_with_progress(
+            &self,
+            _iteration: u64,
+            progress: &Progress,
+        ) -> Result<Outcome, WorkError> {
+            progress.report(50);
+            self.halfway.notify_one();
+            self.release.notified().await;
+            progress.report(100);
+            Ok(Outcome::Worked)
+        }
+    }
+
+    async fn stats(counters: &Arc<TickCounters>) -> serde_json::Value {
+        let response = router(counters.clone(), CancellationToken::new())
+            .oneshot(Request::get("/stats").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stats_show_the_progress_of_the_running_tick() {
+        let worker = Arc::new(HalfwayWorker::default());
+        let counters = Arc::new(TickCounters::default());
+        let shutdown = CancellationToken::new();
+        let task = tokio::spawn({
+            let (worker, counters, shutdown) = (worker.clone(), counters.clone(), shutdown.clone());
+            async move {
+                let config = Config {
+                    tick_interval: Duration::from_secs(60),
+                    ..Config::default()
+                };
+                crate::daemon::run(
+                    worker,
+                    config.into(),
+                    Arc::new(crate::clock::SystemClock),
+                    counters,
+                    shutdown,
+                )
+                .await
+            }
+        });
+
+        worker.halfway.notified().await;
+        let running = stats(&counters).await;
+        assert_eq!(running["ticks"], 1);
+        assert_eq!(running["progress"], 50);
+
+        worker.release.notify_one();
+        shutdown.cancel();
+        task.await.unwrap();
+        let done = stats(&counters).await;
+        assert_eq!(done["succeeded"], 1);
+        assert_eq!(done["progress"], serde_json::Value::Null);
+    }
+}
@@ -1,92 +1,209 @@
-use std::time::Duration;
-use signal_hook::consts::SIGTERM;
+mod adaptive;
+mod admin;
+mod clock;
+mod config;
+mod daemon;
+mod exit;
+mod health;
+mod pool;
+mod publish;
+mod report;
+mod scheduler;
+mod shutdown;
+mod source;
+mod state;
+mod worker;
+mod writable;
+
+use signal_hook::consts::{SIGINT, SIGTERM};
 use signal_hook_tokio::Signals;
-use futures::stream::StreamExt;
-use tokio::time::{sleep, interval};
-use tracing::{info, warn, error};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use clock::SystemClock;
+use config::Config;
+use daemon::{Stopped, TickCounters};
+use exit::Termination;
+use publish::{PublishingWorker, RedisBroker};
+use report::{CountingWorker, RunStats};
+use shutdown::{Component, Drained, Shutdown};
+use source::{JobSource, RedisListSource};
+use worker::{ExampleWorker, Worker};
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> ExitCode {
     // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter("info")
-        .init();
+    tracing_subscriber::fmt().with_env_filter("info").init();
 
     info!("Starting daemon...");
 
+    let termination = run().await.unwrap_or_else(|e| {
+        error!("Failed to start: {}", e);
+        Termination::ConfigError
+    });
+    info!(
+        "Exiting with code {} ({:?})",
+        termination.code(),
+        termination
+    );
+    termination.into()
+}
+
+/// Runs the daemon to completion. Errors are startup failures, reported as
+/// [`Termination::ConfigError`].
+async fn run() -> Result<Termination, Box<dyn std::error::Error>> {
+    let mut config = Config::from_env();
+    writable::disable_unwritable(&mut config)?;
+
+    let mut state = match &config.state_file {
+        Some(path) => state::load(path, config.strict_state)?,
+        None => state::DaemonState::default(),
+    };
+    if state.total_work_runs > 0 {
+        info!(
+            "Resuming after {} work runs (last stopped by {})",
+            state.total_work_runs,
+            state.last_trigger.as_deref().unwrap_or("an unclean exit")
+        );
+    }
+    let state_file = config.state_file.clone();
+
+    let shutdown = Shutdown::new();
+
     // Set up signal handling
-    let signals = Signals::new(&[SIGTERM])?;
+    let signals = Signals::new([SIGTERM, SIGINT])?;
 
     // Spawn signal handling task
-    let signal_task = tokio::spawn(handle_signals(signals));
-
-    // Create shutdown channel
-    let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::channel::<()>(1);
-
-    // Main daemon work loop
-    let daemon_task = tokio::spawn(async move {
-        let mut tick_interval = interval(Duration::from_secs(10));
-        let mut counter = 0;
-
-        info!("Daemon is running...");
-        
-        loop {
-            tokio::select! {
-                _ = tick_interval.tick() => {
-                    counter += 1;
-                    info!("Daemon tick #{} - performing work...", counter);
-                    
-                    // Simulate some work
-                    match perform_work(counter).await {
-                        Ok(_) => info!("Work completed successfully"),
-                        Err(e) => error!("Work failed: {}", e),
-                    }
-                }
-                _ = shutdown_rx.recv() => {
-                    info!("Shutdown signal received, stopping daemon...");
-                    break;
-                }
-            }
-        }
-    });
+    let mut signal_task = tokio::spawn(shutdown::handle_signals(signals, shutdown.clone()));
 
-    // Wait for either the signal handler or daemon task to complete
-    tokio::select! {
-        _ = signal_task => {
-            info!("Signal received, initiating shutdown...");
-            let _ = shutdown_tx.send(()).await;
-        }
-        _ = daemon_task => {
-            info!("Daemon task completed");
+    let stats = Arc::new(RunStats::default());
+    let mut worker: Arc<dyn Worker> =
+        Arc::new(CountingWorker::new(Arc::new(ExampleWorker), stats.clone()));
+
+    // Optionally publish every work result to a message broker
+    let mut publisher = None;
+    if let Some(url) = &config.broker_url {
+        let broker = RedisBroker::from_url(url)?;
+        let (tx, rx) = tokio::sync::mpsc::channel(publish::QUEUE_CAPACITY);
+        worker = Arc::new(PublishingWorker::new(worker, tx));
+        publisher = Some(tokio::spawn(publish::run_publisher(
+            Arc::new(broker),
+            config.broker_subject.clone(),
+            rx,
+        )));
+        info!(
+            "Publishing work results to {} ({})",
+            url, config.broker_subject
+        );
+    }
+
+    // Optional admin server for triggering work on demand
+    if let Some(addr) = &config.admin_addr {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        if config.admin_token.is_none() {
+            warn!("ADMIN_TOKEN is not set: the admin server will refuse every request");
         }
+        let router = admin::router(worker.clone(), config.admin_token.clone());
+        tokio::spawn(admin::serve(listener, router, shutdown.token()));
     }
 
-    info!("Daemon shutdown complete");
-    Ok(())
-}
+    // Optional health probe server, reporting not-ready while the work loop
+    // drains and stopped after it
+    let counters = Arc::new(TickCounters::default());
+    let tick_counters = counters.clone();
+    let draining = CancellationToken::new();
+    let mut health = None;
+    if let Some(addr) = &config.health_addr {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let router = health::router(counters.clone(), draining.clone());
+        health = Some(Component::spawn("health server", |stop| {
+            health::serve(listener, router, stop)
+        }));
+    }
 
-async fn handle_signals(mut signals: Signals) {
-    while let Some(signal) = signals.next().await {
-        match signal {
-            SIGTERM => {
-                info!("Received SIGTERM, preparing to shutdown...");
-                break;
+    // Main daemon work loop, ticking on a schedule or taking jobs from a queue
+    let source: Option<Arc<dyn JobSource>> = match &config.job_source {
+        Some(url) => {
+            info!("Taking jobs from {}", url);
+            Some(Arc::new(RedisListSource::from_url(url)?))
+        }
+        None => None,
+    };
+    let report_file = config.shutdown_report_file.clone();
+    let shutdown_timeout = config.shutdown_timeout;
+    let mut work = Component::spawn("work loop", |stop| async move {
+        match source {
+            Some(source) => daemon::run_jobs(worker, source, config.into(), counters, stop).await,
+            None => daemon::run(worker, config.into(), Arc::new(SystemClock), counters, stop).await,
+        }
+    });
+
+    // The first signal starts an ordered drain bounded by SHUTDOWN_TIMEOUT_SECS;
+    // the signal task only completes if another signal arrives before the
+    // daemon exits, which forces the exit.
+    let token = shutdown.token();
+    let drained = tokio::select! {
+        stopped = work.join() => {
+            info!("Daemon task completed");
+            let deadline = tokio::time::Instant::now() + shutdown_timeout;
+            if let Some(health) = health {
+                shutdown::stop_health(health, deadline).await;
             }
-            _ => {
-                warn!("Received unexpected signal: {}", signal);
+            Some(Drained::Stopped(stopped.unwrap_or(Stopped::Failing)))
+        }
+        _ = token.cancelled() => {
+            let deadline = tokio::time::Instant::now() + shutdown_timeout;
+            let drain = shutdown::stop_in_order(&draining, health, work, deadline);
+            shutdown::unless_forced(&mut signal_task, drain).await
+        }
+        _ = &mut signal_task => None,
+    };
+    let Some(drained) = drained else {
+        error!("Forced shutdown, abandoning in-flight work");
+        std::process::exit(Termination::Forced.code().into());
+    };
+    // A drain that ran out of time still exits as forced, but only after
+    // the report and state save below
+    let (stopped, termination) = match drained {
+        Drained::Stopped(stopped) => (stopped, stopped.into()),
+        Drained::Abandoned => {
+            error!("Work loop abandoned at the shutdown deadline");
+            (Stopped::Shutdown, Termination::Forced)
+        }
+    };
+
+    // Give queued results a moment to reach the broker
+    if let Some(publisher) = publisher {
+        let flush = tokio::time::timeout(Duration::from_secs(5), publisher);
+        match shutdown::unless_forced(&mut signal_task, flush).await {
+            Some(Ok(_)) => {}
+            Some(Err(_)) => warn!("Gave up waiting for queued results to be published"),
+            None => {
+                error!("Forced shutdown, dropping unpublished results");
+                std::process::exit(Termination::Forced.code().into());
             }
         }
     }
-}
 
-async fn perform_work(iteration: u64) -> Result<(), Box<dyn std::error::Error>> {
-    // Simulate some async work
-    sleep(Duration::from_millis(100)).await;
-    
-    // Example: periodic maintenance, health checks, data processing, etc.
-    if iteration % 5 == 0 {
-        info!("Performing maintenance task at iteration {}", iteration);
+    let trigger = match stopped {
+        Stopped::Idle => "idle".to_string(),
+        Stopped::Failing => "repeated work failures".to_string(),
+        Stopped::Shutdown => shutdown.trigger().unwrap_or_else(|| "unknown".to_string()),
+    };
+    let report = stats.report(trigger, tick_counters.snapshot().ticks);
+    report::emit(&report, report_file.as_deref());
+
+    if let Some(path) = &state_file {
+        state.total_work_runs += report.work_runs;
+        state.last_trigger = Some(report.trigger);
+        if let Err(e) = state::save(path, &state) {
+            error!("Failed to save state: {}", e);
+        }
     }
-    
-    Ok(())
+
+    info!("Daemon shutdown complete");
+    Ok(termination)
 }
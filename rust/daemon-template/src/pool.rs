@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinSet;
+use tracing::{debug, error, info};
+
+use crate::worker::Worker;
+
+/// Runs queued jobs on a fixed number of worker tasks that all pull from one
+/// shared queue.
+///
+/// Jobs are not partitioned per task: whichever task is idle takes the next
+/// job, so one slow job holds up only the task running it while the others
+/// keep draining the queue. Each task counts the jobs it handled, readable
+/// through [`WorkerPool::processed`] and exported as the
+/// `pool_jobs_processed_total` counter labelled by `worker`.
+#[allow(dead_code)] // For daemons draining a job queue; `main` drives a single loop
+pub struct WorkerPool {
+    queue: mpsc::Sender<u64>,
+    processed: Arc<Vec<AtomicU64>>,
+    tasks: JoinSet<()>,
+}
+
+#[allow(dead_code)]
+impl WorkerPool {
+    /// Starts `size` tasks running `worker` on submitted jobs. At most
+    /// `capacity` jobs wait in the queue before [`WorkerPool::submit`] waits
+    /// for room.
+    pub fn spawn(worker: Arc<dyn Worker>, size: usize, capacity: usize) -> Self {
+        let (queue, jobs) = mpsc::channel(capacity);
+        let jobs = Arc::new(Mutex::new(jobs));
+        let processed: Arc<Vec<AtomicU64>> =
+            Arc::new((0..size).map(|_| AtomicU64::new(0)).collect());
+
+        let mut tasks = JoinSet::new();
+        for index in 0..size {
+            let (worker, jobs, processed) = (worker.clone(), jobs.clone(), processed.clone());
+            tasks.spawn(async move {
+                loop {
+                    // The lock is only held while waiting for the next job, so
+                    // an idle task takes it as soon as the previous holder
+                    // has one.
+                    let Some(job) = jobs.lock().await.recv().await else {
+                        break;
+                    };
+                    debug!("Pool worker {} running job {}", index, job);
+                    if let Err(e) = worker.perform_work(job).await {
+                        error!("Pool worker {} failed job {}: {}", index, job, e);
+                    }
+                    processed[index].fetch_add(1, Ordering::Relaxed);
+                    metrics::counter!("pool_jobs_processed_total", "worker" => index.to_string())
+                        .increment(1);
+                }
+            });
+        }
+
+        Self {
+            queue,
+            processed,
+            tasks,
+        }
+    }
+
+    /// Queues `job` for the next idle task. Waits while the queue is full.
+    pub async fn submit(&self, job: u64) {
+        // The receiving side lives as long as the tasks, which outlive `self`
+        let _ = self.queue.send(job).await;
+    }
+
+    /// Jobs handled so far by each task, failed ones included.
+    pub fn processed(&self) -> Vec<u64> {
+        self.processed
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /// Stops taking jobs, waits for the queued ones to be handled and
+    /// returns the final per-task counts.
+    pub async fn close(self) -> Vec<u64> {
+        let Self {
+            queue,
+            processed,
+            mut tasks,
+        } = self;
+        drop(queue);
+        while let Some(result) = tasks.join_next().await {
+            if let Err(e) = result {
+                error!("Pool worker panicked: {}", e);
+            }
+        }
+
+        let processed: Vec<u64> = processed
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect();
+        info!("Worker pool closed, jobs per worker: {:?}", processed);
+        processed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::{Outcome, WorkError};
+    use async_trait::async_trait;
+    use std::time::Duration;
+    use tokio::time::{sleep, Instant};
+
+    /// Job 1 takes a hundred times longer than the rest.
+    struct UnevenWorker;
+
+    #[async_trait]
+    impl Worker for UnevenWorker {
+        async fn perform_work(&self, job: u64) -> Result<Outcome, WorkError> {
+            let secs = if job == 1 { 100 } else { 1 };
+            sleep(Duration::from_secs(secs)).await;
+            Ok(Outcome::Worked)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_workers_take_jobs_a_slow_one_leaves_behind() {
+        let started = Instant::now();
+        let pool = WorkerPool::spawn(Arc::new(UnevenWorker), 2, 32);
+        for job in 1..=20 {
+            pool.submit(job).await;
+        }
+
+        let mut processed = pool.close().await;
+        processed.sort();
+        // Partitioned evenly, the worker stuck on job 1 would still have nine
+        // jobs queued behind it when the other finished its share
+        assert_eq!(processed, [1, 19]);
+        assert_eq!(started.elapsed(), Duration::from_secs(100));
+    }
+}
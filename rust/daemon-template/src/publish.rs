@@ -0,0 +1,256 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tracing::{error, warn};
+
+use crate::worker::{Outcome, Progress, WorkError, Worker};
+
+/// Results waiting to be published; when full, new results are dropped
+/// rather than slowing down the work loop.
+pub const QUEUE_CAPACITY: usize = 256;
+
+/// Attempts per message before it is dropped.
+const PUBLISH_ATTEMPTS: u32 = 3;
+const PUBLISH_RETRY_BASE: Duration = Duration::from_millis(200);
+
+/// A message broker results can be published to.
+#[async_trait]
+pub trait Broker: Send + Sync + 'static {
+    async fn publish(&self, subject: &str, payload: &[u8]) -> Result<(), WorkError>;
+}
+
+/// Publishes with Redis `PUBLISH` over a fresh connection per message.
+pub struct RedisBroker {
+    addr: String,
+}
+
+impl RedisBroker {
+    /// Accepts `redis://host:port`.
+    pub fn from_url(url: &str) -> Result<Self, String> {
+        match url.strip_prefix("redis://") {
+            Some(addr) if !addr.is_empty() => Ok(Self {
+                addr: addr.trim_end_matches('/').to_string(),
+            }),
+            _ => Err(format!(
+                "unsupported broker URL {url:?}, expected redis://host:port"
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl Broker for RedisBroker {
+    async fn publish(&self, subject: &str, payload: &[u8]) -> Result<(), WorkError> {
+        let mut command = format!(
+            "*3\r\n$7\r\nPUBLISH\r\n${}\r\n{}\r\n${}\r\n",
+            subject.len(),
+            subject,
+            payload.len()
+        )
+        .into_bytes();
+        command.extend_from_slice(payload);
+        command.extend_from_slice(b"\r\n");
+
+        let mut stream = TcpStream::connect(&self.addr).await?;
+        stream.write_all(&command).await?;
+
+        // Integer reply (`:<subscribers>\r\n`) on success, `-ERR ...` otherwise
+        let mut reply = [0u8; 128];
+        let read = stream.read(&mut reply).await?;
+        match reply[..read].first() {
+            Some(b':') => Ok(()),
+            _ => Err(format!(
+                "unexpected reply from broker: {:?}",
+                String::from_utf8_lossy(&reply[..read]).trim_end()
+            )
+            .into()),
+        }
+    }
+}
+
+/// What gets published after every work iteration.
+#[derive(Serialize, Debug)]
+pub struct WorkResult {
+    pub iteration: u64,
+    pub outcome: Option<Outcome>,
+    pub error: Option<String>,
+    pub finished_at_ms: u64,
+}
+
+/// Wraps a worker, queueing the result of every iteration for publishing.
+/// Queueing never blocks, so a slow or unreachable broker can't hold up the
+/// work loop.
+pub struct PublishingWorker {
+    inner: Arc<dyn Worker>,
+    results: mpsc::Sender<WorkResult>,
+}
+
+impl PublishingWorker {
+    pub fn new(inner: Arc<dyn Worker>, results: mpsc::Sender<WorkResult>) -> Self {
+        Self { inner, results }
+    }
+}
+
+#[async_trait]
+impl Worker for PublishingWorker {
+    async fn start(&self) -> Result<(), WorkError> {
+        self.inner.start().await
+    }
+
+    async fn perform_work(&self, iteration: u64) -> Result<Outcome, WorkError> {
+        self.perform_work_with_progress(iteration, &Progress::default())
+            .await
+    }
+
+    async fn perform_work_with_progress(
+        &self,
+        iteration: u64,
+        progress: &Progress,
+    ) -> Result<Outcome, WorkError> {
+        let result = self
+            .inner
+            .perform_work_with_progress(iteration, progress)
+            .await;
+
+        let message = WorkResult {
+            iteration,
+            outcome: result.as_ref().ok().copied(),
+            error: result.as_ref().err().map(ToString::to_string),
+            finished_at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        };
+        if self.results.try_send(message).is_err() {
+            warn!(
+                "Publish queue full, dropping result of iteration {}",
+                iteration
+            );
+        }
+
+        result
+    }
+}
+
+/// Publishes queued results as JSON until every sender is gone, retrying
+/// each message a few times before giving up on it.
+pub async fn run_publisher(
+    broker: Arc<dyn Broker>,
+    subject: String,
+    mut results: mpsc::Receiver<WorkResult>,
+) {
+    while let Some(result) = results.recv().await {
+        let payload = match serde_json::to_vec(&result) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Failed to serialize work result: {}", e);
+                continue;
+            }
+        };
+
+        let mut delay = PUBLISH_RETRY_BASE;
+        for attempt in 1..=PUBLISH_ATTEMPTS {
+            match broker.publish(&subject, &payload).await {
+                Ok(()) => break,
+                Err(e) if attempt == PUBLISH_ATTEMPTS => {
+                    error!(
+                        "Dropping result of iteration {} after {} attempts: {}",
+                        result.iteration, attempt, e
+                    );
+                }
+                Err(e) => {
+                    warn!("Publish failed, retrying in {:?}: {}", delay, e);
+                    sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tokio::net::TcpListener;
+
+    #[derive(Default)]
+    struct InMemoryBroker {
+        messages: Mutex<Vec<(String, serde_json::Value)>>,
+    }
+
+    #[async_trait]
+    impl Broker for InMemoryBroker {
+        async fn publish(&self, subject: &str, payload: &[u8]) -> Result<(), WorkError> {
+            let payload = serde_json::from_slice(payload)?;
+            self.messages
+                .lock()
+                .unwrap()
+                .push((subject.to_string(), payload));
+            Ok(())
+        }
+    }
+
+    struct Succeeds;
+
+    #[async_trait]
+    impl Worker for Succeeds {
+        async fn perform_work(&self, _iteration: u64) -> Result<Outcome, WorkError> {
+            Ok(Outcome::Worked)
+        }
+    }
+
+    #[tokio::test]
+    async fn successful_tick_result_is_published() {
+        let broker = Arc::new(InMemoryBroker::default());
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        let worker = PublishingWorker::new(Arc::new(Succeeds), tx);
+        let publisher = tokio::spawn(run_publisher(
+            broker.clone(),
+            "daemon.results".to_string(),
+            rx,
+        ));
+
+        assert_eq!(worker.perform_work(4).await.unwrap(), Outcome::Worked);
+        drop(worker);
+        publisher.await.unwrap();
+
+        let messages = broker.messages.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        let (subject, payload) = &messages[0];
+        assert_eq!(subject, "daemon.results");
+        assert_eq!(payload["iteration"], 4);
+        assert_eq!(payload["outcome"], "worked");
+        assert_eq!(payload["error"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn redis_broker_speaks_publish() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("redis://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 256];
+            let read = conn.read(&mut buf).await.unwrap();
+            conn.write_all(b":1\r\n").await.unwrap();
+            String::from_utf8(buf[..read].to_vec()).unwrap()
+        });
+
+        RedisBroker::from_url(&url)
+            .unwrap()
+            .publish("jobs", b"{}")
+            .await
+            .unwrap();
+        assert_eq!(
+            server.await.unwrap(),
+            "*3\r\n$7\r\nPUBLISH\r\n$4\r\njobs\r\n$2\r\n{}\r\n"
+        );
+
+        assert!(RedisBroker::from_url("nats://localhost:4222").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::worker::{Outcome, Progress, WorkError, Worker};
+
+/// Process-lifetime counters summarised in the shutdown report.
+pub struct RunStats {
+    started: Instant,
+    work_runs: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Default for RunStats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            work_runs: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Summary logged (and optionally written to `SHUTDOWN_REPORT_FILE`) once
+/// the work loop has stopped.
+#[derive(Serialize, Debug)]
+pub struct ShutdownReport {
+    pub uptime_secs: u64,
+    /// Ticks (or jobs) the work loop started.
+    pub ticks: u64,
+    /// Every run of the worker, manual ones through the admin server too.
+    pub work_runs: u64,
+    pub failures: u64,
+    pub trigger: String,
+}
+
+impl RunStats {
+    /// `ticks` comes from the work loop's [`TickCounters`](crate::daemon::TickCounters).
+    pub fn report(&self, trigger: impl Into<String>, ticks: u64) -> ShutdownReport {
+        ShutdownReport {
+            uptime_secs: self.started.elapsed().as_secs(),
+            ticks,
+            work_runs: self.work_runs.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            trigger: trigger.into(),
+        }
+    }
+}
+
+/// Wraps a worker, counting its runs (scheduled and manual) and failures.
+pub struct CountingWorker {
+    inner: Arc<dyn Worker>,
+    stats: Arc<RunStats>,
+}
+
+impl CountingWorker {
+    pub fn new(inner: Arc<dyn Worker>, stats: Arc<RunStats>) -> Self {
+        Self { inner, stats }
+    }
+}
+
+#[async_trait]
+impl Worker for CountingWorker {
+    async fn start(&self) -> Result<(), WorkError> {
+        self.inner.start().await
+    }
+
+    async fn perform_work(&self, iteration: u64) -> Result<Outcome, WorkError> {
+        self.perform_work_with_progress(iteration, &Progress::default())
+            .await
+    }
+
+    async fn perform_work_with_progress(
+        &self,
+        iteration: u64,
+        progress: &Progress,
+    ) -> Result<Outcome, WorkError> {
+        let result = self
+            .inner
+            .perform_work_with_progress(iteration, progress)
+            .await;
+        self.stats.work_runs.fetch_add(1, Ordering::Relaxed);
+        if result.is_err() {
+            self.stats.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+/// Logs the report and, when `path` is set, writes it there as JSON.
+/// Failing to write is logged but never fails the shutdown.
+pub fn emit(report: &ShutdownReport, path: Option<&Path>) {
+    info!(
+        uptime_secs = report.uptime_secs,
+        ticks = report.ticks,
+        work_runs = report.work_runs,
+        failures = report.failures,
+        trigger = %report.trigger,
+        "Shutdown report"
+    );
+
+    if let Some(path) = path {
+        let written = serde_json::to_vec_pretty(report)
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(path, json));
+        if let Err(e) = written {
+            warn!(
+                "Failed to write shutdown report to {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FailsOnOdd;
+
+    #[async_trait]
+    impl Worker for FailsOnOdd {
+        async fn perform_work(&self, iteration: u64) -> Result<Outcome, WorkError> {
+            if iteration % 2 == 1 {
+                Err("odd".into())
+            } else {
+                Ok(Outcome::Worked)
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn report_counts_runs_and_failures() {
+        let stats = Arc::new(RunStats::default());
+        let worker = CountingWorker::new(Arc::new(FailsOnOdd), stats.clone());
+        for iteration in 1..=3 {
+            let _ = worker.perform_work(iteration).await;
+        }
+
+        let report = stats.report("SIGTERM", 2);
+        assert_eq!(report.ticks, 2);
+        assert_eq!(report.work_runs, 3);
+        assert_eq!(report.failures, 2);
+        assert_eq!(report.trigger, "SIGTERM");
+
+        let path = std::env::temp_dir().join(format!("daemon-report-{}.json", std::process::id()));
+        emit(&report, Some(&path));
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written["ticks"], 2);
+        assert_eq!(written["work_runs"], 3);
+        assert_eq!(written["trigger"], "SIGTERM");
+    }
+}
@@ -0,0 +1,187 @@
+use futures::stream::{self, select_all, StreamExt};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::adaptive::ConcurrencyLimiter;
+use crate::config::{Config, OverflowPolicy};
+use crate::worker::Worker;
+
+/// A worker run every `every`, alongside the scheduler's other schedules.
+struct Schedule {
+    name: String,
+    every: Duration,
+    worker: Arc<dyn Worker>,
+}
+
+/// Runs several independent schedules while capping how many work units run
+/// at once across all of them (`max_concurrent_work`, or an adaptive limit
+/// below it; see [`ConcurrencyLimiter`]).
+///
+/// A tick that finds every slot taken either waits for one to free up or is
+/// skipped, per `work_overflow`. Either way a burst of schedules firing
+/// together can never exceed the cap.
+///
+/// `main` doesn't use this: its loop (`daemon::run`, or `daemon::run_jobs`
+/// with a job source) runs one work unit at a time, and none of the
+/// concurrency settings affect it.
+#[allow(dead_code)] // For daemons running several schedules; `main` drives a single loop
+pub struct Scheduler {
+    schedules: Vec<Schedule>,
+    limiter: Arc<ConcurrencyLimiter>,
+    overflow: OverflowPolicy,
+}
+
+#[allow(dead_code)]
+impl Scheduler {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            schedules: Vec::new(),
+            limiter: ConcurrencyLimiter::new(config),
+            overflow: config.work_overflow,
+        }
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, every: Duration, worker: Arc<dyn Worker>) {
+        self.schedules.push(Schedule {
+            name: name.into(),
+            every,
+            worker,
+        });
+    }
+
+    /// Runs every schedule until `shutdown` is cancelled, then waits for the
+    /// work units already running. Queued units that never got a slot are
+    /// dropped.
+    pub async fn run(self, shutdown: CancellationToken) {
+        let schedules: Vec<Arc<Schedule>> = self.schedules.into_iter().map(Arc::new).collect();
+        let mut ticks = select_all(schedules.iter().map(|schedule| {
+            let mut ticker = interval(schedule.every);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let schedule = schedule.clone();
+            stream::unfold((ticker, 0u64), move |(mut ticker, iteration)| {
+                let schedule = schedule.clone();
+                async move {
+                    ticker.tick().await;
+                    Some(((schedule, iteration + 1), (ticker, iteration + 1)))
+                }
+            })
+            .boxed()
+        }));
+        let mut tasks = JoinSet::new();
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown.cancelled() => break,
+                Some(Err(e)) = tasks.join_next(), if !tasks.is_empty() => {
+                    error!("Scheduled work panicked: {}", e);
+                }
+                Some((schedule, iteration)) = ticks.next() => {
+                    let permit = match self.overflow {
+                        OverflowPolicy::Queue => None,
+                        OverflowPolicy::Skip => match self.limiter.try_acquire() {
+                            Some(permit) => Some(permit),
+                            None => {
+                                warn!("{} #{} skipped: all work slots busy", schedule.name, iteration);
+                                continue;
+                            }
+                        },
+                    };
+                    let limiter = self.limiter.clone();
+                    let shutdown = shutdown.clone();
+                    tasks.spawn(async move {
+                        let permit = match permit {
+                            Some(permit) => permit,
+                            None => tokio::select! {
+                                _ = shutdown.cancelled() => return,
+                                permit = limiter.acquire() => permit,
+                            },
+                        };
+                        let result = schedule.worker.perform_work(iteration).await;
+                        if let Err(e) = &result {
+                            error!("{} #{} failed: {}", schedule.name, iteration, e);
+                        }
+                        permit.finish(result.is_ok());
+                    });
+                }
+            }
+        }
+
+        info!("Scheduler stopping, waiting for {} work units", tasks.len());
+        while let Some(result) = tasks.join_next().await {
+            if let Err(e) = result {
+                error!("Scheduled work panicked: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::{Outcome, WorkError};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::time::sleep;
+
+    /// Takes a while per run and records the most runs seen in flight at once.
+    #[derive(Default)]
+    struct ConcurrencyProbe {
+        running: AtomicUsize,
+        peak: AtomicUsize,
+        runs: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Worker for ConcurrencyProbe {
+        async fn perform_work(&self, _iteration: u64) -> Result<Outcome, WorkError> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            sleep(Duration::from_secs(3)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            Ok(Outcome::Worked)
+        }
+    }
+
+    async fn run_five_schedules(overflow: OverflowPolicy) -> Arc<ConcurrencyProbe> {
+        let probe = Arc::new(ConcurrencyProbe::default());
+        let mut scheduler = Scheduler::new(&Config {
+            max_concurrent_work: 2,
+            work_overflow: overflow,
+            ..Config::default()
+        });
+        for n in 0..5 {
+            scheduler.add(format!("job-{n}"), Duration::from_secs(1), probe.clone());
+        }
+
+        let shutdown = CancellationToken::new();
+        let task = tokio::spawn(scheduler.run(shutdown.clone()));
+        sleep(Duration::from_secs(10)).await;
+        shutdown.cancel();
+        task.await.unwrap();
+        probe
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queued_work_never_exceeds_the_cap() {
+        let probe = run_five_schedules(OverflowPolicy::Queue).await;
+
+        assert_eq!(probe.peak.load(Ordering::SeqCst), 2);
+        assert_eq!(probe.running.load(Ordering::SeqCst), 0, "drained on stop");
+        // Two slots busy for 3s at a time over 10s, plus the last pair draining
+        assert_eq!(probe.runs.load(Ordering::SeqCst), 8);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skipped_work_never_exceeds_the_cap() {
+        let probe = run_five_schedules(OverflowPolicy::Skip).await;
+
+        assert_eq!(probe.peak.load(Ordering::SeqCst), 2);
+        assert_eq!(probe.running.load(Ordering::SeqCst), 0);
+    }
+}
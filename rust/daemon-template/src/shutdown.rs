@@ -0,0 +1,384 @@
+use futures::stream::StreamExt;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook_tokio::Signals;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout_at, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::daemon::Stopped;
+
+/// How a shutdown request should be handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escalation {
+    /// First request: stop ticking and let in-flight work finish.
+    Graceful,
+    /// A repeated request while already draining: exit immediately.
+    Forced,
+}
+
+/// Tracks shutdown requests so a repeated one escalates to a forced exit.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    requests: Arc<AtomicU32>,
+    trigger: Arc<OnceLock<String>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Token cancelled on the first shutdown request.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// What made the first shutdown request, if any.
+    pub fn trigger(&self) -> Option<String> {
+        self.trigger.get().cloned()
+    }
+
+    /// Requests shutdown on behalf of `source` (a signal name, say).
+    pub fn request(&self, source: &str) -> Escalation {
+        if self.requests.fetch_add(1, Ordering::SeqCst) == 0 {
+            let _ = self.trigger.set(source.to_string());
+            self.token.cancel();
+            Escalation::Graceful
+        } else {
+            Escalation::Forced
+        }
+    }
+}
+
+/// Turns incoming signals into shutdown requests. The first begins a graceful
+/// drain; this function only returns when a later one asks to force the exit.
+pub async fn handle_signals(mut signals: Signals, shutdown: Shutdown) {
+    while let Some(signal) = signals.next().await {
+        let name = match signal {
+            SIGTERM => "SIGTERM",
+            SIGINT => "SIGINT",
+            _ => {
+                warn!("Received unexpected signal: {}", signal);
+                continue;
+            }
+        };
+
+        match shutdown.request(name) {
+            Escalation::Graceful => {
+                info!(
+                    "Received {}, draining before shutdown (send again to force)...",
+                    name
+                )
+            }
+            Escalation::Forced => {
+                warn!("Received {} again while draining, forcing shutdown", name);
+                return;
+            }
+        }
+    }
+
+    // The signal stream never ends while the handle is alive
+    std::future::pending::<()>().await
+}
+
+/// Runs one step of shutdown (draining, flushing) unless shutdown is forced
+/// first, in which case `None`. `forced` is the [`handle_signals`] task,
+/// which only completes on a repeated request, so no step of shutdown can
+/// leave a second signal unanswered.
+pub async fn unless_forced<T>(
+    forced: &mut JoinHandle<()>,
+    step: impl Future<Output = T>,
+) -> Option<T> {
+    tokio::select! {
+        output = step => Some(output),
+        _ = forced => None,
+    }
+}
+
+/// A long-running part of the daemon, stopped by cancelling its own token.
+pub struct Component<T> {
+    name: &'static str,
+    stop: CancellationToken,
+    task: JoinHandle<T>,
+}
+
+impl<T: Send + 'static> Component<T> {
+    /// Spawns `run`, handing it the token [`Component::stop`] cancels.
+    pub fn spawn<F>(name: &'static str, run: impl FnOnce(CancellationToken) -> F) -> Self
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        let stop = CancellationToken::new();
+        let task = tokio::spawn(run(stop.clone()));
+        Self { name, stop, task }
+    }
+
+    /// Waits for the component to finish on its own.
+    pub async fn join(&mut self) -> Option<T> {
+        (&mut self.task)
+            .await
+            .inspect_err(|e| error!("{} panicked: {}", self.name, e))
+            .ok()
+    }
+
+    /// Tells the component to stop and waits for it until `deadline`,
+    /// abandoning it if it runs over. `None` if it panicked or missed the
+    /// deadline.
+    pub async fn stop(mut self, deadline: Instant) -> Option<T> {
+        info!("Stopping {}...", self.name);
+        let started = Instant::now();
+        self.stop.cancel();
+
+        match timeout_at(deadline, self.join()).await {
+            Ok(output) => {
+                info!("{} stopped after {:?}", self.name, started.elapsed());
+                output
+            }
+            Err(_) => {
+                error!("{} did not stop before the shutdown deadline", self.name);
+                self.task.abort();
+                None
+            }
+        }
+    }
+}
+
+/// How an ordered drain ended. Distinct from a forced shutdown, which
+/// [`unless_forced`] reports as `None`: an abandoned drain still gets its
+/// shutdown report and state save.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drained {
+    /// The work loop stopped in time, for this reason.
+    Stopped(Stopped),
+    /// The work loop missed the deadline or panicked, and was abandoned.
+    Abandoned,
+}
+
+/// Stops the daemon's components in a fixed order, all by the same
+/// `deadline`:
+///
+/// 1. cancels `draining`, so `/healthz` reports not-ready and load balancers
+///    move away while this instance still finishes its work;
+/// 2. the work loop, which completes the iteration in progress;
+/// 3. the health server, which answered probes throughout.
+///
+/// Returns why the work loop stopped, or that it had to be abandoned.
+pub async fn stop_in_order(
+    draining: &CancellationToken,
+    health: Option<Component<std::io::Result<()>>>,
+    work: Component<Stopped>,
+    deadline: Instant,
+) -> Drained {
+    info!("Reporting not-ready while draining");
+    draining.cancel();
+    let stopped = work.stop(deadline).await;
+    if let Some(health) = health {
+        stop_health(health, deadline).await;
+    }
+    stopped.map_or(Drained::Abandoned, Drained::Stopped)
+}
+
+/// Stops the health server, logging if it had failed.
+pub async fn stop_health(health: Component<std::io::Result<()>>, deadline: Instant) {
+    if let Some(Err(e)) = health.stop(deadline).await {
+        warn!("Health server failed: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::worker::{Outcome, WorkError, Worker};
+    use async_trait::async_trait;
+    use signal_hook::low_level::raise;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::Notify;
+    use tokio::time::timeout;
+
+    // Signal handlers are process-wide and tests run concurrently, so each
+    // test raises a different signal.
+
+    #[tokio::test]
+    async fn one_signal_drains_gracefully() {
+        let shutdown = Shutdown::new();
+        let mut handler = tokio::spawn(handle_signals(
+            Signals::new([SIGINT]).unwrap(),
+            shutdown.clone(),
+        ));
+
+        raise(SIGINT).unwrap();
+
+        timeout(Duration::from_secs(1), shutdown.token().cancelled())
+            .await
+            .expect("first signal should begin draining");
+        assert!(
+            timeout(Duration::from_millis(100), &mut handler)
+                .await
+                .is_err(),
+            "a single signal must not force an exit"
+        );
+        handler.abort();
+    }
+
+    #[tokio::test]
+    async fn two_signals_force_exit() {
+        let shutdown = Shutdown::new();
+        let handler = tokio::spawn(handle_signals(
+            Signals::new([SIGTERM]).unwrap(),
+            shutdown.clone(),
+        ));
+
+        raise(SIGTERM).unwrap();
+        timeout(Duration::from_secs(1), shutdown.token().cancelled())
+            .await
+            .unwrap();
+        raise(SIGTERM).unwrap();
+
+        timeout(Duration::from_secs(1), handler)
+            .await
+            .expect("second signal should force an exit")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_forced_shutdown_cuts_any_step_short() {
+        let forced_now = Arc::new(Notify::new());
+        let mut forced = tokio::spawn({
+            let forced_now = forced_now.clone();
+            async move { forced_now.notified().await }
+        });
+
+        // Steps finish normally until shutdown is forced
+        assert_eq!(unless_forced(&mut forced, async { 1 }).await, Some(1));
+
+        let step = tokio::spawn(async move {
+            let flush = std::future::pending::<()>();
+            unless_forced(&mut forced, flush).await
+        });
+        tokio::task::yield_now().await;
+        assert!(!step.is_finished());
+        forced_now.notify_one();
+        let outcome = timeout(Duration::from_secs(1), step)
+            .await
+            .expect("a forced shutdown should not wait for the step");
+        assert_eq!(outcome.unwrap(), None);
+    }
+
+    #[test]
+    fn repeated_requests_escalate_to_forced() {
+        let shutdown = Shutdown::new();
+
+        assert_eq!(shutdown.request("SIGTERM"), Escalation::Graceful);
+        assert!(shutdown.token().is_cancelled());
+        assert_eq!(shutdown.request("SIGINT"), Escalation::Forced);
+        assert_eq!(shutdown.trigger().as_deref(), Some("SIGTERM"));
+    }
+
+    /// Takes `duration` per iteration, announcing when one starts.
+    struct SlowWorker {
+        duration: Duration,
+        started: Notify,
+        finished: AtomicBool,
+    }
+
+    #[async_trait]
+    impl Worker for SlowWorker {
+        async fn perform_work(&self, _iteration: u64) -> Result<Outcome, WorkError> {
+            self.finished.store(false, Ordering::SeqCst);
+            self.started.notify_one();
+            tokio::time::sleep(self.duration).await;
+            self.finished.store(true, Ordering::SeqCst);
+            Ok(Outcome::Worked)
+        }
+    }
+
+    async fn probe(addr: std::net::SocketAddr) -> std::io::Result<String> {
+        let mut conn = TcpStream::connect(addr).await?;
+        conn.write_all(b"GET /healthz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut raw = String::new();
+        conn.read_to_string(&mut raw).await?;
+        Ok(raw)
+    }
+
+    #[tokio::test]
+    async fn healthz_reports_draining_until_the_work_loop_stops() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let draining = CancellationToken::new();
+        let health = Component::spawn("health server", {
+            let router = crate::health::router(Arc::default(), draining.clone());
+            |stop| crate::health::serve(listener, router, stop)
+        });
+        assert!(probe(addr).await.unwrap().starts_with("HTTP/1.1 200"));
+
+        let worker = Arc::new(SlowWorker {
+            duration: Duration::from_millis(500),
+            started: Notify::new(),
+            finished: AtomicBool::new(false),
+        });
+        let work = Component::spawn("work loop", {
+            let worker = worker.clone();
+            |stop| async move {
+                let config = Config {
+                    tick_interval: Duration::from_secs(60),
+                    ..Config::default()
+                };
+                crate::daemon::run(
+                    worker,
+                    config.into(),
+                    Arc::new(crate::clock::SystemClock),
+                    Arc::default(),
+                    stop,
+                )
+                .await
+            }
+        });
+        worker.started.notified().await;
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let drain = tokio::spawn({
+            let draining = draining.clone();
+            async move { stop_in_order(&draining, Some(health), work, deadline).await }
+        });
+
+        // Probes are answered, but unhealthy, while the iteration still runs
+        timeout(Duration::from_millis(300), async {
+            while !probe(addr).await.unwrap().starts_with("HTTP/1.1 503") {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("healthz should report draining promptly");
+        assert!(!worker.finished.load(Ordering::SeqCst));
+
+        assert_eq!(drain.await.unwrap(), Drained::Stopped(Stopped::Shutdown));
+        assert!(worker.finished.load(Ordering::SeqCst));
+        assert!(Instant::now() < deadline);
+        assert!(probe(addr).await.is_err(), "health server stops last");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn components_missing_the_deadline_are_abandoned() {
+        let work = Component::spawn("work loop", |_stop| async {
+            std::future::pending::<()>().await;
+            Stopped::Shutdown
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert_eq!(
+            stop_in_order(&CancellationToken::new(), None, work, deadline).await,
+            Drained::Abandoned
+        );
+        assert_eq!(Instant::now(), deadline);
+    }
+}
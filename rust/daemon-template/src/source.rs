@@ -0,0 +1,239 @@
+use async_trait::async_trait;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::worker::WorkError;
+
+/// A queue the daemon takes jobs from in queue mode (`JOB_SOURCE`) instead
+/// of ticking on a schedule. Jobs are ids, passed to the worker as the
+/// iteration, the same as [`WorkerPool`](crate::pool::WorkerPool) jobs.
+#[async_trait]
+pub trait JobSource: Send + Sync + 'static {
+    /// Takes the next job, or `None` if the queue is empty right now.
+    async fn fetch(&self) -> Result<Option<u64>, WorkError>;
+
+    /// Marks a fetched job as done so it is never delivered again.
+    async fn ack(&self, job: u64) -> Result<(), WorkError>;
+}
+
+/// Takes jobs from a Redis list, over a fresh connection per command.
+///
+/// Fetching moves a job onto `<queue>:processing` (`RPOPLPUSH`) and
+/// acknowledging removes it from there, so jobs that failed or were in
+/// progress when the daemon died stay on the processing list for an
+/// operator or a reaper to push back.
+pub struct RedisListSource {
+    addr: String,
+    queue: String,
+}
+
+impl RedisListSource {
+    /// Accepts `redis://host:port/<list>`.
+    pub fn from_url(url: &str) -> Result<Self, String> {
+        let invalid =
+            || format!("unsupported job source URL {url:?}, expected redis://host:port/list");
+        let (addr, queue) = url
+            .strip_prefix("redis://")
+            .and_then(|rest| rest.split_once('/'))
+            .ok_or_else(invalid)?;
+        if addr.is_empty() || queue.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            addr: addr.to_string(),
+            queue: queue.to_string(),
+        })
+    }
+
+    fn processing(&self) -> String {
+        format!("{}:processing", self.queue)
+    }
+
+    /// Sends one command and returns the raw reply.
+    async fn command(&self, args: &[&str]) -> Result<Vec<u8>, WorkError> {
+        let mut command = format!("*{}\r\n", args.len());
+        for arg in args {
+            command.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+        }
+
+        let mut stream = TcpStream::connect(&self.addr).await?;
+        stream.write_all(command.as_bytes()).await?;
+        let mut reply = vec![0u8; 128];
+        let read = stream.read(&mut reply).await?;
+        reply.truncate(read);
+        if reply.first() == Some(&b'-') {
+            return Err(format!(
+                "error from Redis: {}",
+                String::from_utf8_lossy(&reply[1..]).trim_end()
+            )
+            .into());
+        }
+        Ok(reply)
+    }
+}
+
+#[async_trait]
+impl JobSource for RedisListSource {
+    async fn fetch(&self) -> Result<Option<u64>, WorkError> {
+        let reply = self
+            .command(&["RPOPLPUSH", &self.queue, &self.processing()])
+            .await?;
+        let reply = String::from_utf8_lossy(&reply);
+        // `$-1` for an empty list, otherwise `$<len>\r\n<job>\r\n`
+        if reply.starts_with("$-1") {
+            return Ok(None);
+        }
+        let job = reply
+            .strip_prefix('$')
+            .and_then(|rest| rest.split("\r\n").nth(1))
+            .ok_or_else(|| format!("unexpected reply from Redis: {:?}", reply.trim_end()))?;
+        job.parse()
+            .map(Some)
+            .map_err(|_| format!("job {job:?} in {} is not an id", self.queue).into())
+    }
+
+    async fn ack(&self, job: u64) -> Result<(), WorkError> {
+        self.command(&["LREM", &self.processing(), "1", &job.to_string()])
+            .await
+            .map(drop)
+    }
+}
+
+/// Takes jobs queued by other tasks in the same process. Nothing is
+/// redelivered, so acknowledging does nothing.
+#[allow(dead_code)] // For daemons that produce their own jobs; `main` reads from Redis
+pub struct ChannelSource(Mutex<mpsc::Receiver<u64>>);
+
+#[allow(dead_code)]
+impl ChannelSource {
+    pub fn new(jobs: mpsc::Receiver<u64>) -> Self {
+        Self(Mutex::new(jobs))
+    }
+}
+
+#[async_trait]
+impl JobSource for ChannelSource {
+    async fn fetch(&self) -> Result<Option<u64>, WorkError> {
+        Ok(self.0.lock().await.try_recv().ok())
+    }
+
+    async fn ack(&self, _job: u64) -> Result<(), WorkError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::daemon::{run_jobs, Stopped, TickCounters};
+    use crate::worker::{Outcome, Worker};
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio_util::sync::CancellationToken;
+
+    /// In-memory queue recording every fetch and acknowledgement.
+    #[derive(Default)]
+    struct MemorySource {
+        jobs: std::sync::Mutex<VecDeque<u64>>,
+        fetches: AtomicU64,
+        acked: std::sync::Mutex<Vec<u64>>,
+    }
+
+    #[async_trait]
+    impl JobSource for MemorySource {
+        async fn fetch(&self) -> Result<Option<u64>, WorkError> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            Ok(self.jobs.lock().unwrap().pop_front())
+        }
+
+        async fn ack(&self, job: u64) -> Result<(), WorkError> {
+            self.acked.lock().unwrap().push(job);
+            Ok(())
+        }
+    }
+
+    /// Fails job 3, handles every other.
+    struct FailsJobThree;
+
+    #[async_trait]
+    impl Worker for FailsJobThree {
+        async fn perform_work(&self, job: u64) -> Result<Outcome, WorkError> {
+            if job == 3 {
+                Err("bad job".into())
+            } else {
+                Ok(Outcome::Worked)
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queued_jobs_are_processed_and_successes_acked() {
+        let source = Arc::new(MemorySource::default());
+        source.jobs.lock().unwrap().extend([1, 2, 3, 4]);
+        let counters = Arc::new(TickCounters::default());
+        let shutdown = CancellationToken::new();
+
+        let task = tokio::spawn({
+            let (source, counters, shutdown) = (source.clone(), counters.clone(), shutdown.clone());
+            async move {
+                run_jobs(
+                    Arc::new(FailsJobThree),
+                    source,
+                    Config::default().into(),
+                    counters,
+                    shutdown,
+                )
+                .await
+            }
+        });
+
+        // Then ten seconds of an empty queue
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(*source.acked.lock().unwrap(), [1, 2, 4]);
+        let snapshot = counters.snapshot();
+        assert_eq!((snapshot.succeeded, snapshot.failed), (3, 1));
+        // Backing off 0.1s, 0.2s, ... up to 5s between empty polls, rather
+        // than a hundred polls at 0.1s
+        let fetches = source.fetches.load(Ordering::SeqCst);
+        assert_eq!(fetches, 4 + 7, "{fetches}");
+
+        shutdown.cancel();
+        assert_eq!(task.await.unwrap(), Stopped::Shutdown);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn an_empty_queue_counts_towards_idle_shutdown() {
+        let source = Arc::new(MemorySource::default());
+        source.jobs.lock().unwrap().push_back(1);
+        let config = Config {
+            idle_shutdown_ticks: Some(3),
+            ..Config::default()
+        };
+
+        let stopped = run_jobs(
+            Arc::new(FailsJobThree),
+            source.clone(),
+            config.into(),
+            Arc::default(),
+            CancellationToken::new(),
+        )
+        .await;
+        assert_eq!(stopped, Stopped::Idle);
+        assert_eq!(*source.acked.lock().unwrap(), [1]);
+    }
+
+    #[test]
+    fn redis_urls_need_a_list() {
+        let source = RedisListSource::from_url("redis://localhost:6379/jobs").unwrap();
+        assert_eq!(
+            (source.addr.as_str(), source.queue.as_str()),
+            ("localhost:6379", "jobs")
+        );
+        assert!(RedisListSource::from_url("redis://localhost:6379").is_err());
+        assert!(RedisListSource::from_url("nats://localhost:4222/jobs").is_err());
+    }
+}
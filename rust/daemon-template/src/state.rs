@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// What the daemon remembers across restarts (`STATE_FILE`).
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct DaemonState {
+    /// Work runs over every previous run of the daemon.
+    pub total_work_runs: u64,
+    /// What stopped the previous run, if it stopped cleanly.
+    pub last_trigger: Option<String>,
+}
+
+/// Reads the state file, starting fresh when it doesn't exist yet.
+///
+/// A file that exists but can't be parsed (truncated by a crash, edited by
+/// hand) is moved aside to `<file>.corrupt-<unix secs>` and the daemon
+/// starts from the default state, unless `strict` (`STRICT_STATE`) asks for
+/// an error instead so the operator can intervene.
+pub fn load(path: &Path, strict: bool) -> Result<DaemonState, String> {
+    let raw = match std::fs::read(path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            info!("No state file at {}, starting fresh", path.display());
+            return Ok(DaemonState::default());
+        }
+        Err(e) => return Err(format!("cannot read state file {}: {e}", path.display())),
+    };
+
+    match serde_json::from_slice(&raw) {
+        Ok(state) => Ok(state),
+        Err(e) if strict => Err(format!("corrupt state file {}: {e}", path.display())),
+        Err(e) => {
+            let backup = backup_path(path);
+            match std::fs::rename(path, &backup) {
+                Ok(()) => warn!(
+                    "Corrupt state file {} ({}), moved it to {} and starting fresh",
+                    path.display(),
+                    e,
+                    backup.display()
+                ),
+                Err(rename_error) => warn!(
+                    "Corrupt state file {} ({}), starting fresh; could not back it up: {}",
+                    path.display(),
+                    e,
+                    rename_error
+                ),
+            }
+            Ok(DaemonState::default())
+        }
+    }
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".corrupt-{secs}"));
+    PathBuf::from(name)
+}
+
+/// Writes the state via a temporary file and a rename, so a crash mid-write
+/// leaves the previous state intact rather than a truncated file.
+pub fn save(path: &Path, state: &DaemonState) -> Result<(), String> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let json = serde_json::to_vec_pretty(state).map_err(|e| e.to_string())?;
+    std::fs::write(&tmp, json)
+        .and_then(|()| std::fs::rename(&tmp, path))
+        .map_err(|e| format!("cannot write state file {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("daemon-state-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn round_trips_and_starts_fresh_without_a_file() {
+        let dir = temp_dir("round-trip");
+        let path = dir.join("state.json");
+
+        assert_eq!(load(&path, true).unwrap(), DaemonState::default());
+
+        let state = DaemonState {
+            total_work_runs: 42,
+            last_trigger: Some("SIGTERM".to_string()),
+        };
+        save(&path, &state).unwrap();
+        assert_eq!(load(&path, true).unwrap(), state);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_file_is_backed_up_and_replaced_by_defaults() {
+        let dir = temp_dir("lenient");
+        let path = dir.join("state.json");
+        std::fs::write(&path, r#"{"total_work_runs": 4"#).unwrap();
+
+        assert_eq!(load(&path, false).unwrap(), DaemonState::default());
+
+        assert!(!path.exists());
+        let backups: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert!(backups[0].starts_with("state.json.corrupt-"), "{backups:?}");
+        assert_eq!(
+            std::fs::read_to_string(dir.join(&backups[0])).unwrap(),
+            r#"{"total_work_runs": 4"#
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn strict_mode_fails_fast_and_leaves_the_file_alone() {
+        let dir = temp_dir("strict");
+        let path = dir.join("state.json");
+        std::fs::write(&path, "garbage").unwrap();
+
+        let error = load(&path, true).unwrap_err();
+        assert!(error.contains("corrupt state file"), "{error}");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "garbage");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::info;
+
+pub type WorkError = Box<dyn std::error::Error + Send + Sync>;
+
+/// What a successful iteration amounted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    /// Something was processed.
+    Worked,
+    /// There was nothing to do. Counts towards `IDLE_SHUTDOWN_TICKS`.
+    #[allow(dead_code)] // returned by real workers; the example always works
+    Idle,
+}
+
+/// How far the iteration in progress has got, as reported by the worker and
+/// read by the daemon's `/stats` endpoint and progress log. Clones share the
+/// same value.
+#[derive(Clone)]
+pub struct Progress(Arc<AtomicU8>);
+
+/// Stored while nothing has been reported for the current iteration.
+const UNREPORTED: u8 = u8::MAX;
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU8::new(UNREPORTED)))
+    }
+}
+
+impl Progress {
+    /// Records that the current iteration is `percent` (capped at 100) done.
+    pub fn report(&self, percent: u8) {
+        self.0.store(percent.min(100), Ordering::Relaxed);
+    }
+
+    /// The latest report for the current iteration, if there was one.
+    pub fn percent(&self) -> Option<u8> {
+        Some(self.0.load(Ordering::Relaxed)).filter(|percent| *percent != UNREPORTED)
+    }
+
+    /// Forgets the last report, ready for the next iteration.
+    pub fn clear(&self) {
+        self.0.store(UNREPORTED, Ordering::Relaxed);
+    }
+}
+
+/// A unit of periodic work driven by the daemon's tick loop.
+///
+/// Implement this for your own business logic and hand it to the loop in
+/// `main` instead of [`ExampleWorker`]. Scheduled ticks count `iteration`
+/// up from 1; runs triggered through `POST /admin/run` pass 0.
+#[async_trait]
+pub trait Worker: Send + Sync + 'static {
+    /// One-off preparation before the first tick, such as connecting to
+    /// dependencies. Retried with backoff until it succeeds or shutdown is
+    /// requested.
+    async fn start(&self) -> Result<(), WorkError> {
+        Ok(())
+    }
+
+    async fn perform_work(&self, iteration: u64) -> Result<Outcome, WorkError>;
+
+    /// What the tick loop actually calls. Long-running workers override this
+    /// to report how far they are through `progress`, and implement
+    /// `perform_work` by calling it with a fresh [`Progress`]; by default
+    /// nothing is reported.
+    async fn perform_work_with_progress(
+        &self,
+        iteration: u64,
+        progress: &Progress,
+    ) -> Result<Outcome, WorkError> {
+        let _ = progress;
+        self.perform_work(iteration).await
+    }
+}
+
+/// Placeholder worker simulating some async work.
+pub struct ExampleWorker;
+
+#[async_trait]
+impl Worker for ExampleWorker {
+    async fn perform_work(&self, iteration: u64) -> Result<Outcome, WorkError> {
+        self.perform_work_with_progress(iteration, &Progress::default())
+            .await
+    }
+
+    async fn perform_work_with_progress(
+        &self,
+        iteration: u64,
+        progress: &Progress,
+    ) -> Result<Outcome, WorkError> {
+        // Simulate some async work, reporting progress halfway through
+        sleep(Duration::from_millis(50)).await;
+        progress.report(50);
+        sleep(Duration::from_millis(50)).await;
+
+        // Example: periodic maintenance, health checks, data processing, etc.
+        if iteration.is_multiple_of(5) {
+            info!("Performing maintenance task at iteration {}", iteration);
+        }
+
+        Ok(Outcome::Worked)
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+use tracing::warn;
+
+use crate::config::Config;
+
+/// Checks that new files can be created next to `path`, by creating and
+/// removing a probe file there. Fails on a read-only filesystem (e.g. a
+/// container with a read-only root) as well as on missing permissions.
+pub fn check(path: &Path) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let probe = dir.join(format!(".write-probe-{}", std::process::id()));
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)?;
+    std::fs::remove_file(&probe)
+}
+
+/// Checks every file the daemon writes (`STATE_FILE`,
+/// `SHUTDOWN_REPORT_FILE`) at startup, so an unwritable one is found then
+/// rather than on its first write, which may be at shutdown.
+///
+/// Each unwritable target is switched off with a warning, or with
+/// `STRICT_WRITES` the daemon refuses to start instead.
+pub fn disable_unwritable(config: &mut Config) -> Result<(), String> {
+    let strict = config.strict_writes;
+    let targets = [
+        ("STATE_FILE", &mut config.state_file),
+        ("SHUTDOWN_REPORT_FILE", &mut config.shutdown_report_file),
+    ];
+    for (name, target) in targets {
+        let Some(path) = target.as_deref() else {
+            continue;
+        };
+        if let Err(e) = check(path) {
+            if strict {
+                return Err(format!("{name} {} is not writable: {e}", path.display()));
+            }
+            warn!(
+                "{} {} is not writable ({}), running without it",
+                name,
+                path.display(),
+                e
+            );
+            *target = None;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    /// A directory nothing can be created in. Root ignores permissions, so
+    /// when running as root this falls back to `/proc`.
+    fn read_only_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("daemon-read-only-{}", std::process::id()));
+        let _ = std::fs::create_dir(&dir);
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o555)).unwrap();
+        if check(&dir.join("file")).is_ok() {
+            std::fs::remove_dir(&dir).unwrap();
+            return PathBuf::from("/proc");
+        }
+        dir
+    }
+
+    fn config_writing_to(dir: &Path, strict_writes: bool) -> Config {
+        Config {
+            state_file: Some(dir.join("state.json")),
+            shutdown_report_file: Some(dir.join("report.json")),
+            strict_writes,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn unwritable_targets_are_disabled_or_refused() {
+        let dir = read_only_dir();
+
+        let mut lenient = config_writing_to(&dir, false);
+        disable_unwritable(&mut lenient).unwrap();
+        assert_eq!(lenient.state_file, None);
+        assert_eq!(lenient.shutdown_report_file, None);
+
+        let mut strict = config_writing_to(&dir, true);
+        let error = disable_unwritable(&mut strict).unwrap_err();
+        assert!(error.starts_with("STATE_FILE "), "{error}");
+        assert!(error.contains("is not writable"), "{error}");
+        if dir != Path::new("/proc") {
+            std::fs::remove_dir(&dir).unwrap();
+        }
+
+        // Writable targets are kept, and the probe is cleaned up
+        let writable = std::env::temp_dir().join(format!("daemon-writable-{}", std::process::id()));
+        std::fs::create_dir_all(&writable).unwrap();
+        let mut config = config_writing_to(&writable, true);
+        disable_unwritable(&mut config).unwrap();
+        assert_eq!(config.state_file, Some(writable.join("state.json")));
+        assert_eq!(std::fs::read_dir(&writable).unwrap().count(), 0);
+        std::fs::remove_dir_all(&writable).unwrap();
+    }
+}
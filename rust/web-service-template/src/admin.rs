@@ -0,0 +1,344 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use crate::config::RedactedConfig;
+use crate::extract::{JsonBody, Payload};
+use crate::{ApiResponse, AppState};
+
+/// Operator endpoints, mounted under `/admin` when `ADMIN_ENABLED` is set.
+pub fn router(state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route("/config", get(get_config))
+        .route("/maintenance", get(get_maintenance).put(set_maintenance))
+        .route("/shutdown", post(shutdown))
+        .route_layer(middleware::from_fn(
+            crate::content_type::require_content_type,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_admin_token,
+        ))
+}
+
+/// Requires `Authorization: Bearer <ADMIN_TOKEN>` when a token is configured.
+/// The token is compared in constant time, so response timing doesn't reveal
+/// how much of a guess was right.
+async fn require_admin_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(expected) = &state.config.admin_token {
+        let provided = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        let matches =
+            provided.is_some_and(|provided| provided.as_bytes().ct_eq(expected.as_bytes()).into());
+        if !matches {
+            return (
+                StatusCode::UNAUTHORIZED,
+                ApiResponse::error("Admin credentials required"),
+            )
+                .into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Returns the effective configuration with secrets redacted.
+async fn get_config(State(state): State<AppState>) -> Json<ApiResponse<RedactedConfig>> {
+    Json(ApiResponse {
+        success: true,
+        data: Some(state.config.redacted()),
+        message: "Effective configuration".to_string(),
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MaintenanceState {
+    enabled: bool,
+}
+
+impl Payload for MaintenanceState {
+    const FIELDS: &'static [&'static str] = &["enabled"];
+}
+
+/// A 403 refusing `action` unless `ADMIN_TOKEN` is configured. Endpoints
+/// that can take the service out of action go through this, so an
+/// unauthenticated deployment can't be disrupted by any client that can
+/// reach `/admin`.
+fn refused_without_token(state: &AppState, action: &str) -> Option<Response> {
+    state.config.admin_token.is_none().then(|| {
+        (
+            StatusCode::FORBIDDEN,
+            ApiResponse::error(format!("{action} over HTTP requires ADMIN_TOKEN to be set")),
+        )
+            .into_response()
+    })
+}
+
+async fn get_maintenance(State(state): State<AppState>) -> Json<ApiResponse<MaintenanceState>> {
+    Json(ApiResponse {
+        success: true,
+        data: Some(MaintenanceState {
+            enabled: state.maintenance.is_enabled(),
+        }),
+        message: "Maintenance mode status".to_string(),
+    })
+}
+
+/// Turns maintenance mode on or off. Like shutdown it is refused with 403
+/// unless `ADMIN_TOKEN` is configured: maintenance answers every API
+/// request with 503.
+async fn set_maintenance(
+    State(state): State<AppState>,
+    JsonBody(payload): JsonBody<MaintenanceState>,
+) -> Response {
+    if let Some(refused) = refused_without_token(&state, "Toggling maintenance mode") {
+        return refused;
+    }
+    state.maintenance.set(payload.enabled);
+    tracing::warn!(
+        "Maintenance mode {}",
+        if payload.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    );
+
+    Json(ApiResponse {
+        success: true,
+        data: Some(payload),
+        message: "Maintenance mode updated".to_string(),
+    })
+    .into_response()
+}
+
+/// Starts the same graceful shutdown as SIGTERM, for platforms where sending
+/// signals is awkward. Responds 202 and then drains.
+///
+/// Refused with 403 unless `ADMIN_TOKEN` is configured, so an
+/// unauthenticated deployment can't be taken down by any client that can
+/// reach `/admin`.
+async fn shutdown(State(state): State<AppState>) -> Response {
+    if let Some(refused) = refused_without_token(&state, "Shutdown") {
+        return refused;
+    }
+
+    tracing::warn!("Shutdown requested via /admin/shutdown");
+    state.stats.record_trigger("POST /admin/shutdown");
+    // In-flight requests, this one included, complete before the server exits
+    state.shutdown.cancel();
+
+    (
+        StatusCode::ACCEPTED,
+        Json(ApiResponse::<()> {
+            success: true,
+            data: None,
+            message: "Shutdown initiated".to_string(),
+        }),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::test_support::{body_json, send, test_state_with};
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+
+    fn admin_config() -> Config {
+        Config {
+            admin_enabled: true,
+            admin_token: Some("s3cret-token".to_string()),
+            max_uri_bytes: 4096,
+            ..Config::default()
+        }
+    }
+
+    #[cfg(feature = "camel-case-api")]
+    #[tokio::test]
+    async fn config_endpoint_redacts_secrets() {
+        let state = test_state_with(admin_config());
+
+        let response = send(
+            &state,
+            Request::get("/admin/config")
+                .header(header::AUTHORIZATION, "Bearer s3cret-token")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_json(response).await;
+        let config = &body["data"];
+        assert_eq!(config["bindAddr"], "0.0.0.0:3000");
+        assert_eq!(config["maxUriBytes"], 4096);
+        assert_eq!(config["adminToken"], "***");
+        assert!(!body.to_string().contains("s3cret-token"));
+    }
+
+    #[tokio::test]
+    async fn maintenance_toggle_blocks_api_but_not_health() {
+        let state = test_state_with(admin_config());
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let response = send(
+            &state,
+            Request::put("/admin/maintenance")
+                .header(header::AUTHORIZATION, "Bearer s3cret-token")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"enabled":true}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send(&state, get("/items")).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = body_json(response).await;
+        assert!(body["message"].as_str().unwrap().contains("maintenance"));
+
+        let response = send(&state, get("/health")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["data"]["status"], "degraded");
+
+        state.maintenance.set(false);
+        assert_eq!(send(&state, get("/items")).await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn admin_endpoints_require_token_and_flag() {
+        let state = test_state_with(admin_config());
+        let response = send(
+            &state,
+            Request::get("/admin/config").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let state = test_state_with(Config::default());
+        let response = send(
+            &state,
+            Request::get("/admin/config").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn shutdown_endpoint_stops_the_server() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::time::{timeout, Duration};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(crate::serve(
+            listener,
+            test_state_with(admin_config()),
+            Vec::new(),
+            None,
+        ));
+
+        let mut conn = tokio::net::TcpStream::connect(addr).await.unwrap();
+        conn.write_all(
+            b"POST /admin/shutdown HTTP/1.1\r\nHost: localhost\r\n\
+              Authorization: Bearer s3cret-token\r\nContent-Length: 0\r\n\
+              Connection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        let mut raw = String::new();
+        conn.read_to_string(&mut raw).await.unwrap();
+        assert!(
+            raw.starts_with("HTTP/1.1 202"),
+            "unexpected response: {raw}"
+        );
+
+        timeout(Duration::from_secs(1), server)
+            .await
+            .expect("serve should return after /admin/shutdown")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn shutdown_endpoint_is_refused_without_a_token() {
+        let state = test_state_with(Config {
+            admin_enabled: true,
+            ..Config::default()
+        });
+
+        let response = send(
+            &state,
+            Request::post("/admin/shutdown")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(!state.shutdown.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn maintenance_cannot_be_toggled_without_a_token() {
+        let state = test_state_with(Config {
+            admin_enabled: true,
+            ..Config::default()
+        });
+
+        let response = send(
+            &state,
+            Request::put("/admin/maintenance")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"enabled":true}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            body_json(response).await["message"],
+            "Toggling maintenance mode over HTTP requires ADMIN_TOKEN to be set"
+        );
+        assert!(!state.maintenance.is_enabled());
+    }
+
+    #[tokio::test]
+    async fn maintenance_toggles_are_validated_like_other_bodies() {
+        let state = test_state_with(admin_config());
+        let put = |body: &'static str| {
+            Request::put("/admin/maintenance")
+                .header(header::AUTHORIZATION, "Bearer s3cret-token")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = send(&state, put("")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_json(response).await["message"],
+            "Request body is required: expected a JSON object with fields enabled"
+        );
+        let response = send(&state, put(r#"{"enabled":"yes"}"#)).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body_json(response).await["success"], false);
+    }
+}
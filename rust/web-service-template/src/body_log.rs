@@ -0,0 +1,342 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::config::Config;
+use crate::redact::Redactor;
+use crate::ApiResponse;
+
+/// Largest body buffered for logging. Bigger requests are refused with 413
+/// on logged routes, which is why logging is for debugging only; responses
+/// are passed on whole, with only this much of them kept for the log.
+const MAX_BUFFERED_BYTES: usize = 8 * 1024 * 1024;
+
+/// Response types that stream for as long as the client listens, so never
+/// end for the log to render them; they are passed on untouched.
+const STREAMING_CONTENT_TYPES: [&str; 2] = ["text/event-stream", "application/x-ndjson"];
+
+/// Debug logging of request and response bodies for selected routes
+/// (`DEBUG_BODY_ROUTES`).
+///
+/// Off unless routes are listed. Request headers and both bodies are logged
+/// at DEBUG, passed through the [`Redactor`] first, and bodies are truncated
+/// to `DEBUG_BODY_MAX_BYTES`. Responses are copied as they are sent rather
+/// than buffered, and their body is logged once it has been sent.
+pub struct BodyLogging {
+    routes: HashSet<String>,
+    max_bytes: usize,
+    redactor: Redactor,
+}
+
+impl BodyLogging {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            routes: config.debug_body_routes.iter().cloned().collect(),
+            max_bytes: config.debug_body_max_bytes,
+            redactor: Redactor::new(config),
+        }
+    }
+
+    fn render(&self, body: &[u8]) -> String {
+        let text = match serde_json::from_slice::<serde_json::Value>(body) {
+            Ok(mut json) => {
+                self.redactor.json(&mut json);
+                json.to_string()
+            }
+            Err(_) => String::from_utf8_lossy(body).into_owned(),
+        };
+        truncate(text, self.max_bytes)
+    }
+}
+
+fn truncate(mut text: String, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let total = text.len();
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    text.push_str(&format!("... ({total} bytes)"));
+    text
+}
+
+/// Middleware logging the bodies of requests to the configured routes and
+/// of their responses.
+pub async fn log_bodies(
+    State(logging): State<Arc<BodyLogging>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = match request.extensions().get::<MatchedPath>() {
+        Some(route) if logging.routes.contains(route.as_str()) => route.as_str().to_string(),
+        _ => return next.run(request).await,
+    };
+
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_BUFFERED_BYTES).await else {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            ApiResponse::error("Request body too large for debug body logging"),
+        )
+            .into_response();
+    };
+    debug!(
+        "{} {} request headers: {}",
+        parts.method,
+        route,
+        logging.redactor.headers(&parts.headers)
+    );
+    debug!(
+        "{} {} request body: {}",
+        parts.method,
+        route,
+        logging.render(&bytes)
+    );
+
+    let response = next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await;
+
+    let (parts, body) = response.into_parts();
+    if is_streaming(&parts.headers) {
+        debug!(
+            "{} response body ({}): streamed, not logged",
+            route, parts.status
+        );
+        return Response::from_parts(parts, body);
+    }
+    let mut copy = ResponseCopy {
+        logging,
+        route,
+        status: parts.status,
+        json: parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("json")),
+        bytes: Vec::new(),
+        overflowed: false,
+    };
+    let body = body.into_data_stream().map(move |chunk| {
+        if let Ok(bytes) = &chunk {
+            copy.push(bytes);
+        }
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+fn is_streaming(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            STREAMING_CONTENT_TYPES
+                .iter()
+                .any(|streaming| value.starts_with(streaming))
+        })
+}
+
+/// The first `MAX_BUFFERED_BYTES` of a response body as it is sent, logged
+/// when the body is dropped: after its last chunk, or early if the client
+/// went away.
+struct ResponseCopy {
+    logging: Arc<BodyLogging>,
+    route: String,
+    status: StatusCode,
+    json: bool,
+    bytes: Vec<u8>,
+    overflowed: bool,
+}
+
+impl ResponseCopy {
+    fn push(&mut self, chunk: &[u8]) {
+        let room = MAX_BUFFERED_BYTES - self.bytes.len();
+        self.overflowed |= chunk.len() > room;
+        self.bytes
+            .extend_from_slice(&chunk[..chunk.len().min(room)]);
+    }
+}
+
+impl Drop for ResponseCopy {
+    fn drop(&mut self) {
+        // A cut-off JSON document can't be parsed to redact it
+        let body = if self.overflowed && self.json {
+            format!("over {MAX_BUFFERED_BYTES} bytes of JSON, not logged")
+        } else {
+            self.logging.render(&self.bytes)
+        };
+        debug!("{} response body ({}): {}", self.route, self.status, body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{send, test_state_with, CapturedLogs};
+
+    fn create(state: &crate::AppState) -> impl std::future::Future<Output = Response> + '_ {
+        send(
+            state,
+            Request::post("/items?api_key=opensesame")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, "Bearer hunter2")
+                .body(Body::from(format!(
+                    r#"{{"name":"logged","description":"{}","password":"abc123"}}"#,
+                    "x".repeat(200)
+                )))
+                .unwrap(),
+        )
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn logs_truncated_redacted_bodies_for_selected_routes() {
+        let state = test_state_with(Config {
+            debug_body_routes: vec!["/items".to_string()],
+            debug_body_max_bytes: 64,
+            ..Config::default()
+        });
+        let logs = CapturedLogs::default();
+        let _guard = logs.install();
+
+        assert_eq!(create(&state).await.status(), StatusCode::CREATED);
+
+        let output = logs.contents();
+        assert!(output.contains("authorization: ***"), "{output}");
+        assert!(output.contains(r#"POST /items request body: {"description":"xxx"#));
+        assert!(output.contains("/items response body (201 Created): "));
+        assert!(output.contains(" bytes)"), "truncated: {output}");
+        assert!(!output.contains(&"x".repeat(200)));
+        assert!(!output.contains("abc123"));
+        assert!(!output.contains("hunter2"));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn streamed_responses_pass_through_untouched() {
+        let state = test_state_with(Config {
+            debug_body_routes: ["/items", "/items/changes", "/items/export"]
+                .map(String::from)
+                .to_vec(),
+            ..Config::default()
+        });
+        crate::test_support::seed(&state, 3).await;
+        let logs = CapturedLogs::default();
+        let _guard = logs.install();
+
+        // The change feed never ends, yet its events still get through
+        let changes = send(
+            &state,
+            Request::get("/items/changes").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(changes.status(), StatusCode::OK);
+        let mut events = changes.into_body().into_data_stream();
+        assert_eq!(create(&state).await.status(), StatusCode::CREATED);
+        let event = events.next().await.unwrap().unwrap();
+        assert!(event.starts_with(b"event: created\n"));
+
+        let ndjson = send(
+            &state,
+            Request::get("/items")
+                .header(header::ACCEPT, "application/x-ndjson")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        let lines = to_bytes(ndjson.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            lines
+                .split(|&b| b == b'\n')
+                .filter(|l| !l.is_empty())
+                .count(),
+            4
+        );
+
+        let export = send(
+            &state,
+            Request::get("/items/export").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(export.status(), StatusCode::OK);
+        let exported = to_bytes(export.into_body(), usize::MAX).await.unwrap();
+        assert!(!exported.is_empty());
+
+        let output = logs.contents();
+        assert!(
+            output.contains("/items/changes response body (200 OK): streamed, not logged"),
+            "{output}"
+        );
+        assert!(
+            output.contains("/items/export response body (200 OK): "),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn oversized_json_is_not_logged_half_redacted() {
+        let logging = Arc::new(BodyLogging::new(&Config::default()));
+        let logs = CapturedLogs::default();
+        let _guard = logs.install();
+
+        let mut copy = ResponseCopy {
+            logging,
+            route: "/items".to_string(),
+            status: StatusCode::OK,
+            json: true,
+            bytes: Vec::new(),
+            overflowed: false,
+        };
+        copy.push(br#"{"password":"abc123","#);
+        copy.push(&vec![b' '; MAX_BUFFERED_BYTES]);
+        assert!(copy.overflowed);
+        assert_eq!(copy.bytes.len(), MAX_BUFFERED_BYTES);
+        drop(copy);
+
+        let output = logs.contents();
+        assert!(output.contains("of JSON, not logged"), "{output}");
+        assert!(!output.contains("abc123"));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn logs_nothing_when_disabled() {
+        let state = test_state_with(Config::default());
+        let logs = CapturedLogs::default();
+        let _guard = logs.install();
+
+        assert_eq!(create(&state).await.status(), StatusCode::CREATED);
+        assert!(!logs.contents().contains("body"));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn redacts_passwords_and_authorization_in_full() {
+        let state = test_state_with(Config {
+            debug_body_routes: vec!["/items".to_string()],
+            ..Config::default()
+        });
+        let logs = CapturedLogs::default();
+        let _guard = logs.install();
+
+        assert_eq!(create(&state).await.status(), StatusCode::CREATED);
+
+        let output = logs.contents();
+        assert!(output.contains(r#""password":"***""#), "{output}");
+        assert!(output.contains("authorization: ***"), "{output}");
+        // The access log's span carries the URI, query included
+        assert!(output.contains("uri=/items?api_key=***"), "{output}");
+        assert!(output.contains("finished processing request"), "{output}");
+        assert!(!output.contains("abc123"));
+        assert!(!output.contains("hunter2"));
+        assert!(!output.contains("opensesame"));
+    }
+}
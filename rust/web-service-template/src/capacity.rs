@@ -0,0 +1,289 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+use crate::changes::{Change, ChangeFeed};
+use crate::config::{Config, EvictionPolicy};
+use crate::history::ItemHistory;
+use crate::ids::ItemId;
+use crate::items::Item;
+
+/// Bounds the store at `MAX_ITEMS` items. Once it is full, creates are
+/// refused or the least recently used items are evicted to make room, per
+/// `ITEM_EVICTION`.
+///
+/// Use is tracked only for LRU eviction: reads and writes of single items
+/// [`touch`](Capacity::touch) them, listings don't. Items never touched
+/// (restored from a snapshot) count as the least recently used, lowest id
+/// first.
+///
+/// Items are kept ordered by last use, so finding the next to evict doesn't
+/// mean sorting the store on every create once it is full.
+pub struct Capacity {
+    max: Option<usize>,
+    policy: EvictionPolicy,
+    history: Arc<ItemHistory>,
+    changes: Arc<ChangeFeed>,
+    uses: Mutex<Uses>,
+    now: AtomicU64,
+}
+
+/// When each item was last used, by logical time; higher is more recent
+/// and 0 means never. Entries of items removed some other way (expiry,
+/// replace) linger until eviction reaches them or they outnumber the store.
+#[derive(Default)]
+struct Uses {
+    by_id: HashMap<ItemId, u64>,
+    /// The same entries, least recently used first.
+    ordered: BTreeSet<(u64, ItemId)>,
+    /// Whether items that were never touched have been entered at time 0.
+    /// Every create touches its items, so this is needed only once.
+    seeded: bool,
+}
+
+impl Uses {
+    fn remove(&mut self, id: &ItemId) {
+        if let Some(used) = self.by_id.remove(id) {
+            self.ordered.remove(&(used, id.clone()));
+        }
+    }
+}
+
+impl Capacity {
+    pub fn new(config: &Config, history: Arc<ItemHistory>, changes: Arc<ChangeFeed>) -> Self {
+        Self {
+            max: config.max_items,
+            policy: config.item_eviction,
+            history,
+            changes,
+            uses: Mutex::default(),
+            now: AtomicU64::new(1),
+        }
+    }
+
+    fn tracks_use(&self) -> bool {
+        self.max.is_some() && self.policy == EvictionPolicy::Lru
+    }
+
+    /// Marks `id` as the most recently used item.
+    pub fn touch(&self, id: &ItemId) {
+        if self.tracks_use() {
+            let now = self.now.fetch_add(1, Ordering::Relaxed);
+            let mut uses = self.uses.lock().unwrap();
+            uses.remove(id);
+            uses.by_id.insert(id.clone(), now);
+            uses.ordered.insert((now, id.clone()));
+        }
+    }
+
+    /// Makes room in `items` for `count` new items, evicting the least
+    /// recently used ones under LRU. The error says why there is no room:
+    /// the store is full and evicting is off, or `count` alone is more
+    /// than it holds.
+    pub fn make_room(
+        &self,
+        items: &mut HashMap<ItemId, Arc<Item>>,
+        count: usize,
+    ) -> Result<(), String> {
+        let Some(max) = self.max else {
+            return Ok(());
+        };
+        let excess = (items.len() + count).saturating_sub(max);
+        if excess == 0 {
+            return Ok(());
+        }
+        if self.policy == EvictionPolicy::Reject || count > max {
+            return Err(format!(
+                "The store holds at most {max} items and has room for {}",
+                max.saturating_sub(items.len())
+            ));
+        }
+
+        let mut uses = self.uses.lock().unwrap();
+        let uses = &mut *uses;
+        if !uses.seeded {
+            for id in items.keys() {
+                if !uses.by_id.contains_key(id) {
+                    uses.by_id.insert(id.clone(), 0);
+                    uses.ordered.insert((0, id.clone()));
+                }
+            }
+            uses.seeded = true;
+        }
+        // Drop entries of items removed some other way once they could
+        // outnumber the store, which keeps this amortized constant per create
+        if uses.by_id.len() > 2 * max {
+            uses.by_id.retain(|id, _| items.contains_key(id));
+            let by_id = &uses.by_id;
+            uses.ordered.retain(|(_, id)| by_id.contains_key(id));
+        }
+
+        let mut evicted: Vec<ItemId> = Vec::with_capacity(excess);
+        while evicted.len() < excess {
+            let Some((_, id)) = uses.ordered.pop_first() else {
+                break;
+            };
+            uses.by_id.remove(&id);
+            if items.contains_key(&id) {
+                evicted.push(id);
+            }
+        }
+
+        for id in &evicted {
+            if let Some(item) = items.remove(id) {
+                self.changes.publish(Change::Deleted, &item);
+            }
+            uses.remove(id);
+        }
+        self.history.retain(|id| !evicted.contains(id));
+        debug!("Evicted {} least recently used items", evicted.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{body_json, send, test_state_with};
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+
+    fn create(name: &str) -> Request<Body> {
+        Request::post("/items")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(format!(
+                r#"{{"name":"{name}","description":""}}"#
+            )))
+            .unwrap()
+    }
+
+    fn get(id: u32) -> Request<Body> {
+        Request::get(format!("/items/{id}"))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn full_at(max_items: usize, item_eviction: EvictionPolicy) -> Config {
+        Config {
+            max_items: Some(max_items),
+            item_eviction,
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn a_full_store_rejects_creates() {
+        let state = test_state_with(full_at(2, EvictionPolicy::Reject));
+        for name in ["a", "b"] {
+            assert_eq!(
+                send(&state, create(name)).await.status(),
+                StatusCode::CREATED
+            );
+        }
+
+        let response = send(&state, create("c")).await;
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+        assert_eq!(
+            body_json(response).await["message"],
+            "The store holds at most 2 items and has room for 0"
+        );
+        assert_eq!(state.store.read().await.len(), 2);
+
+        // Replacing an item doesn't need room
+        let put = Request::put("/items/1")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"name":"a2","description":""}"#))
+            .unwrap();
+        assert_eq!(send(&state, put).await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn lru_evicts_the_least_recently_used_item() {
+        let state = test_state_with(full_at(3, EvictionPolicy::Lru));
+        for name in ["a", "b", "c"] {
+            assert_eq!(
+                send(&state, create(name)).await.status(),
+                StatusCode::CREATED
+            );
+        }
+        // 1 is read after 2 and 3 were written, so 2 is the least recent
+        assert_eq!(send(&state, get(1)).await.status(), StatusCode::OK);
+
+        assert_eq!(
+            send(&state, create("d")).await.status(),
+            StatusCode::CREATED
+        );
+        let mut ids: Vec<ItemId> = state.store.read().await.keys().cloned().collect();
+        ids.sort();
+        assert_eq!(ids, [1, 3, 4].map(ItemId::from));
+        assert_eq!(send(&state, get(2)).await.status(), StatusCode::NOT_FOUND);
+
+        // Then 3
+        assert_eq!(
+            send(&state, create("e")).await.status(),
+            StatusCode::CREATED
+        );
+        assert!(!state.store.read().await.contains_key(&ItemId::from(3)));
+    }
+
+    #[tokio::test]
+    async fn bulk_creates_need_room_for_the_whole_batch() {
+        let state = test_state_with(full_at(3, EvictionPolicy::Reject));
+        assert_eq!(
+            send(&state, create("a")).await.status(),
+            StatusCode::CREATED
+        );
+
+        let bulk = |names: &[&str]| {
+            let entries: Vec<_> = names
+                .iter()
+                .map(|name| serde_json::json!({"name": name, "description": ""}))
+                .collect();
+            Request::post("/items/bulk")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(serde_json::to_vec(&entries).unwrap()))
+                .unwrap()
+        };
+        let response = send(&state, bulk(&["b", "c", "d"])).await;
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+        assert_eq!(state.store.read().await.len(), 1, "nothing created");
+
+        let response = send(&state, bulk(&["b", "c"])).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(state.store.read().await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn running_out_of_ids_evicts_nothing() {
+        let state = test_state_with(full_at(2, EvictionPolicy::Lru));
+        let put = Request::put(format!("/items/{}", u32::MAX - 1))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"name":"a","description":""}"#))
+            .unwrap();
+        assert_eq!(send(&state, put).await.status(), StatusCode::CREATED);
+        // Takes the last id, filling the store
+        assert_eq!(
+            send(&state, create("b")).await.status(),
+            StatusCode::CREATED
+        );
+        let before: Vec<_> = state.store.read().await.values().cloned().collect();
+
+        let response = send(&state, create("c")).await;
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+        let bulk = Request::post("/items/bulk")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"[{"name":"c","description":""}]"#))
+            .unwrap();
+        assert_eq!(
+            send(&state, bulk).await.status(),
+            StatusCode::INSUFFICIENT_STORAGE
+        );
+
+        let store = state.store.read().await;
+        assert_eq!(store.len(), 2, "nothing evicted");
+        assert!(before.iter().all(|item| store.contains_key(&item.id)));
+    }
+}
@@ -0,0 +1,303 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use futures::stream::{self, Stream};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::items::Item;
+use crate::{ApiResponse, AppState};
+
+/// What happened to an item, sent as the SSE event name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Created,
+    Updated,
+    /// Removed by the store itself: expired or evicted.
+    Deleted,
+}
+
+impl Change {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Deleted => "deleted",
+        }
+    }
+}
+
+/// What the feed carries.
+#[derive(Debug, Clone)]
+enum Message {
+    Item(Change, Arc<Item>),
+    /// The store changed in too many ways to describe item by item (an
+    /// import); the data names the operation.
+    Resync(&'static str),
+}
+
+/// Broadcasts every change to the store to the subscribers of
+/// `GET /items/changes`: per item for creates (bulk ones included), puts,
+/// patches, expiry purges and evictions, and as a single `resync` after an
+/// import, after which subscribers should re-read what they mirror.
+///
+/// Publishing never waits for subscribers. Each one has room for
+/// `SSE_MAX_LAG` changes it hasn't read yet; one that falls further behind
+/// is sent a final `lagged` event and disconnected, rather than holding up
+/// the others or buffering without bound. At most `SSE_MAX_SUBSCRIBERS`
+/// are connected at once.
+pub struct ChangeFeed {
+    sender: broadcast::Sender<Message>,
+    max_subscribers: usize,
+    slots: Arc<Semaphore>,
+}
+
+/// A subscriber's receiver, holding its slot until it is dropped.
+struct Subscription {
+    receiver: broadcast::Receiver<Message>,
+    _slot: OwnedSemaphorePermit,
+}
+
+impl ChangeFeed {
+    pub fn new(max_subscribers: usize, max_lag: usize) -> Self {
+        let (sender, _) = broadcast::channel(max_lag);
+        Self {
+            sender,
+            max_subscribers,
+            slots: Arc::new(Semaphore::new(max_subscribers)),
+        }
+    }
+
+    pub fn publish(&self, change: Change, item: &Item) {
+        // Fails only when nobody is subscribed
+        let _ = self
+            .sender
+            .send(Message::Item(change, Arc::new(item.clone())));
+    }
+
+    /// Tells subscribers to re-read the store after `operation`.
+    pub fn resync(&self, operation: &'static str) {
+        let _ = self.sender.send(Message::Resync(operation));
+    }
+
+    /// `None` when every slot is taken.
+    fn subscribe(&self) -> Option<Subscription> {
+        let slot = self.slots.clone().try_acquire_owned().ok()?;
+        Some(Subscription {
+            receiver: self.sender.subscribe(),
+            _slot: slot,
+        })
+    }
+}
+
+/// The subscription's changes as SSE events, ending after a `lagged` event
+/// or once graceful shutdown begins, so open streams don't hold up the drain.
+fn events(
+    subscription: Subscription,
+    shutdown: CancellationToken,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    stream::unfold(Some(subscription), move |subscription| {
+        let shutdown = shutdown.clone();
+        async move {
+            let mut subscription = subscription?;
+            let received = tokio::select! {
+                received = subscription.receiver.recv() => received,
+                _ = shutdown.cancelled() => return None,
+            };
+            match received {
+                Ok(Message::Item(change, item)) => {
+                    let event = Event::default()
+                        .event(change.name())
+                        .json_data(&*item)
+                        .expect("an item always serializes");
+                    Some((Ok(event), Some(subscription)))
+                }
+                Ok(Message::Resync(operation)) => {
+                    let event = Event::default().event("resync").data(operation);
+                    Some((Ok(event), Some(subscription)))
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    debug!(
+                        "Disconnecting a change subscriber {} changes behind",
+                        missed
+                    );
+                    let event = Event::default().event("lagged").data(missed.to_string());
+                    Some((Ok(event), None))
+                }
+                Err(RecvError::Closed) => None,
+            }
+        }
+    })
+}
+
+/// Streams item changes as server-sent events: `created`, `updated` and
+/// `deleted`, each carrying the item, and `resync`, naming the operation
+/// after which the store should be re-read. A subscriber that falls too far
+/// behind gets a `lagged` event with the number of changes it missed and is
+/// disconnected; it should re-read what it needs and subscribe again. Past
+/// `SSE_MAX_SUBSCRIBERS`, new subscribers get 503.
+pub async fn item_changes(State(state): State<AppState>) -> Response {
+    let Some(subscription) = state.changes.subscribe() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            ApiResponse::error(format!(
+                "At most {} change subscribers at once",
+                state.changes.max_subscribers
+            )),
+        )
+            .into_response();
+    };
+    Sse::new(events(subscription, state.shutdown.clone()))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Config, EvictionPolicy};
+    use crate::test_support::{send, test_state, test_state_with};
+    use axum::{
+        body::{Body, BodyDataStream},
+        http::{header, Request, StatusCode},
+    };
+    use futures::StreamExt;
+
+    fn subscribe() -> Request<Body> {
+        Request::get("/items/changes").body(Body::empty()).unwrap()
+    }
+
+    fn create(name: &str) -> Request<Body> {
+        Request::post("/items")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(format!(
+                r#"{{"name":"{name}","description":""}}"#
+            )))
+            .unwrap()
+    }
+
+    fn post_json(uri: &str, body: &str) -> Request<Body> {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    /// The next event on the stream, `None` once it has ended.
+    async fn next_event(events: &mut BodyDataStream) -> Option<String> {
+        let chunk = events.next().await?.unwrap();
+        Some(String::from_utf8(chunk.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn subscribers_past_the_limit_get_503() {
+        let state = test_state_with(Config {
+            sse_max_subscribers: 2,
+            ..Config::default()
+        });
+
+        let first = send(&state, subscribe()).await;
+        let second = send(&state, subscribe()).await;
+        assert_eq!(
+            (first.status(), second.status()),
+            (StatusCode::OK, StatusCode::OK)
+        );
+        assert_eq!(
+            send(&state, subscribe()).await.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        // Disconnecting frees the slot
+        drop(first);
+        assert_eq!(send(&state, subscribe()).await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn a_slow_subscriber_is_disconnected_without_holding_up_others() {
+        let state = test_state_with(Config {
+            sse_max_lag: 2,
+            ..Config::default()
+        });
+        let mut fast = send(&state, subscribe())
+            .await
+            .into_body()
+            .into_data_stream();
+        let mut slow = send(&state, subscribe())
+            .await
+            .into_body()
+            .into_data_stream();
+
+        for name in ["a", "b", "c"] {
+            assert_eq!(
+                send(&state, create(name)).await.status(),
+                StatusCode::CREATED
+            );
+            let event = next_event(&mut fast).await.unwrap();
+            assert!(event.starts_with("event: created\n"), "{event}");
+            assert!(event.contains(&format!(r#""name":"{name}""#)), "{event}");
+        }
+
+        // Three changes behind with room for two
+        assert_eq!(
+            next_event(&mut slow).await.unwrap(),
+            "event: lagged\ndata: 1\n\n"
+        );
+        assert_eq!(next_event(&mut slow).await, None);
+
+        send(&state, create("d")).await;
+        assert!(next_event(&mut fast).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn bulk_creates_and_imports_are_published() {
+        let state = test_state();
+        let mut events = send(&state, subscribe())
+            .await
+            .into_body()
+            .into_data_stream();
+
+        let bulk = r#"[{"name":"a","description":""},{"name":"b","description":""}]"#;
+        send(&state, post_json("/items/bulk", bulk)).await;
+        for name in ["a", "b"] {
+            let event = next_event(&mut events).await.unwrap();
+            assert!(event.starts_with("event: created\n"), "{event}");
+            assert!(event.contains(&format!(r#""name":"{name}""#)), "{event}");
+        }
+
+        let import = r#"[{"name":"c","description":""}]"#;
+        send(&state, post_json("/items/import?mode=replace", import)).await;
+        assert_eq!(
+            next_event(&mut events).await.unwrap(),
+            "event: resync\ndata: import\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn evicted_items_are_published_as_deleted() {
+        let state = test_state_with(Config {
+            max_items: Some(1),
+            item_eviction: EvictionPolicy::Lru,
+            ..Config::default()
+        });
+        send(&state, create("old")).await;
+        let mut events = send(&state, subscribe())
+            .await
+            .into_body()
+            .into_data_stream();
+
+        send(&state, create("new")).await;
+        let event = next_event(&mut events).await.unwrap();
+        assert!(event.starts_with("event: deleted\n"), "{event}");
+        assert!(event.contains(r#""name":"old""#), "{event}");
+        let event = next_event(&mut events).await.unwrap();
+        assert!(event.starts_with("event: created\n"), "{event}");
+    }
+}
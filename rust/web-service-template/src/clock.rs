@@ -0,0 +1,24 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of wall-clock time, injectable so time-dependent behaviour such as
+/// item expiry can be tested without waiting on the real clock.
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> SystemTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Seconds since the Unix epoch, the resolution item timestamps are kept at.
+pub fn unix_secs(clock: &dyn Clock) -> u64 {
+    clock
+        .now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
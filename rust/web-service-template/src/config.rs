@@ -0,0 +1,1114 @@
+use axum::http::HeaderValue;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::list_query::MAX_LIST_LIMIT;
+
+/// Per-route request timeouts keyed by route template, parsed from
+/// `route=millis` pairs separated by commas, e.g.
+/// `/items/import=120000,/items/export=60000`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteTimeouts(pub BTreeMap<String, Duration>);
+
+impl FromStr for RouteTimeouts {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (route, millis) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected route=millis, got {entry:?}"))?;
+                let millis: u64 = millis
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|ms| *ms > 0)
+                    .ok_or_else(|| format!("invalid timeout in {entry:?}"))?;
+                Ok((route.trim().to_string(), Duration::from_millis(millis)))
+            })
+            .collect::<Result<_, String>>()
+            .map(Self)
+    }
+}
+
+/// Which components the process runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunMode {
+    /// HTTP server only.
+    Server,
+    /// HTTP server plus the periodic background worker, sharing the store and
+    /// shutdown signal.
+    Combined,
+}
+
+impl FromStr for RunMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "server" => Ok(Self::Server),
+            "combined" => Ok(Self::Combined),
+            other => Err(format!("unknown run mode: {other}")),
+        }
+    }
+}
+
+/// How new items get their ids (`ID_SCHEME`); see [`crate::ids`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdScheme {
+    /// 1, 2, 3, ...
+    Sequential,
+    /// Random UUIDs.
+    UuidV4,
+    /// Time-ordered UUIDs.
+    UuidV7,
+    /// Time-ordered ULIDs.
+    Ulid,
+}
+
+impl FromStr for IdScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sequential" => Ok(Self::Sequential),
+            "uuid-v4" => Ok(Self::UuidV4),
+            "uuid-v7" => Ok(Self::UuidV7),
+            "ulid" => Ok(Self::Ulid),
+            other => Err(format!("unknown id scheme: {other}")),
+        }
+    }
+}
+
+/// Which creates count as the same submission for `CREATE_DEDUPE_WINDOW_MS`
+/// (`CREATE_DEDUPE_KEY`); see [`crate::dedupe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupeKey {
+    /// The same payload from the same client, by `X-API-Key`.
+    Client,
+    /// The same payload from any client.
+    Payload,
+}
+
+impl FromStr for DedupeKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "client" => Ok(Self::Client),
+            "payload" => Ok(Self::Payload),
+            other => Err(format!("unknown dedupe key: {other}")),
+        }
+    }
+}
+
+/// What a create does once the store holds `MAX_ITEMS` items
+/// (`ITEM_EVICTION`); see [`crate::capacity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EvictionPolicy {
+    /// Fail the create with 507.
+    Reject,
+    /// Evict the least recently read or written item.
+    Lru,
+}
+
+impl FromStr for EvictionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject" => Ok(Self::Reject),
+            "lru" => Ok(Self::Lru),
+            other => Err(format!("unknown eviction policy: {other}")),
+        }
+    }
+}
+
+/// Runtime configuration, read from environment variables at startup.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Address the HTTP server binds to (`BIND_ADDR`).
+    pub bind_addr: String,
+    /// `RUN_MODE`: `server` (default) or `combined`.
+    pub run_mode: RunMode,
+    /// Interval between background worker ticks in combined mode
+    /// (`WORKER_INTERVAL_SECS`).
+    pub worker_interval: Duration,
+    /// p99 latency above which new requests start being shed
+    /// (`LOAD_SHED_LATENCY_BUDGET_MS`). `None` disables load shedding.
+    pub load_shed_latency_budget: Option<Duration>,
+    /// `Retry-After` value sent with shed responses (`LOAD_SHED_RETRY_AFTER_SECS`).
+    pub load_shed_retry_after_secs: u64,
+    /// In-flight API requests above which `/readyz` reports not ready
+    /// (`READY_HIGH_WATER`). `None` ties readiness to nothing but draining.
+    pub ready_high_water: Option<usize>,
+    /// In-flight count an overloaded instance must drain to before `/readyz`
+    /// recovers (`READY_LOW_WATER`). Defaults to half the high-water mark.
+    pub ready_low_water: Option<usize>,
+    /// Most requests handled at once (`MAX_CONCURRENT_REQUESTS`); the rest
+    /// queue by route priority. `None` admits everything immediately.
+    pub max_concurrent_requests: Option<usize>,
+    /// Requests each API key (`X-API-Key`) may make per day (`DAILY_QUOTA`).
+    /// `None` sets no quota.
+    pub daily_quota: Option<u64>,
+    /// UTC hour at which daily quotas reset (`QUOTA_RESET_HOUR`, 0-23).
+    pub quota_reset_hour: u8,
+    /// Most API keys given a quota of their own each day
+    /// (`QUOTA_MAX_KEYS`); keys first seen past it share one.
+    pub quota_max_keys: usize,
+    /// Upper bound on handling a request (`REQUEST_TIMEOUT_MS`). Handlers see
+    /// the remaining time as a `Deadline` for their downstream calls.
+    pub request_timeout: Duration,
+    /// Overrides of `request_timeout` for individual route templates
+    /// (`ROUTE_TIMEOUTS_MS`, e.g. `/items/import=120000`).
+    pub route_timeouts: RouteTimeouts,
+    /// Longest accepted request path plus query string, in bytes
+    /// (`MAX_URI_BYTES`). Longer requests get 414 URI Too Long.
+    pub max_uri_bytes: usize,
+    /// Most entries accepted by one `/items/import` request
+    /// (`MAX_IMPORT_ITEMS`). The body is parsed as it streams in, so this
+    /// bounds the work per request rather than the memory held.
+    pub max_import_items: usize,
+    /// Most ids accepted by one `/items/batch-get` request
+    /// (`MAX_BATCH_GET_IDS`).
+    pub max_batch_get_ids: usize,
+    /// Page size `GET /items` uses when the request gives no `limit`
+    /// (`DEFAULT_LIST_LIMIT`), so a bare listing can't dump the whole store.
+    pub default_list_limit: usize,
+    /// `GET /items` pages with at least this many items are streamed item
+    /// by item instead of serialized in one go (`STREAM_LIST_MIN_ITEMS`).
+    /// `None` always buffers.
+    pub stream_list_min_items: Option<usize>,
+    /// Largest `offset` a list endpoint accepts (`MAX_LIST_OFFSET`); larger
+    /// ones get 400. Offsets past the end of the results, but within this,
+    /// get an empty page.
+    pub max_list_offset: usize,
+    /// Identical create payloads within this window return the first item
+    /// instead of creating another (`CREATE_DEDUPE_WINDOW_MS`). Zero disables.
+    pub create_dedupe_window: Duration,
+    /// Whether deduplicated creates must come from the same client
+    /// (`CREATE_DEDUPE_KEY`: `client` or `payload`).
+    pub create_dedupe_key: DedupeKey,
+    /// Items older than this are hidden from reads and purged in the
+    /// background (`ITEM_TTL_SECS`). `None` keeps items forever.
+    pub item_ttl: Option<Duration>,
+    /// How often expired items are purged when a TTL is set
+    /// (`ITEM_PURGE_INTERVAL_SECS`).
+    pub item_purge_interval: Duration,
+    /// Reject creates and updates that would give an item a name another
+    /// live item already has, with 409 (`UNIQUE_NAME`).
+    pub unique_name: bool,
+    /// How new items get their ids (`ID_SCHEME`: `sequential`, `uuid-v4`,
+    /// `uuid-v7` or `ulid`).
+    pub id_scheme: IdScheme,
+    /// Longest item description, in characters, accepted by creates and
+    /// updates; longer ones are a 422 (`MAX_DESCRIPTION_LEN`).
+    pub max_description_len: usize,
+    /// Versions kept per item for `GET /items/:id/diff`
+    /// (`ITEM_HISTORY_LIMIT`); older ones can no longer be compared.
+    pub item_history_limit: usize,
+    /// Most items the store holds (`MAX_ITEMS`); `None` is unbounded.
+    pub max_items: Option<usize>,
+    /// What creates do once the store is full (`ITEM_EVICTION`: `reject` or
+    /// `lru`).
+    pub item_eviction: EvictionPolicy,
+    /// Most recent events kept from `POST /ingest` (`INGEST_BUFFER_EVENTS`).
+    pub ingest_buffer_events: usize,
+    /// Most clients subscribed to `GET /items/changes` at once
+    /// (`SSE_MAX_SUBSCRIBERS`); more get 503.
+    pub sse_max_subscribers: usize,
+    /// Changes a subscriber may fall behind by before it is disconnected
+    /// (`SSE_MAX_LAG`).
+    pub sse_max_lag: usize,
+    /// Retries allowed across the whole service per `retry_budget_window`
+    /// (`RETRY_BUDGET`); further retries fail fast.
+    pub retry_budget: u32,
+    /// Window the retry budget refills over (`RETRY_BUDGET_WINDOW_SECS`).
+    pub retry_budget_window: Duration,
+    /// Value of the `API-Version` header on every response (`API_VERSION`).
+    pub api_version: String,
+    /// Route templates (e.g. `/items/export`) answered with `Deprecation:
+    /// true` (`DEPRECATED_ROUTES`, comma-separated).
+    pub deprecated_routes: Vec<String>,
+    /// `Sunset` header sent with deprecated routes, as an HTTP date
+    /// (`API_SUNSET`).
+    pub api_sunset: Option<String>,
+    /// Route templates whose request and response bodies are logged at DEBUG
+    /// for troubleshooting (`DEBUG_BODY_ROUTES`, comma-separated). Empty, the
+    /// default, disables body logging.
+    pub debug_body_routes: Vec<String>,
+    /// Logged bodies are truncated to this many bytes (`DEBUG_BODY_MAX_BYTES`).
+    pub debug_body_max_bytes: usize,
+    /// Fraction of requests traced and access-logged (`TRACE_SAMPLE_RATE`,
+    /// 0.0-1.0). Server errors are logged whatever the rate.
+    pub trace_sample_rate: f64,
+    /// PEM certificate chain to serve HTTPS with (`TLS_CERT_FILE`). Together
+    /// with `tls_key_file` this switches the listener to TLS; the pair is
+    /// reloaded from disk on SIGHUP.
+    pub tls_cert_file: Option<PathBuf>,
+    /// PEM private key matching `tls_cert_file` (`TLS_KEY_FILE`).
+    pub tls_key_file: Option<PathBuf>,
+    /// Oldest TLS version handshakes may use (`TLS_MIN_VERSION`): `1.2` or
+    /// `1.3`.
+    pub tls_min_version: String,
+    /// Cipher suites handshakes may use, by their IANA names such as
+    /// `TLS13_AES_256_GCM_SHA384` (`TLS_CIPHERS`, comma-separated). Empty,
+    /// the default, allows every suite rustls supports.
+    pub tls_ciphers: Vec<String>,
+    /// Header names whose values are masked wherever requests are logged
+    /// (`REDACT_HEADERS`, comma-separated), on top of the credential headers
+    /// that always are.
+    pub redact_headers: Vec<String>,
+    /// JSON fields masked wherever bodies are logged (`REDACT_FIELDS`,
+    /// comma-separated): bare keys match at any depth, dotted paths such as
+    /// `user.pin` only there.
+    pub redact_fields: Vec<String>,
+    /// Per-check timeout for `/healthz/deep` (`HEALTH_CHECK_TIMEOUT_MS`).
+    pub health_check_timeout: Duration,
+    /// How long a `/healthz/deep` result is reused before the checks run
+    /// again (`HEALTH_CACHE_MS`); zero runs them on every request.
+    pub health_cache_ttl: Duration,
+    /// Message returned to requests arriving after shutdown has begun
+    /// (`SHUTDOWN_MESSAGE`).
+    pub shutdown_message: String,
+    /// `Retry-After` value sent while shutting down (`SHUTDOWN_RETRY_AFTER_SECS`).
+    pub shutdown_retry_after_secs: u64,
+    /// Where to write the JSON shutdown report, in addition to logging it
+    /// (`SHUTDOWN_REPORT_FILE`).
+    pub shutdown_report_file: Option<PathBuf>,
+    /// File the store is saved to on graceful shutdown and loaded from on
+    /// startup (`SNAPSHOT_FILE`); unset keeps the store in memory only.
+    pub snapshot_file: Option<PathBuf>,
+    /// Whether graceful shutdown saves the store to `snapshot_file`
+    /// (`PERSIST_ON_SHUTDOWN`); off, the snapshot is only loaded.
+    pub persist_on_shutdown: bool,
+    /// How long shutdown waits for the snapshot to be written before
+    /// exiting without it (`SNAPSHOT_SAVE_TIMEOUT_MS`).
+    pub snapshot_save_timeout: Duration,
+    /// How long shutdown waits for background tasks (workers, flushers) to
+    /// finish once the server has stopped (`TASK_DRAIN_TIMEOUT_MS`).
+    pub task_drain_timeout: Duration,
+    /// Consul agent to register this instance with while it serves
+    /// (`REGISTRY_URL`, e.g. `http://127.0.0.1:8500`). Needs the `consul`
+    /// feature.
+    pub registry_url: Option<String>,
+    /// Name the instance is registered under (`SERVICE_NAME`).
+    pub service_name: String,
+    /// Start in maintenance mode (`MAINTENANCE_MODE`); togglable at runtime
+    /// through `/admin/maintenance`.
+    pub maintenance_mode: bool,
+    /// Mount the `/admin` endpoints (`ADMIN_ENABLED`).
+    pub admin_enabled: bool,
+    /// Bearer token required by `/admin` endpoints when set (`ADMIN_TOKEN`).
+    /// Without it, toggling maintenance and shutting down are refused.
+    /// Secret.
+    pub admin_token: Option<String>,
+    /// Variables [`Config::from_env`] found set but couldn't parse, each
+    /// described as [`Config::validate`] reports it.
+    pub invalid_env: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind_addr: "0.0.0.0:3000".to_string(),
+            run_mode: RunMode::Server,
+            worker_interval: Duration::from_secs(10),
+            load_shed_latency_budget: None,
+            load_shed_retry_after_secs: 1,
+            ready_high_water: None,
+            ready_low_water: None,
+            max_concurrent_requests: None,
+            daily_quota: None,
+            quota_reset_hour: 0,
+            quota_max_keys: 10_000,
+            request_timeout: Duration::from_secs(30),
+            route_timeouts: RouteTimeouts::default(),
+            max_uri_bytes: 8 * 1024,
+            max_import_items: 10_000,
+            max_batch_get_ids: 100,
+            default_list_limit: 100,
+            stream_list_min_items: None,
+            max_list_offset: 1_000_000,
+            create_dedupe_window: Duration::ZERO,
+            create_dedupe_key: DedupeKey::Client,
+            item_ttl: None,
+            item_purge_interval: Duration::from_secs(60),
+            unique_name: false,
+            id_scheme: IdScheme::Sequential,
+            max_description_len: 10_000,
+            item_history_limit: 20,
+            max_items: None,
+            item_eviction: EvictionPolicy::Reject,
+            ingest_buffer_events: 10_000,
+            sse_max_subscribers: 100,
+            sse_max_lag: 256,
+            retry_budget: 20,
+            retry_budget_window: Duration::from_secs(10),
+            api_version: "1".to_string(),
+            deprecated_routes: Vec::new(),
+            api_sunset: None,
+            debug_body_routes: Vec::new(),
+            debug_body_max_bytes: 1024,
+            trace_sample_rate: 1.0,
+            tls_cert_file: None,
+            tls_key_file: None,
+            tls_min_version: "1.2".to_string(),
+            tls_ciphers: Vec::new(),
+            redact_headers: vec!["x-api-key".to_string()],
+            redact_fields: ["password", "secret", "token", "api_key", "apikey"]
+                .map(String::from)
+                .to_vec(),
+            health_check_timeout: Duration::from_secs(1),
+            health_cache_ttl: Duration::ZERO,
+            shutdown_message: "Service is shutting down, please retry shortly".to_string(),
+            shutdown_retry_after_secs: 5,
+            shutdown_report_file: None,
+            snapshot_file: None,
+            persist_on_shutdown: true,
+            snapshot_save_timeout: Duration::from_secs(5),
+            task_drain_timeout: Duration::from_secs(10),
+            registry_url: None,
+            service_name: "web-service-template".to_string(),
+            maintenance_mode: false,
+            admin_enabled: false,
+            admin_token: None,
+            invalid_env: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads every setting, falling back to its default when unset. Values
+    /// that are set but malformed or out of range fall back too, and are
+    /// recorded in `invalid_env` for [`Config::validate`] to reject.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let invalid = RefCell::new(Vec::new());
+
+        let mut config = Self {
+            bind_addr: env::var("BIND_ADDR").unwrap_or(defaults.bind_addr),
+            run_mode: env_parse(&invalid, "RUN_MODE").unwrap_or(defaults.run_mode),
+            worker_interval: env_parse_where(&invalid, "WORKER_INTERVAL_SECS", POSITIVE, |secs| {
+                *secs > 0
+            })
+            .map(Duration::from_secs)
+            .unwrap_or(defaults.worker_interval),
+            load_shed_latency_budget: env_parse_where(
+                &invalid,
+                "LOAD_SHED_LATENCY_BUDGET_MS",
+                POSITIVE,
+                |ms| *ms > 0,
+            )
+            .map(Duration::from_millis),
+            load_shed_retry_after_secs: env_parse(&invalid, "LOAD_SHED_RETRY_AFTER_SECS")
+                .unwrap_or(defaults.load_shed_retry_after_secs),
+            ready_high_water: env_parse(&invalid, "READY_HIGH_WATER"),
+            ready_low_water: env_parse(&invalid, "READY_LOW_WATER"),
+            max_concurrent_requests: env_parse_where(
+                &invalid,
+                "MAX_CONCURRENT_REQUESTS",
+                POSITIVE,
+                |max| *max > 0,
+            ),
+            daily_quota: env_parse(&invalid, "DAILY_QUOTA"),
+            quota_reset_hour: env_parse_where(
+                &invalid,
+                "QUOTA_RESET_HOUR",
+                "an hour, 0-23",
+                |hour| *hour < 24,
+            )
+            .unwrap_or(defaults.quota_reset_hour),
+            quota_max_keys: env_parse_where(&invalid, "QUOTA_MAX_KEYS", POSITIVE, |max| *max > 0)
+                .unwrap_or(defaults.quota_max_keys),
+            request_timeout: env_parse_where(&invalid, "REQUEST_TIMEOUT_MS", POSITIVE, |ms| {
+                *ms > 0
+            })
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.request_timeout),
+            route_timeouts: env_parse(&invalid, "ROUTE_TIMEOUTS_MS")
+                .unwrap_or(defaults.route_timeouts),
+            max_uri_bytes: env_parse(&invalid, "MAX_URI_BYTES").unwrap_or(defaults.max_uri_bytes),
+            max_import_items: env_parse(&invalid, "MAX_IMPORT_ITEMS")
+                .unwrap_or(defaults.max_import_items),
+            max_batch_get_ids: env_parse(&invalid, "MAX_BATCH_GET_IDS")
+                .unwrap_or(defaults.max_batch_get_ids),
+            default_list_limit: env_parse(&invalid, "DEFAULT_LIST_LIMIT")
+                .unwrap_or(defaults.default_list_limit),
+            stream_list_min_items: env_parse(&invalid, "STREAM_LIST_MIN_ITEMS"),
+            max_list_offset: env_parse(&invalid, "MAX_LIST_OFFSET")
+                .unwrap_or(defaults.max_list_offset),
+            create_dedupe_window: env_parse::<u64>(&invalid, "CREATE_DEDUPE_WINDOW_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.create_dedupe_window),
+            create_dedupe_key: env_parse(&invalid, "CREATE_DEDUPE_KEY")
+                .unwrap_or(defaults.create_dedupe_key),
+            item_ttl: env_parse_where(&invalid, "ITEM_TTL_SECS", POSITIVE, |secs| *secs > 0)
+                .map(Duration::from_secs),
+            item_purge_interval: env_parse_where(
+                &invalid,
+                "ITEM_PURGE_INTERVAL_SECS",
+                POSITIVE,
+                |secs| *secs > 0,
+            )
+            .map(Duration::from_secs)
+            .unwrap_or(defaults.item_purge_interval),
+            unique_name: env_parse(&invalid, "UNIQUE_NAME").unwrap_or(defaults.unique_name),
+            id_scheme: env_parse(&invalid, "ID_SCHEME").unwrap_or(defaults.id_scheme),
+            max_description_len: env_parse(&invalid, "MAX_DESCRIPTION_LEN")
+                .unwrap_or(defaults.max_description_len),
+            item_history_limit: env_parse_where(
+                &invalid,
+                "ITEM_HISTORY_LIMIT",
+                POSITIVE,
+                |limit| *limit > 0,
+            )
+            .unwrap_or(defaults.item_history_limit),
+            max_items: env_parse_where(&invalid, "MAX_ITEMS", POSITIVE, |max| *max > 0),
+            item_eviction: env_parse(&invalid, "ITEM_EVICTION").unwrap_or(defaults.item_eviction),
+            ingest_buffer_events: env_parse(&invalid, "INGEST_BUFFER_EVENTS")
+                .unwrap_or(defaults.ingest_buffer_events),
+            sse_max_subscribers: env_parse(&invalid, "SSE_MAX_SUBSCRIBERS")
+                .unwrap_or(defaults.sse_max_subscribers),
+            sse_max_lag: env_parse(&invalid, "SSE_MAX_LAG").unwrap_or(defaults.sse_max_lag),
+            retry_budget: env_parse(&invalid, "RETRY_BUDGET").unwrap_or(defaults.retry_budget),
+            retry_budget_window: env_parse_where(
+                &invalid,
+                "RETRY_BUDGET_WINDOW_SECS",
+                POSITIVE,
+                |secs| *secs > 0,
+            )
+            .map(Duration::from_secs)
+            .unwrap_or(defaults.retry_budget_window),
+            api_version: env::var("API_VERSION").unwrap_or(defaults.api_version),
+            deprecated_routes: env_list("DEPRECATED_ROUTES").unwrap_or(defaults.deprecated_routes),
+            api_sunset: env::var("API_SUNSET").ok().filter(|s| !s.is_empty()),
+            debug_body_routes: env_list("DEBUG_BODY_ROUTES").unwrap_or(defaults.debug_body_routes),
+            debug_body_max_bytes: env_parse(&invalid, "DEBUG_BODY_MAX_BYTES")
+                .unwrap_or(defaults.debug_body_max_bytes),
+            trace_sample_rate: env_parse_where(
+                &invalid,
+                "TRACE_SAMPLE_RATE",
+                "between 0.0 and 1.0",
+                |rate| (0.0..=1.0).contains(rate),
+            )
+            .unwrap_or(defaults.trace_sample_rate),
+            tls_cert_file: env::var_os("TLS_CERT_FILE")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            tls_key_file: env::var_os("TLS_KEY_FILE")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            tls_min_version: env::var("TLS_MIN_VERSION")
+                .map(|version| version.trim().to_string())
+                .unwrap_or(defaults.tls_min_version),
+            tls_ciphers: env_list("TLS_CIPHERS").unwrap_or(defaults.tls_ciphers),
+            redact_headers: env_list("REDACT_HEADERS").unwrap_or(defaults.redact_headers),
+            redact_fields: env_list("REDACT_FIELDS").unwrap_or(defaults.redact_fields),
+            health_check_timeout: env_parse_where(
+                &invalid,
+                "HEALTH_CHECK_TIMEOUT_MS",
+                POSITIVE,
+                |ms| *ms > 0,
+            )
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.health_check_timeout),
+            health_cache_ttl: env_parse(&invalid, "HEALTH_CACHE_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.health_cache_ttl),
+            shutdown_message: env::var("SHUTDOWN_MESSAGE").unwrap_or(defaults.shutdown_message),
+            shutdown_retry_after_secs: env_parse(&invalid, "SHUTDOWN_RETRY_AFTER_SECS")
+                .unwrap_or(defaults.shutdown_retry_after_secs),
+            shutdown_report_file: env::var_os("SHUTDOWN_REPORT_FILE")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            snapshot_file: env::var_os("SNAPSHOT_FILE")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            persist_on_shutdown: env_parse(&invalid, "PERSIST_ON_SHUTDOWN")
+                .unwrap_or(defaults.persist_on_shutdown),
+            snapshot_save_timeout: env_parse::<u64>(&invalid, "SNAPSHOT_SAVE_TIMEOUT_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.snapshot_save_timeout),
+            task_drain_timeout: env_parse::<u64>(&invalid, "TASK_DRAIN_TIMEOUT_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.task_drain_timeout),
+            registry_url: env::var("REGISTRY_URL").ok().filter(|url| !url.is_empty()),
+            service_name: env::var("SERVICE_NAME").unwrap_or(defaults.service_name),
+            maintenance_mode: env_parse(&invalid, "MAINTENANCE_MODE")
+                .unwrap_or(defaults.maintenance_mode),
+            admin_enabled: env_parse(&invalid, "ADMIN_ENABLED").unwrap_or(defaults.admin_enabled),
+            admin_token: env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+            invalid_env: Vec::new(),
+        };
+        config.invalid_env = invalid.into_inner();
+        config
+    }
+
+    /// Checks every setting up front, returning all problems at once rather
+    /// than failing on the first.
+    pub fn validate(&self) -> Result<(), ConfigErrors> {
+        let mut problems = self.invalid_env.clone();
+
+        match self.bind_addr.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {}
+            _ => problems.push(format!(
+                "BIND_ADDR must be host:port, got {:?}",
+                self.bind_addr
+            )),
+        }
+        if self.max_uri_bytes == 0 {
+            problems.push("MAX_URI_BYTES must be greater than zero".to_string());
+        }
+        if self.max_import_items == 0 {
+            problems.push("MAX_IMPORT_ITEMS must be greater than zero".to_string());
+        }
+        if self.max_batch_get_ids == 0 {
+            problems.push("MAX_BATCH_GET_IDS must be greater than zero".to_string());
+        }
+        if !(1..=MAX_LIST_LIMIT).contains(&self.default_list_limit) {
+            problems.push(format!(
+                "DEFAULT_LIST_LIMIT must be between 1 and {MAX_LIST_LIMIT}, got {}",
+                self.default_list_limit
+            ));
+        }
+        match (self.ready_high_water, self.ready_low_water) {
+            (Some(high), Some(low)) if low >= high => problems.push(format!(
+                "READY_LOW_WATER ({low}) must be lower than READY_HIGH_WATER ({high})"
+            )),
+            (None, Some(_)) => {
+                problems.push("READY_LOW_WATER is set but READY_HIGH_WATER is not".to_string())
+            }
+            _ => {}
+        }
+        if self.ingest_buffer_events == 0 {
+            problems.push("INGEST_BUFFER_EVENTS must be greater than zero".to_string());
+        }
+        if self.sse_max_lag == 0 {
+            problems.push("SSE_MAX_LAG must be greater than zero".to_string());
+        }
+        if self.health_check_timeout >= self.request_timeout {
+            problems.push(format!(
+                "HEALTH_CHECK_TIMEOUT_MS ({:?}) must be shorter than REQUEST_TIMEOUT_MS ({:?})",
+                self.health_check_timeout, self.request_timeout
+            ));
+        }
+        if let Some(ttl) = self.item_ttl {
+            if self.item_purge_interval > ttl {
+                problems.push(format!(
+                    "ITEM_PURGE_INTERVAL_SECS ({:?}) must not exceed ITEM_TTL_SECS ({:?})",
+                    self.item_purge_interval, ttl
+                ));
+            }
+        }
+        if HeaderValue::from_str(&self.api_version).is_err() {
+            problems.push(format!(
+                "API_VERSION is not a valid header value: {:?}",
+                self.api_version
+            ));
+        }
+        if let Some(route) = self.route_timeouts.0.keys().find(|r| !r.starts_with('/')) {
+            problems.push(format!(
+                "ROUTE_TIMEOUTS_MS entries must be route templates starting with '/', got {route:?}"
+            ));
+        }
+        if let Some(route) = self.debug_body_routes.iter().find(|r| !r.starts_with('/')) {
+            problems.push(format!(
+                "DEBUG_BODY_ROUTES entries must be route templates starting with '/', got {route:?}"
+            ));
+        }
+        if let Some(route) = self.deprecated_routes.iter().find(|r| !r.starts_with('/')) {
+            problems.push(format!(
+                "DEPRECATED_ROUTES entries must be route templates starting with '/', got {route:?}"
+            ));
+        }
+        if let Some(sunset) = &self.api_sunset {
+            if HeaderValue::from_str(sunset).is_err() {
+                problems.push(format!(
+                    "API_SUNSET is not a valid header value: {sunset:?}"
+                ));
+            }
+        }
+        if self.tls_cert_file.is_some() != self.tls_key_file.is_some() {
+            problems.push("TLS_CERT_FILE and TLS_KEY_FILE must be set together".to_string());
+        }
+        for (key, path) in [
+            ("TLS_CERT_FILE", &self.tls_cert_file),
+            ("TLS_KEY_FILE", &self.tls_key_file),
+        ] {
+            if let Some(path) = path {
+                if let Err(e) = std::fs::File::open(path) {
+                    problems.push(format!("{key} {} is not readable: {e}", path.display()));
+                }
+            }
+        }
+        if !["1.2", "1.3"].contains(&self.tls_min_version.as_str()) {
+            problems.push(format!(
+                "TLS_MIN_VERSION must be 1.2 or 1.3, got {:?}",
+                self.tls_min_version
+            ));
+        } else {
+            #[cfg(feature = "tls")]
+            if let Err(e) = crate::tls::TlsPolicy::new(&self.tls_min_version, &self.tls_ciphers) {
+                problems.push(format!("TLS_CIPHERS is unusable: {e}"));
+            }
+        }
+        if !cfg!(feature = "tls") && (self.tls_cert_file.is_some() || self.tls_key_file.is_some()) {
+            problems.push(
+                "TLS_CERT_FILE and TLS_KEY_FILE need a build with the `tls` feature".to_string(),
+            );
+        }
+        if let Some(url) = &self.registry_url {
+            if !cfg!(feature = "consul") {
+                problems.push("REGISTRY_URL needs a build with the `consul` feature".to_string());
+            } else if !url.starts_with("http://") {
+                problems.push(format!(
+                    "REGISTRY_URL must be an http:// URL of a Consul agent, got {url:?}"
+                ));
+            }
+        }
+        if self.admin_token.is_some() && !self.admin_enabled {
+            problems.push("ADMIN_TOKEN is set but ADMIN_ENABLED is not".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigErrors(problems))
+        }
+    }
+
+    /// A view of the configuration that is safe to expose, with every secret
+    /// replaced by `"***"`.
+    pub fn redacted(&self) -> RedactedConfig {
+        // Destructured without `..` on purpose: a new field won't compile
+        // until it is listed here and explicitly classified as secret or not.
+        let Self {
+            bind_addr,
+            run_mode,
+            worker_interval,
+            load_shed_latency_budget,
+            load_shed_retry_after_secs,
+            ready_high_water,
+            ready_low_water,
+            max_concurrent_requests,
+            daily_quota,
+            quota_reset_hour,
+            quota_max_keys,
+            request_timeout,
+            route_timeouts,
+            max_uri_bytes,
+            max_import_items,
+            max_batch_get_ids,
+            default_list_limit,
+            stream_list_min_items,
+            max_list_offset,
+            create_dedupe_window,
+            create_dedupe_key,
+            item_ttl,
+            item_purge_interval,
+            unique_name,
+            id_scheme,
+            max_description_len,
+            item_history_limit,
+            max_items,
+            item_eviction,
+            ingest_buffer_events,
+            sse_max_subscribers,
+            sse_max_lag,
+            retry_budget,
+            retry_budget_window,
+            api_version,
+            deprecated_routes,
+            api_sunset,
+            debug_body_routes,
+            debug_body_max_bytes,
+            trace_sample_rate,
+            tls_cert_file,
+            tls_key_file,
+            tls_min_version,
+            tls_ciphers,
+            redact_headers,
+            redact_fields,
+            health_check_timeout,
+            health_cache_ttl,
+            shutdown_message,
+            shutdown_retry_after_secs,
+            shutdown_report_file,
+            snapshot_file,
+            persist_on_shutdown,
+            snapshot_save_timeout,
+            task_drain_timeout,
+            registry_url,
+            service_name,
+            maintenance_mode,
+            admin_enabled,
+            admin_token,
+            invalid_env: _,
+        } = self;
+
+        RedactedConfig {
+            bind_addr: bind_addr.clone(),
+            run_mode: *run_mode,
+            worker_interval_secs: worker_interval.as_secs(),
+            load_shed_latency_budget_ms: load_shed_latency_budget.map(|d| d.as_millis() as u64),
+            load_shed_retry_after_secs: *load_shed_retry_after_secs,
+            ready_high_water: *ready_high_water,
+            ready_low_water: *ready_low_water,
+            max_concurrent_requests: *max_concurrent_requests,
+            daily_quota: *daily_quota,
+            quota_reset_hour: *quota_reset_hour,
+            quota_max_keys: *quota_max_keys,
+            request_timeout_ms: request_timeout.as_millis() as u64,
+            route_timeouts_ms: route_timeouts
+                .0
+                .iter()
+                .map(|(route, timeout)| (route.clone(), timeout.as_millis() as u64))
+                .collect(),
+            max_uri_bytes: *max_uri_bytes,
+            max_import_items: *max_import_items,
+            max_batch_get_ids: *max_batch_get_ids,
+            default_list_limit: *default_list_limit,
+            stream_list_min_items: *stream_list_min_items,
+            max_list_offset: *max_list_offset,
+            create_dedupe_window_ms: create_dedupe_window.as_millis() as u64,
+            create_dedupe_key: *create_dedupe_key,
+            item_ttl_secs: item_ttl.map(|d| d.as_secs()),
+            item_purge_interval_secs: item_purge_interval.as_secs(),
+            unique_name: *unique_name,
+            id_scheme: *id_scheme,
+            max_description_len: *max_description_len,
+            item_history_limit: *item_history_limit,
+            max_items: *max_items,
+            item_eviction: *item_eviction,
+            ingest_buffer_events: *ingest_buffer_events,
+            sse_max_subscribers: *sse_max_subscribers,
+            sse_max_lag: *sse_max_lag,
+            retry_budget: *retry_budget,
+            retry_budget_window_secs: retry_budget_window.as_secs(),
+            api_version: api_version.clone(),
+            deprecated_routes: deprecated_routes.clone(),
+            api_sunset: api_sunset.clone(),
+            debug_body_routes: debug_body_routes.clone(),
+            debug_body_max_bytes: *debug_body_max_bytes,
+            trace_sample_rate: *trace_sample_rate,
+            tls_cert_file: tls_cert_file
+                .as_ref()
+                .map(|path| path.display().to_string()),
+            tls_key_file: tls_key_file.as_ref().map(|path| path.display().to_string()),
+            tls_min_version: tls_min_version.clone(),
+            tls_ciphers: tls_ciphers.clone(),
+            redact_headers: redact_headers.clone(),
+            redact_fields: redact_fields.clone(),
+            health_check_timeout_ms: health_check_timeout.as_millis() as u64,
+            health_cache_ms: health_cache_ttl.as_millis() as u64,
+            shutdown_message: shutdown_message.clone(),
+            shutdown_retry_after_secs: *shutdown_retry_after_secs,
+            shutdown_report_file: shutdown_report_file
+                .as_ref()
+                .map(|path| path.display().to_string()),
+            snapshot_file: snapshot_file
+                .as_ref()
+                .map(|path| path.display().to_string()),
+            persist_on_shutdown: *persist_on_shutdown,
+            snapshot_save_timeout_ms: snapshot_save_timeout.as_millis() as u64,
+            task_drain_timeout_ms: task_drain_timeout.as_millis() as u64,
+            registry_url: registry_url.clone(),
+            service_name: service_name.clone(),
+            maintenance_mode: *maintenance_mode,
+            admin_enabled: *admin_enabled,
+            admin_token: redact(admin_token),
+        }
+    }
+}
+
+const REDACTED: &str = "***";
+
+fn redact(secret: &Option<String>) -> Option<&'static str> {
+    secret.as_ref().map(|_| REDACTED)
+}
+
+/// Serializable, secret-free counterpart of [`Config`].
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-api", serde(rename_all = "camelCase"))]
+pub struct RedactedConfig {
+    bind_addr: String,
+    run_mode: RunMode,
+    worker_interval_secs: u64,
+    load_shed_latency_budget_ms: Option<u64>,
+    load_shed_retry_after_secs: u64,
+    ready_high_water: Option<usize>,
+    ready_low_water: Option<usize>,
+    max_concurrent_requests: Option<usize>,
+    daily_quota: Option<u64>,
+    quota_reset_hour: u8,
+    quota_max_keys: usize,
+    request_timeout_ms: u64,
+    route_timeouts_ms: BTreeMap<String, u64>,
+    max_uri_bytes: usize,
+    max_import_items: usize,
+    max_batch_get_ids: usize,
+    default_list_limit: usize,
+    stream_list_min_items: Option<usize>,
+    max_list_offset: usize,
+    create_dedupe_window_ms: u64,
+    create_dedupe_key: DedupeKey,
+    item_ttl_secs: Option<u64>,
+    item_purge_interval_secs: u64,
+    unique_name: bool,
+    id_scheme: IdScheme,
+    max_description_len: usize,
+    item_history_limit: usize,
+    max_items: Option<usize>,
+    item_eviction: EvictionPolicy,
+    ingest_buffer_events: usize,
+    sse_max_subscribers: usize,
+    sse_max_lag: usize,
+    retry_budget: u32,
+    retry_budget_window_secs: u64,
+    api_version: String,
+    deprecated_routes: Vec<String>,
+    api_sunset: Option<String>,
+    debug_body_routes: Vec<String>,
+    debug_body_max_bytes: usize,
+    trace_sample_rate: f64,
+    tls_cert_file: Option<String>,
+    tls_key_file: Option<String>,
+    tls_min_version: String,
+    tls_ciphers: Vec<String>,
+    redact_headers: Vec<String>,
+    redact_fields: Vec<String>,
+    health_check_timeout_ms: u64,
+    health_cache_ms: u64,
+    shutdown_message: String,
+    shutdown_retry_after_secs: u64,
+    shutdown_report_file: Option<String>,
+    snapshot_file: Option<String>,
+    persist_on_shutdown: bool,
+    snapshot_save_timeout_ms: u64,
+    task_drain_timeout_ms: u64,
+    registry_url: Option<String>,
+    service_name: String,
+    maintenance_mode: bool,
+    admin_enabled: bool,
+    admin_token: Option<&'static str>,
+}
+
+/// Every problem found by [`Config::validate`].
+#[derive(Debug)]
+pub struct ConfigErrors(pub Vec<String>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} configuration problem(s):", self.0.len())?;
+        for problem in &self.0 {
+            write!(f, "\n  - {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+/// Reads a comma-separated list, trimming entries and dropping empty ones.
+fn env_list(key: &str) -> Option<Vec<String>> {
+    let raw = env::var(key).ok()?;
+    Some(
+        raw.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(String::from)
+            .collect(),
+    )
+}
+
+/// Parses an environment variable. One that is present but malformed is
+/// treated as unset and described in `invalid`.
+fn env_parse<T: FromStr>(invalid: &RefCell<Vec<String>>, key: &str) -> Option<T> {
+    let raw = env::var(key).ok()?;
+    match raw.trim().parse() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            invalid
+                .borrow_mut()
+                .push(format!("{key} has a malformed value: {raw:?}"));
+            None
+        }
+    }
+}
+
+/// What [`env_parse_where`] says most counts and durations must be.
+const POSITIVE: &str = "greater than zero";
+
+/// Like [`env_parse`], but a value that parses and fails `valid` is treated
+/// as unset too, and described in `invalid` as out of range (`expected`).
+fn env_parse_where<T: FromStr + fmt::Display>(
+    invalid: &RefCell<Vec<String>>,
+    key: &str,
+    expected: &str,
+    valid: impl FnOnce(&T) -> bool,
+) -> Option<T> {
+    let value = env_parse(invalid, key)?;
+    if valid(&value) {
+        return Some(value);
+    }
+    invalid
+        .borrow_mut()
+        .push(format!("{key} must be {expected}, got {value}"));
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        Config::default().validate().unwrap();
+    }
+
+    #[test]
+    fn validate_reports_every_problem_together() {
+        let config = Config {
+            bind_addr: "localhost".to_string(),
+            max_uri_bytes: 0,
+            request_timeout: Duration::from_millis(500),
+            item_ttl: Some(Duration::from_secs(30)),
+            admin_token: Some("token".to_string()),
+            tls_cert_file: Some("/no/such/cert.pem".into()),
+            tls_key_file: Some("/no/such/key.pem".into()),
+            invalid_env: vec![r#"REQUEST_TIMEOUT_MS has a malformed value: "abc""#.to_string()],
+            ..Config::default()
+        };
+
+        let errors = config.validate().unwrap_err();
+        let mut expected = vec![
+            r#"REQUEST_TIMEOUT_MS has a malformed value: "abc""#,
+            r#"BIND_ADDR must be host:port, got "localhost""#,
+            "MAX_URI_BYTES must be greater than zero",
+            "HEALTH_CHECK_TIMEOUT_MS (1s) must be shorter than REQUEST_TIMEOUT_MS (500ms)",
+            "ITEM_PURGE_INTERVAL_SECS (60s) must not exceed ITEM_TTL_SECS (30s)",
+            "TLS_CERT_FILE /no/such/cert.pem is not readable: No such file or directory (os error 2)",
+            "TLS_KEY_FILE /no/such/key.pem is not readable: No such file or directory (os error 2)",
+        ];
+        if !cfg!(feature = "tls") {
+            expected.push("TLS_CERT_FILE and TLS_KEY_FILE need a build with the `tls` feature");
+        }
+        expected.push("ADMIN_TOKEN is set but ADMIN_ENABLED is not");
+        assert_eq!(errors.0, expected);
+        assert!(errors.to_string().starts_with(&format!(
+            "{} configuration problem(s):\n  - REQUEST_TIMEOUT_MS",
+            expected.len()
+        )));
+    }
+
+    #[test]
+    fn malformed_variables_are_recorded_for_validation() {
+        // Names no other test reads, as tests share the environment
+        env::set_var("CONFIG_TEST_NEGATIVE", "-1");
+        env::set_var("CONFIG_TEST_NUMBER", " 42 ");
+        let invalid = RefCell::new(Vec::new());
+
+        assert_eq!(env_parse::<usize>(&invalid, "CONFIG_TEST_NEGATIVE"), None);
+        assert_eq!(env_parse::<usize>(&invalid, "CONFIG_TEST_NUMBER"), Some(42));
+        assert_eq!(env_parse::<usize>(&invalid, "CONFIG_TEST_UNSET"), None);
+        assert_eq!(
+            invalid.into_inner(),
+            [r#"CONFIG_TEST_NEGATIVE has a malformed value: "-1""#]
+        );
+    }
+
+    #[test]
+    fn out_of_range_variables_are_recorded_for_validation() {
+        env::set_var("CONFIG_TEST_HOUR", "25");
+        env::set_var("CONFIG_TEST_RATE", "1.5");
+        env::set_var("CONFIG_TEST_INTERVAL", "0");
+        env::set_var("CONFIG_TEST_IN_RANGE", "23");
+        let invalid = RefCell::new(Vec::new());
+
+        let hour = |key| env_parse_where::<u8>(&invalid, key, "an hour, 0-23", |h| *h < 24);
+        assert_eq!(hour("CONFIG_TEST_HOUR"), None);
+        assert_eq!(hour("CONFIG_TEST_IN_RANGE"), Some(23));
+        let rate =
+            env_parse_where::<f64>(&invalid, "CONFIG_TEST_RATE", "between 0.0 and 1.0", |r| {
+                (0.0..=1.0).contains(r)
+            });
+        assert_eq!(rate, None);
+        let interval =
+            env_parse_where::<u64>(&invalid, "CONFIG_TEST_INTERVAL", POSITIVE, |s| *s > 0);
+        assert_eq!(interval, None);
+        assert_eq!(
+            invalid.into_inner(),
+            [
+                "CONFIG_TEST_HOUR must be an hour, 0-23, got 25",
+                "CONFIG_TEST_RATE must be between 0.0 and 1.0, got 1.5",
+                "CONFIG_TEST_INTERVAL must be greater than zero, got 0",
+            ]
+        );
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn unusable_tls_ciphers_are_reported() {
+        let config = Config {
+            tls_ciphers: vec!["TLS_RSA_WITH_RC4_128_MD5".to_string()],
+            ..Config::default()
+        };
+
+        let errors = config.validate().unwrap_err().0;
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(
+            errors[0].starts_with(
+                r#"TLS_CIPHERS is unusable: unknown cipher suite "TLS_RSA_WITH_RC4_128_MD5""#
+            ),
+            "{errors:?}"
+        );
+    }
+
+    #[cfg(not(feature = "tls"))]
+    #[test]
+    fn tls_settings_need_the_tls_feature() {
+        // Readable files, so the feature is the only problem
+        let readable = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let config = Config {
+            tls_cert_file: Some(readable.clone()),
+            tls_key_file: Some(readable),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            config.validate().unwrap_err().0,
+            ["TLS_CERT_FILE and TLS_KEY_FILE need a build with the `tls` feature"]
+        );
+    }
+
+    #[test]
+    fn route_timeouts_parse_from_pairs() {
+        let timeouts: RouteTimeouts = " /items/import=120000, /items/export = 500 ,"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            timeouts.0,
+            BTreeMap::from([
+                ("/items/export".to_string(), Duration::from_millis(500)),
+                ("/items/import".to_string(), Duration::from_secs(120)),
+            ])
+        );
+
+        assert!("/items/import".parse::<RouteTimeouts>().is_err());
+        assert!("/items/import=0".parse::<RouteTimeouts>().is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use axum::{
+    body::HttpBody,
+    extract::{MatchedPath, Request},
+    http::{header, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::error::ApiError;
+
+const JSON: &str = "application/json";
+const NDJSON: &str = "application/x-ndjson";
+
+/// Body types accepted by routes that don't take JSON. Every other mutating
+/// route takes `application/json` (or an `application/*+json` type).
+const NON_JSON_ROUTES: &[(&str, &str)] = &[("/ingest", NDJSON)];
+
+/// Rejects a POST, PUT or PATCH whose body isn't declared as a type the
+/// route accepts with 415, before any extractor tries to parse it. Requests
+/// without a body are let through so handlers can report what is missing.
+pub async fn require_content_type(request: Request, next: Next) -> Response {
+    if !matches!(
+        *request.method(),
+        Method::POST | Method::PUT | Method::PATCH
+    ) || request.body().size_hint().exact() == Some(0)
+    {
+        return next.run(request).await;
+    }
+
+    let expected = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|route| {
+            NON_JSON_ROUTES
+                .iter()
+                .find(|(path, _)| *path == route.as_str())
+        })
+        .map_or(JSON, |(_, media_type)| media_type);
+
+    let declared = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap_or_default());
+    let rejection = match declared {
+        None => format!("Missing Content-Type: expected {expected}"),
+        Some(declared) if accepts(expected, declared) => return next.run(request).await,
+        Some(declared) => format!("Unsupported Content-Type {declared:?}: expected {expected}"),
+    };
+    ApiError::UnsupportedMediaType(rejection).into_response()
+}
+
+/// Whether `declared` (parameters such as `charset` allowed) is `expected`,
+/// or a structured `+json` type where JSON is expected.
+fn accepts(expected: &str, declared: &str) -> bool {
+    let essence = declared
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence == expected
+        || (expected == JSON
+            && essence
+                .strip_prefix("application/")
+                .is_some_and(|subtype| subtype.ends_with("+json")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{body_json, send, test_state};
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+
+    fn create(content_type: Option<&str>) -> Request<Body> {
+        let mut request = Request::post("/items");
+        if let Some(content_type) = content_type {
+            request = request.header(header::CONTENT_TYPE, content_type);
+        }
+        request
+            .body(Body::from(r#"{"name":"typed","description":""}"#))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn bodies_without_a_content_type_are_rejected() {
+        let state = test_state();
+
+        let response = send(&state, create(None)).await;
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(
+            body_json(response).await["message"],
+            "Missing Content-Type: expected application/json"
+        );
+        assert!(state.store.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn mismatched_content_types_are_rejected() {
+        let state = test_state();
+
+        let response = send(&state, create(Some("text/plain"))).await;
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(
+            body_json(response).await["message"],
+            r#"Unsupported Content-Type "text/plain": expected application/json"#
+        );
+
+        // JSON sent to the NDJSON endpoint is a mismatch too
+        let response = send(
+            &state,
+            Request::post("/ingest")
+                .header(header::CONTENT_TYPE, JSON)
+                .body(Body::from("{}\n"))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        for accepted in [
+            "application/json; charset=utf-8",
+            "application/merge-patch+json",
+        ] {
+            let response = send(&state, create(Some(accepted))).await;
+            assert_eq!(response.status(), StatusCode::CREATED, "{accepted}");
+            state.store.write().await.clear();
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{timeout_at, Instant};
+
+use crate::config::Config;
+use crate::ApiResponse;
+
+/// The point in time by which the current request must be answered.
+///
+/// Inserted into request extensions by [`enforce_request_timeout`]; handlers
+/// take it with `Extension<Deadline>` and pass it to [`call_with_deadline`]
+/// so downstream calls never outlive the request that made them.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    pub fn after(timeout: Duration) -> Self {
+        Self(Instant::now() + timeout)
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DeadlineExceeded;
+
+impl std::fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("request deadline exceeded")
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+/// Runs a downstream call bounded by its own `call_timeout` and by whatever
+/// is left of the request's deadline, whichever ends first. Fails fast
+/// without starting the call if the deadline has already passed.
+#[allow(dead_code)] // Call helper for handlers that talk to downstream services
+pub async fn call_with_deadline<F: Future>(
+    deadline: Option<Deadline>,
+    call_timeout: Duration,
+    call: F,
+) -> Result<F::Output, DeadlineExceeded> {
+    let budget = match deadline {
+        Some(deadline) if deadline.remaining().is_zero() => return Err(DeadlineExceeded),
+        Some(deadline) => deadline.remaining().min(call_timeout),
+        None => call_timeout,
+    };
+
+    tokio::time::timeout(budget, call)
+        .await
+        .map_err(|_| DeadlineExceeded)
+}
+
+/// The request timeout for each route: the global default unless the route
+/// template has an override.
+#[derive(Debug, Clone)]
+pub struct RequestTimeouts {
+    default: Duration,
+    per_route: BTreeMap<String, Duration>,
+}
+
+impl RequestTimeouts {
+    /// `builtin` holds the overrides the routes are declared with; those in
+    /// `ROUTE_TIMEOUTS_MS` take precedence over them.
+    pub fn new(config: &Config, builtin: &[(&str, Duration)]) -> Self {
+        let mut per_route: BTreeMap<String, Duration> = builtin
+            .iter()
+            .map(|(route, timeout)| (route.to_string(), *timeout))
+            .collect();
+        per_route.extend(config.route_timeouts.0.clone());
+        Self {
+            default: config.request_timeout,
+            per_route,
+        }
+    }
+
+    pub fn for_route(&self, route: Option<&str>) -> Duration {
+        route
+            .and_then(|route| self.per_route.get(route))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Middleware bounding each request to its route's timeout and exposing the
+/// resulting [`Deadline`] to handlers. Slow requests get 408 Request Timeout.
+///
+/// The timeout covers producing the response, not sending it: a streamed
+/// body (an export, a large listing) keeps flowing past the deadline once
+/// its head is out.
+pub async fn enforce_request_timeout(
+    State(timeouts): State<Arc<RequestTimeouts>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let route = request.extensions().get::<MatchedPath>();
+    let timeout = timeouts.for_route(route.map(MatchedPath::as_str));
+    let deadline = Deadline::after(timeout);
+    request.extensions_mut().insert(deadline);
+
+    match timeout_at(deadline.0, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => (
+            StatusCode::REQUEST_TIMEOUT,
+            ApiResponse::error(format!("Request did not complete within {timeout:?}")),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RouteTimeouts;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tokio::time::sleep;
+    use tower::ServiceExt;
+
+    #[tokio::test(start_paused = true)]
+    async fn route_override_outlasts_the_global_timeout() {
+        let timeouts = RequestTimeouts::new(
+            &Config {
+                request_timeout: Duration::from_millis(100),
+                route_timeouts: RouteTimeouts(BTreeMap::from([(
+                    "/slow/:id".to_string(),
+                    Duration::from_secs(1),
+                )])),
+                ..Config::default()
+            },
+            &[],
+        );
+        let handler = || async {
+            sleep(Duration::from_millis(300)).await;
+            "done"
+        };
+        let app = Router::new()
+            .route("/slow/:id", get(handler))
+            .route("/quick/:id", get(handler))
+            .layer(middleware::from_fn_with_state(
+                Arc::new(timeouts),
+                enforce_request_timeout,
+            ));
+        let status = |path: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = Request::get(path).body(Body::empty()).unwrap();
+                app.oneshot(request).await.unwrap().status()
+            }
+        };
+
+        assert_eq!(status("/slow/1").await, StatusCode::OK);
+        assert_eq!(status("/quick/1").await, StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[test]
+    fn configured_timeouts_override_the_builtin_ones() {
+        let builtin = [
+            ("/export", Duration::from_secs(300)),
+            ("/health", Duration::from_secs(1)),
+        ];
+        let timeouts = RequestTimeouts::new(
+            &Config {
+                request_timeout: Duration::from_secs(30),
+                route_timeouts: "/health=5000".parse().unwrap(),
+                ..Config::default()
+            },
+            &builtin,
+        );
+        assert_eq!(
+            timeouts.for_route(Some("/export")),
+            Duration::from_secs(300)
+        );
+        assert_eq!(timeouts.for_route(Some("/health")), Duration::from_secs(5));
+        assert_eq!(timeouts.for_route(Some("/items")), Duration::from_secs(30));
+        assert_eq!(timeouts.for_route(None), Duration::from_secs(30));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_streamed_body_outlives_the_timeout() {
+        use futures::StreamExt;
+
+        let timeouts = RequestTimeouts::new(
+            &Config {
+                request_timeout: Duration::from_millis(100),
+                ..Config::default()
+            },
+            &[],
+        );
+        let handler = || async {
+            let chunks = futures::stream::iter(["a", "b", "c"]).then(|chunk| async move {
+                sleep(Duration::from_millis(80)).await;
+                Ok::<_, std::convert::Infallible>(chunk)
+            });
+            Body::from_stream(chunks)
+        };
+        let app =
+            Router::new()
+                .route("/stream", get(handler))
+                .layer(middleware::from_fn_with_state(
+                    Arc::new(timeouts),
+                    enforce_request_timeout,
+                ));
+
+        let response = app
+            .oneshot(Request::get("/stream").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"abc");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn downstream_call_is_cut_short_by_request_deadline() {
+        let deadline = Deadline::after(Duration::from_millis(50));
+        let started = Instant::now();
+
+        let result = call_with_deadline(Some(deadline), Duration::from_secs(10), async {
+            sleep(Duration::from_secs(1)).await;
+        })
+        .await;
+
+        assert_eq!(result, Err(DeadlineExceeded));
+        assert_eq!(started.elapsed(), Duration::from_millis(50));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_deadline_fails_without_calling() {
+        let deadline = Deadline::after(Duration::from_millis(10));
+        sleep(Duration::from_millis(20)).await;
+
+        let mut called = false;
+        let result = call_with_deadline(Some(deadline), Duration::from_secs(1), async {
+            called = true;
+        })
+        .await;
+
+        assert_eq!(result, Err(DeadlineExceeded));
+        assert!(!called);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn own_timeout_applies_without_a_deadline() {
+        let result = call_with_deadline(None, Duration::from_millis(5), async { 7 }).await;
+        assert_eq!(result, Ok(7));
+    }
+}
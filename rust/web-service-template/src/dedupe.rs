@@ -0,0 +1,173 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::config::DedupeKey;
+use crate::ids::ItemId;
+use crate::items::CreateItemRequest;
+
+/// Short-lived memory of recent creates, keyed by a hash of the normalized
+/// payload, so an accidental double submit returns the first item instead
+/// of creating a twin (`CREATE_DEDUPE_WINDOW_MS`, zero disables).
+///
+/// With [`DedupeKey::Client`] the hash also covers the client's
+/// `X-API-Key`, so two clients creating the same item both get one;
+/// requests without a key count as one anonymous client.
+///
+/// This is not an idempotency mechanism: two identical creates further apart
+/// than the window both succeed, and so does a repeat after the first item
+/// was changed to something else.
+pub struct CreateDedupe {
+    window: Duration,
+    key: DedupeKey,
+    recent: Mutex<HashMap<u64, (ItemId, Instant)>>,
+}
+
+impl CreateDedupe {
+    pub fn new(window: Duration, key: DedupeKey) -> Self {
+        Self {
+            window,
+            key,
+            recent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Id of an item created from an equivalent payload within the window.
+    pub fn recent(&self, payload: &CreateItemRequest, client: Option<&str>) -> Option<ItemId> {
+        if self.window.is_zero() {
+            return None;
+        }
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap();
+        recent.retain(|_, (_, at)| now.duration_since(*at) < self.window);
+        recent
+            .get(&self.fingerprint(payload, client))
+            .map(|(id, _)| id.clone())
+    }
+
+    pub fn record(&self, payload: &CreateItemRequest, client: Option<&str>, id: ItemId) {
+        if self.window.is_zero() {
+            return;
+        }
+        self.recent
+            .lock()
+            .unwrap()
+            .insert(self.fingerprint(payload, client), (id, Instant::now()));
+    }
+
+    /// Hash of the payload with surrounding whitespace ignored, and of the
+    /// client when keyed by client.
+    fn fingerprint(&self, payload: &CreateItemRequest, client: Option<&str>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        payload.name.trim().hash(&mut hasher);
+        payload.description.trim().hash(&mut hasher);
+        if self.key == DedupeKey::Client {
+            client.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Config, DedupeKey};
+    use crate::quota::API_KEY;
+    use crate::test_support::{body_json, send, test_state_with};
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use std::time::Duration;
+
+    fn create(body: &str) -> Request<Body> {
+        Request::post("/items")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn identical_rapid_creates_yield_one_item() {
+        let state = test_state_with(Config {
+            create_dedupe_window: Duration::from_secs(2),
+            ..Config::default()
+        });
+
+        let first = send(
+            &state,
+            create(r#"{"name":"Widget","description":"A widget"}"#),
+        )
+        .await;
+        assert_eq!(first.status(), StatusCode::CREATED);
+        let first = body_json(first).await;
+
+        let second = send(
+            &state,
+            create(r#"{"name":" Widget ","description":"A widget"}"#),
+        )
+        .await;
+        assert_eq!(second.status(), StatusCode::OK);
+        assert_eq!(body_json(second).await["data"], first["data"]);
+        assert_eq!(state.store.read().await.len(), 1);
+
+        // Outside the window it is a new item again
+        tokio::time::advance(Duration::from_secs(2)).await;
+        let third = send(
+            &state,
+            create(r#"{"name":"Widget","description":"A widget"}"#),
+        )
+        .await;
+        assert_eq!(third.status(), StatusCode::CREATED);
+        assert_eq!(state.store.read().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn the_key_decides_whether_other_clients_count_as_duplicates() {
+        let create_as = |client: &str| {
+            Request::post("/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(&API_KEY, client)
+                .body(Body::from(r#"{"name":"Widget","description":""}"#))
+                .unwrap()
+        };
+
+        for (key, created) in [(DedupeKey::Client, 2), (DedupeKey::Payload, 1)] {
+            let state = test_state_with(Config {
+                create_dedupe_window: Duration::from_secs(60),
+                create_dedupe_key: key,
+                ..Config::default()
+            });
+            for client in ["alice", "alice", "bob", "bob"] {
+                send(&state, create_as(client)).await;
+            }
+            assert_eq!(state.store.read().await.len(), created, "{key:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn a_duplicate_of_a_replaced_item_is_created_anew() {
+        let state = test_state_with(Config {
+            create_dedupe_window: Duration::from_secs(60),
+            ..Config::default()
+        });
+        let widget = r#"{"name":"Widget","description":"A widget"}"#;
+        assert_eq!(
+            send(&state, create(widget)).await.status(),
+            StatusCode::CREATED
+        );
+        let put = Request::put("/items/1")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"name":"Gadget","description":""}"#))
+            .unwrap();
+        assert_eq!(send(&state, put).await.status(), StatusCode::OK);
+
+        let response = send(&state, create(widget)).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created = body_json(response).await;
+        assert_eq!(created["data"]["name"], "Widget");
+        assert_eq!(state.store.read().await.len(), 2);
+    }
+}
@@ -0,0 +1,56 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+use crate::ApiResponse;
+
+/// Handler error rendered as the usual `ApiResponse` envelope with
+/// `success: false`.
+#[derive(Debug)]
+pub enum ApiError {
+    /// 400: the request is malformed or incomplete.
+    BadRequest(String),
+    /// 404: the resource doesn't exist.
+    NotFound(String),
+    /// 409: the request conflicts with the current state of the store.
+    Conflict(String),
+    /// 412: an `If-Match` precondition no longer holds.
+    PreconditionFailed(String),
+    /// 415: the body is not in a format the endpoint accepts.
+    UnsupportedMediaType(String),
+    /// 422: the body parsed but does not describe a valid request.
+    Unprocessable(String),
+    /// 507: the store is full and the request would add to it.
+    InsufficientStorage(String),
+}
+
+impl ApiError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            Self::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::InsufficientStorage(_) => StatusCode::INSUFFICIENT_STORAGE,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let message = match self {
+            Self::BadRequest(message)
+            | Self::NotFound(message)
+            | Self::Conflict(message)
+            | Self::PreconditionFailed(message)
+            | Self::UnsupportedMediaType(message)
+            | Self::Unprocessable(message)
+            | Self::InsufficientStorage(message) => message,
+        };
+        (status, ApiResponse::error(message)).into_response()
+    }
+}
@@ -0,0 +1,206 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+use crate::changes::{Change, ChangeFeed};
+use crate::clock::{unix_secs, Clock};
+use crate::history::ItemHistory;
+use crate::items::{Item, ItemStore};
+use crate::telemetry;
+use crate::worker::{Outcome, WorkError, Worker};
+
+/// Item time-to-live (`ITEM_TTL_SECS`), judged against an injectable clock.
+///
+/// Expired items are hidden from reads as soon as they pass the TTL and
+/// removed from the store by [`PurgeWorker`] on its next tick.
+#[derive(Clone)]
+pub struct Expiry {
+    clock: Arc<dyn Clock>,
+    ttl: Option<Duration>,
+}
+
+impl Expiry {
+    pub fn new(clock: Arc<dyn Clock>, ttl: Option<Duration>) -> Self {
+        Self { clock, ttl }
+    }
+
+    /// Current time in seconds since the Unix epoch, for `Item::created_at`.
+    pub fn now_secs(&self) -> u64 {
+        unix_secs(self.clock.as_ref())
+    }
+
+    pub fn is_expired(&self, item: &Item) -> bool {
+        self.ttl
+            .is_some_and(|ttl| self.now_secs().saturating_sub(item.created_at) > ttl.as_secs())
+    }
+}
+
+/// Background worker that deletes expired items from the store, along with
+/// their history, and tells change subscribers.
+pub struct PurgeWorker {
+    store: ItemStore,
+    expiry: Expiry,
+    history: Arc<ItemHistory>,
+    changes: Arc<ChangeFeed>,
+}
+
+impl PurgeWorker {
+    pub fn new(
+        store: ItemStore,
+        expiry: Expiry,
+        history: Arc<ItemHistory>,
+        changes: Arc<ChangeFeed>,
+    ) -> Self {
+        Self {
+            store,
+            expiry,
+            history,
+            changes,
+        }
+    }
+}
+
+#[async_trait]
+impl Worker for PurgeWorker {
+    async fn perform_work(&self, _iteration: u64) -> Result<Outcome, WorkError> {
+        let mut items = self.store.write().await;
+        let before = items.len();
+        items.retain(|_, item| {
+            let expired = self.expiry.is_expired(item);
+            if expired {
+                self.changes.publish(Change::Deleted, item);
+            }
+            !expired
+        });
+        let purged = before - items.len();
+        self.history.retain(|id| items.contains_key(id));
+
+        if purged == 0 {
+            return Ok(Outcome::Idle);
+        }
+        telemetry::record(|| metrics::counter!("items_expired_total").increment(purged as u64));
+        info!("Purged {} expired items", purged);
+        Ok(Outcome::Worked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::ids::ItemId;
+    use crate::test_support::{body_json, json_id, send, test_state_with_clock, ManualClock};
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use futures::StreamExt;
+    use tokio_util::sync::CancellationToken;
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_items_are_hidden_then_purged() {
+        let clock = Arc::new(ManualClock::default());
+        let state = test_state_with_clock(
+            Config {
+                item_ttl: Some(Duration::from_secs(60)),
+                ..Config::default()
+            },
+            clock.clone(),
+        );
+
+        let response = send(
+            &state,
+            Request::post("/items")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"name":"short-lived","description":""}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(send(&state, get("/items/1")).await.status(), StatusCode::OK);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(
+            send(&state, get("/items/1")).await.status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            body_json(send(&state, get("/items")).await).await["data"]["items"],
+            serde_json::json!([])
+        );
+        assert_eq!(state.store.read().await.len(), 1, "hidden, not yet purged");
+
+        let shutdown = CancellationToken::new();
+        let purge = tokio::spawn(crate::worker::run(
+            Arc::new(PurgeWorker::new(
+                state.store.clone(),
+                state.expiry.clone(),
+                state.history.clone(),
+                state.changes.clone(),
+            )),
+            Duration::from_secs(30),
+            shutdown.clone(),
+        ));
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        shutdown.cancel();
+        purge.await.unwrap();
+
+        assert!(state.store.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn timestamps_and_ttl_follow_the_injected_clock() {
+        let clock = Arc::new(ManualClock::default());
+        let state = test_state_with_clock(
+            Config {
+                item_ttl: Some(Duration::from_secs(3600)),
+                ..Config::default()
+            },
+            clock.clone(),
+        );
+        let started = std::time::Instant::now();
+
+        let response = send(
+            &state,
+            Request::post("/items")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"name":"hourly","description":""}"#))
+                .unwrap(),
+        )
+        .await;
+        let created = &body_json(response).await["data"];
+        assert_eq!(created["id"], json_id(1));
+        assert_eq!(
+            state.store.read().await[&ItemId::from(1)].created_at,
+            1_700_000_000
+        );
+
+        let purge = PurgeWorker::new(
+            state.store.clone(),
+            state.expiry.clone(),
+            state.history.clone(),
+            state.changes.clone(),
+        );
+        let mut changes = send(
+            &state,
+            Request::get("/items/changes").body(Body::empty()).unwrap(),
+        )
+        .await
+        .into_body()
+        .into_data_stream();
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(purge.perform_work(1).await.unwrap(), Outcome::Idle);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(purge.perform_work(2).await.unwrap(), Outcome::Worked);
+        assert!(state.store.read().await.is_empty());
+        let event = changes.next().await.unwrap().unwrap();
+        assert!(event.starts_with(b"event: deleted\n"));
+
+        // An hour of item lifetime passed without waiting for it
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,51 @@
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+use crate::error::ApiError;
+
+/// A request payload that can describe itself in error messages.
+pub trait Payload: DeserializeOwned {
+    /// Wire names of the fields a client is expected to send.
+    const FIELDS: &'static [&'static str];
+}
+
+/// `Json<T>` with errors in the `ApiResponse` envelope.
+///
+/// A missing or blank body is reported as 400 naming the expected fields,
+/// separately from a body that is present but malformed (400) or that
+/// doesn't match `T` (422).
+pub struct JsonBody<T>(pub T);
+
+#[async_trait]
+impl<T: Payload, S: Send + Sync> FromRequest<S> for JsonBody<T> {
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let (parts, body) = request.into_parts();
+        let bytes = Bytes::from_request(Request::from_parts(parts.clone(), body), state)
+            .await
+            .map_err(|err| ApiError::BadRequest(err.body_text()))?;
+
+        if bytes.iter().all(u8::is_ascii_whitespace) {
+            return Err(ApiError::BadRequest(format!(
+                "Request body is required: expected a JSON object with fields {}",
+                T::FIELDS.join(", ")
+            )));
+        }
+
+        let request = Request::from_parts(parts, Body::from(bytes));
+        match Json::<T>::from_request(request, state).await {
+            Ok(Json(payload)) => Ok(Self(payload)),
+            Err(JsonRejection::MissingJsonContentType(err)) => {
+                Err(ApiError::UnsupportedMediaType(err.body_text()))
+            }
+            Err(JsonRejection::JsonDataError(err)) => Err(ApiError::Unprocessable(err.body_text())),
+            Err(err) => Err(ApiError::BadRequest(err.body_text())),
+        }
+    }
+}
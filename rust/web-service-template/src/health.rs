@@ -0,0 +1,309 @@
+use async_trait::async_trait;
+use axum::{
+    extract::State,
+    http::{HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Instant};
+
+use crate::{ApiResponse, AppState, ItemStore};
+
+/// The status every probe reports, so `/health`, `/readyz` and
+/// `/healthz/deep` all answer with the same contract.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Healthy,
+    /// Working, but with reduced capacity or a non-critical dependency down.
+    /// Still answered with 200 so load balancers keep sending traffic.
+    Degraded,
+    Unhealthy,
+}
+
+impl HealthStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Healthy => "healthy",
+            Self::Degraded => "degraded",
+            Self::Unhealthy => "unhealthy",
+        }
+    }
+}
+
+/// A status with an optional explanation, as reported by a probe or a
+/// single [`HealthCheck`].
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Health {
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Health {
+    pub fn healthy(detail: impl Into<Option<String>>) -> Self {
+        Self::new(HealthStatus::Healthy, detail)
+    }
+
+    pub fn degraded(detail: impl Into<Option<String>>) -> Self {
+        Self::new(HealthStatus::Degraded, detail)
+    }
+
+    pub fn unhealthy(detail: impl Into<Option<String>>) -> Self {
+        Self::new(HealthStatus::Unhealthy, detail)
+    }
+
+    fn new(status: HealthStatus, detail: impl Into<Option<String>>) -> Self {
+        Self {
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Header repeating a probe's [`HealthStatus`], so a degraded 200 can be
+/// told apart from a healthy one without reading the body.
+pub static HEALTH_STATUS: HeaderName = HeaderName::from_static("x-health-status");
+
+/// Answers a probe: 200 for healthy and degraded, 503 for unhealthy, with
+/// the status in [`HEALTH_STATUS`] and `data` holding `body`, whose
+/// `status` should match.
+pub fn probe_response(status: HealthStatus, message: &str, body: impl Serialize) -> Response {
+    let code = match status {
+        HealthStatus::Healthy | HealthStatus::Degraded => StatusCode::OK,
+        HealthStatus::Unhealthy => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (
+        code,
+        [(
+            HEALTH_STATUS.clone(),
+            HeaderValue::from_static(status.as_str()),
+        )],
+        Json(ApiResponse {
+            success: status != HealthStatus::Unhealthy,
+            data: Some(body),
+            message: message.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+/// A pluggable dependency check reported by `/healthz/deep`.
+///
+/// Register new subsystems (database, cache, downstream APIs) with
+/// [`HealthRegistry::register`] in `AppState::new`.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Whether a failure of this check makes the whole service unhealthy.
+    /// A non-critical check that fails only degrades it.
+    fn critical(&self) -> bool {
+        true
+    }
+
+    async fn check(&self) -> Health;
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[cfg_attr(feature = "camel-case-api", serde(rename_all = "camelCase"))]
+pub struct SubsystemHealth {
+    #[serde(flatten)]
+    pub health: Health,
+    pub critical: bool,
+    pub latency_ms: u64,
+}
+
+impl SubsystemHealth {
+    /// What this subsystem's status means for the service as a whole.
+    fn service_status(&self) -> HealthStatus {
+        if self.critical {
+            self.health.status
+        } else {
+            self.health.status.min(HealthStatus::Degraded)
+        }
+    }
+}
+
+pub type HealthReport = BTreeMap<String, SubsystemHealth>;
+
+/// The `data` of `/healthz/deep`: the overall status and each subsystem's.
+#[derive(Serialize, Debug)]
+pub struct DeepHealth {
+    #[serde(flatten)]
+    pub health: Health,
+    pub subsystems: HealthReport,
+}
+
+/// The set of registered checks, the timeout applied to each of them and
+/// the cache that keeps frequent probes from re-running them.
+pub struct HealthRegistry {
+    checks: Vec<Arc<dyn HealthCheck>>,
+    check_timeout: Duration,
+    cache_ttl: Duration,
+    cached: Mutex<Option<(Instant, HealthReport)>>,
+}
+
+impl HealthRegistry {
+    pub fn new(check_timeout: Duration, cache_ttl: Duration) -> Self {
+        Self {
+            checks: Vec::new(),
+            check_timeout,
+            cache_ttl,
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub fn register(&mut self, check: impl HealthCheck + 'static) {
+        self.checks.push(Arc::new(check));
+    }
+
+    /// The last report if it is younger than the cache TTL, otherwise a
+    /// fresh one from [`HealthRegistry::run`]. Callers arriving while the
+    /// checks run wait for that result rather than starting another run.
+    pub async fn report(&self) -> HealthReport {
+        if self.cache_ttl.is_zero() {
+            return self.run().await;
+        }
+
+        let mut cached = self.cached.lock().await;
+        if let Some((taken, report)) = cached.as_ref() {
+            if taken.elapsed() < self.cache_ttl {
+                return report.clone();
+            }
+        }
+
+        let report = self.run().await;
+        *cached = Some((Instant::now(), report.clone()));
+        report
+    }
+
+    /// Runs every check concurrently, each bounded by the check timeout.
+    pub async fn run(&self) -> HealthReport {
+        let runs = self.checks.iter().map(|check| async move {
+            let started = Instant::now();
+            let health = timeout(self.check_timeout, check.check())
+                .await
+                .unwrap_or_else(|_| {
+                    Health::unhealthy(format!("timed out after {:?}", self.check_timeout))
+                });
+            let health = SubsystemHealth {
+                health,
+                critical: check.critical(),
+                latency_ms: started.elapsed().as_millis() as u64,
+            };
+            (check.name().to_string(), health)
+        });
+
+        futures::future::join_all(runs).await.into_iter().collect()
+    }
+}
+
+/// Verifies the item store lock can be acquired.
+pub struct StoreCheck {
+    store: ItemStore,
+}
+
+impl StoreCheck {
+    pub fn new(store: ItemStore) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for StoreCheck {
+    fn name(&self) -> &str {
+        "store"
+    }
+
+    async fn check(&self) -> Health {
+        let count = self.store.read().await.len();
+        Health::healthy(format!("{count} items"))
+    }
+}
+
+/// Liveness: healthy whenever the service can answer at all, degraded
+/// while in maintenance mode (which this probe is exempt from).
+pub async fn health_check(State(state): State<AppState>) -> Response {
+    let health = if state.maintenance.is_enabled() {
+        Health::degraded("In maintenance mode".to_string())
+    } else {
+        Health::healthy("Web service is running".to_string())
+    };
+    probe_response(health.status, "OK", health)
+}
+
+/// Reports each registered check, reusing a result younger than
+/// `HEALTH_CACHE_MS`. The service is as healthy as its least healthy
+/// critical subsystem, and degraded if a non-critical one isn't healthy.
+pub async fn deep_health(State(state): State<AppState>) -> Response {
+    let subsystems = state.health.report().await;
+    let status = subsystems
+        .values()
+        .map(SubsystemHealth::service_status)
+        .max()
+        .unwrap_or(HealthStatus::Healthy);
+
+    let message = match status {
+        HealthStatus::Healthy => "All subsystems healthy",
+        HealthStatus::Degraded => "Running with degraded subsystems",
+        HealthStatus::Unhealthy => "One or more critical subsystems are unhealthy",
+    };
+    let body = DeepHealth {
+        health: Health::new(status, None),
+        subsystems,
+    };
+    probe_response(status, message, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{send, test_state};
+    use axum::{body::Body, http::Request};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Clone, Default)]
+    struct CountingCheck(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl HealthCheck for CountingCheck {
+        fn name(&self) -> &str {
+            "database"
+        }
+
+        async fn check(&self) -> Health {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Health::healthy(None)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rapid_probes_within_the_ttl_share_one_run() {
+        let mut state = test_state();
+        let check = CountingCheck::default();
+        let mut registry = HealthRegistry::new(Duration::from_millis(100), Duration::from_secs(2));
+        registry.register(check.clone());
+        state.health = Arc::new(registry);
+        let probe = || {
+            send(
+                &state,
+                Request::get("/healthz/deep").body(Body::empty()).unwrap(),
+            )
+        };
+
+        let responses = futures::future::join_all((0..50).map(|_| probe())).await;
+        assert!(responses.iter().all(|r| r.status() == StatusCode::OK));
+        tokio::time::advance(Duration::from_millis(1_999)).await;
+        probe().await;
+        assert_eq!(check.0.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(Duration::from_millis(1)).await;
+        probe().await;
+        assert_eq!(check.0.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,170 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::ids::ItemId;
+use crate::items::Item;
+
+/// Past versions of every item, numbered per item from 1 (as created), for
+/// `GET /items/:id/diff`. Each item keeps its latest `ITEM_HISTORY_LIMIT`
+/// versions; older ones are dropped but keep their numbers.
+pub struct ItemHistory {
+    limit: usize,
+    items: Mutex<HashMap<ItemId, Versions>>,
+}
+
+struct Versions {
+    /// Number of the oldest version kept.
+    first: u32,
+    kept: VecDeque<Item>,
+}
+
+impl ItemHistory {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            items: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records `item` as the newest version of its item. `previous` is the
+    /// version it replaces, `None` for a new item, whose history starts over.
+    ///
+    /// Bulk creates and imports don't record their writes; the version such
+    /// a write left is recorded here first, so it still counts as a step.
+    pub fn record(&self, previous: Option<&Item>, item: &Item) {
+        let mut items = self.items.lock().unwrap();
+        let versions = items.entry(item.id.clone()).or_insert(Versions {
+            first: 1,
+            kept: VecDeque::new(),
+        });
+        match previous {
+            None => {
+                versions.first = 1;
+                versions.kept.clear();
+            }
+            Some(previous) if versions.kept.back() != Some(previous) => {
+                versions.kept.push_back(previous.clone());
+            }
+            Some(_) => {}
+        }
+        versions.kept.push_back(item.clone());
+        while versions.kept.len() > self.limit {
+            versions.kept.pop_front();
+            versions.first += 1;
+        }
+    }
+
+    /// Versions `from` and `to` of `current`, the item as stored now. The
+    /// error is the first of the two that was never written or is no longer
+    /// kept.
+    pub fn versions(&self, current: &Item, from: u32, to: u32) -> Result<(Item, Item), u32> {
+        let items = self.items.lock().unwrap();
+        let (first, mut kept) = match items.get(&current.id) {
+            Some(versions) => (versions.first, versions.kept.iter().collect::<Vec<_>>()),
+            None => (1, Vec::new()),
+        };
+        if kept.last() != Some(&current) {
+            kept.push(current);
+        }
+        let version = |number: u32| {
+            let index = number.checked_sub(first).ok_or(number)?;
+            kept.get(index as usize).copied().cloned().ok_or(number)
+        };
+        Ok((version(from)?, version(to)?))
+    }
+
+    /// Forgets the history of every item `keep` rejects, for items removed
+    /// from the store.
+    pub fn retain(&self, keep: impl Fn(&ItemId) -> bool) {
+        self.items.lock().unwrap().retain(|id, _| keep(id));
+    }
+}
+
+/// One field that differs between two versions of an item.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub from: String,
+    pub to: String,
+}
+
+/// Body of `GET /items/:id/diff`: the fields that changed from version
+/// `from` to version `to`, with their old and new values.
+#[derive(Serialize, Debug)]
+pub struct ItemDiff {
+    pub id: ItemId,
+    pub from: u32,
+    pub to: u32,
+    pub changes: Vec<FieldChange>,
+}
+
+impl ItemDiff {
+    /// Compares the fields a client sets; timestamps change with every
+    /// version and are left out.
+    pub fn between(from: (u32, &Item), to: (u32, &Item)) -> Self {
+        let fields = [
+            ("name", &from.1.name, &to.1.name),
+            ("description", &from.1.description, &to.1.description),
+        ];
+        Self {
+            id: to.1.id.clone(),
+            from: from.0,
+            to: to.0,
+            changes: fields
+                .into_iter()
+                .filter(|(_, old, new)| old != new)
+                .map(|(field, old, new)| FieldChange {
+                    field,
+                    from: old.clone(),
+                    to: new.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::ItemBuilder;
+
+    fn named(name: &str) -> Item {
+        ItemBuilder::new(1).name(name).created_at(1_000).build()
+    }
+
+    #[test]
+    fn only_the_latest_versions_are_kept() {
+        let history = ItemHistory::new(2);
+        history.record(None, &named("a"));
+        history.record(Some(&named("a")), &named("b"));
+        history.record(Some(&named("b")), &named("c"));
+
+        let current = named("c");
+        assert_eq!(history.versions(&current, 1, 3).unwrap_err(), 1, "dropped");
+        let (from, to) = history.versions(&current, 2, 3).unwrap();
+        assert_eq!((from.name.as_str(), to.name.as_str()), ("b", "c"));
+        assert_eq!(
+            history.versions(&current, 2, 4).unwrap_err(),
+            4,
+            "not written yet"
+        );
+    }
+
+    #[test]
+    fn unrecorded_writes_count_as_versions() {
+        let history = ItemHistory::new(10);
+        // Created by an import, then patched
+        history.record(Some(&named("imported")), &named("patched"));
+        // Then changed by another import
+        let current = named("re-imported");
+
+        let (from, to) = history.versions(&current, 1, 3).unwrap();
+        assert_eq!(
+            (from.name.as_str(), to.name.as_str()),
+            ("imported", "re-imported")
+        );
+        let (_, to) = history.versions(&current, 1, 2).unwrap();
+        assert_eq!(to.name, "patched");
+    }
+}
@@ -0,0 +1,491 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Path},
+    http::request::Parts,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::config::IdScheme;
+use crate::error::ApiError;
+
+/// Key of an item, in the store and in its URL. Sequential ids are JSON
+/// numbers, or with the `string-ids` feature strings (`"id":"123"`) so that
+/// clients parsing numbers as doubles never round them; every other
+/// [`IdScheme`] produces strings.
+///
+/// Input takes both forms whatever the feature: `123` and `"123"` are the
+/// same id.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ItemId {
+    Number(u32),
+    Text(String),
+}
+
+impl Serialize for ItemId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Number(id) if cfg!(feature = "string-ids") => serializer.collect_str(id),
+            Self::Number(id) => serializer.serialize_u32(*id),
+            Self::Text(id) => serializer.serialize_str(id),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(u32),
+            Text(String),
+        }
+
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Number(id) => Self::Number(id),
+            Raw::Text(id) => match id.parse() {
+                Ok(number) => Self::Number(number),
+                Err(_) => Self::Text(id),
+            },
+        })
+    }
+}
+
+impl From<u32> for ItemId {
+    fn from(id: u32) -> Self {
+        Self::Number(id)
+    }
+}
+
+impl fmt::Display for ItemId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(id) => id.fmt(f),
+            Self::Text(id) => id.fmt(f),
+        }
+    }
+}
+
+/// Assigns ids to new items and recognizes the ids it could have assigned,
+/// so handlers never depend on the scheme (`ID_SCHEME`).
+pub trait IdGenerator: Send + Sync {
+    /// An id for a new item that no item has had, or why there is none left
+    /// to give.
+    fn generate(&self) -> Result<ItemId, String>;
+
+    /// Fails unless `count` more ids can be generated. Creates check this
+    /// before evicting to make room, so running out of ids never costs an
+    /// existing item.
+    fn check_available(&self, _count: usize) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Notes an id an item got without [`IdGenerator::generate`]
+    /// (`PUT /items/:id`, a restored snapshot) so it is never generated.
+    fn observe(&self, _id: &ItemId) {}
+
+    /// Parses an id as a client writes it, normalized to the form
+    /// [`IdGenerator::generate`] produces. `None` if this scheme can't
+    /// produce it.
+    fn parse(&self, raw: &str) -> Option<ItemId>;
+}
+
+/// The generator for `scheme`.
+pub fn generator(scheme: IdScheme) -> Arc<dyn IdGenerator> {
+    match scheme {
+        IdScheme::Sequential => Arc::new(Sequential::default()),
+        IdScheme::UuidV4 => Arc::new(UuidV4),
+        IdScheme::UuidV7 => Arc::new(UuidV7),
+        IdScheme::Ulid => Arc::new(Ulid::default()),
+    }
+}
+
+/// Numbers from 1, each one past the highest given out so far, so new ids
+/// never land on one a client chose with `PUT /items/:id` or on one of an
+/// expired or evicted item. Once `u32::MAX` has been given out there is
+/// nothing past it, and creates fail until the process restarts, when
+/// numbering resumes past the highest restored id.
+#[derive(Default)]
+pub struct Sequential {
+    highest: AtomicU32,
+}
+
+impl IdGenerator for Sequential {
+    fn generate(&self) -> Result<ItemId, String> {
+        // Relaxed: generating happens under the store's write lock
+        self.highest
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |highest| {
+                highest.checked_add(1)
+            })
+            .map(|highest| ItemId::Number(highest + 1))
+            .map_err(|highest| {
+                format!("No sequential ids left: the highest id, {highest}, has been given out")
+            })
+    }
+
+    fn check_available(&self, count: usize) -> Result<(), String> {
+        let highest = self.highest.load(Ordering::Relaxed);
+        let left = u32::MAX - highest;
+        if left == 0 {
+            Err(format!(
+                "No sequential ids left: the highest id, {highest}, has been given out"
+            ))
+        } else if (left as usize) < count {
+            Err(format!(
+                "Only {left} sequential ids are left and {count} are needed"
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn observe(&self, id: &ItemId) {
+        if let ItemId::Number(id) = id {
+            self.highest.fetch_max(*id, Ordering::Relaxed);
+        }
+    }
+
+    fn parse(&self, raw: &str) -> Option<ItemId> {
+        raw.parse().ok().filter(|id| *id > 0).map(ItemId::Number)
+    }
+}
+
+/// Random UUIDs, which reveal nothing about how many items exist.
+pub struct UuidV4;
+
+impl IdGenerator for UuidV4 {
+    fn generate(&self) -> Result<ItemId, String> {
+        Ok(ItemId::Text(Uuid::new_v4().to_string()))
+    }
+
+    fn parse(&self, raw: &str) -> Option<ItemId> {
+        parse_uuid(raw, 4)
+    }
+}
+
+/// Time-ordered UUIDs: random enough to be unguessable, and sorting by id
+/// sorts by creation.
+pub struct UuidV7;
+
+impl IdGenerator for UuidV7 {
+    fn generate(&self) -> Result<ItemId, String> {
+        // Ordered within the process even within one millisecond
+        Ok(ItemId::Text(Uuid::now_v7().to_string()))
+    }
+
+    fn parse(&self, raw: &str) -> Option<ItemId> {
+        parse_uuid(raw, 7)
+    }
+}
+
+fn parse_uuid(raw: &str, version: usize) -> Option<ItemId> {
+    Uuid::try_parse(raw)
+        .ok()
+        .filter(|uuid| uuid.get_version_num() == version)
+        .map(|uuid| ItemId::Text(uuid.to_string()))
+}
+
+/// Crockford's base 32, which ULIDs are written in.
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// ULIDs: a 48-bit millisecond timestamp and 80 random bits, written as 26
+/// characters that sort like the ids do.
+///
+/// Ids generated within the same millisecond (or after the clock stepped
+/// back) increment the previous one instead of drawing new random bits, so
+/// they still increase.
+#[derive(Default)]
+pub struct Ulid {
+    last: Mutex<u128>,
+}
+
+impl IdGenerator for Ulid {
+    fn generate(&self) -> Result<ItemId, String> {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let fresh = (millis << 80) | (rand::random::<u128>() & ((1 << 80) - 1));
+
+        let mut last = self.last.lock().unwrap();
+        *last = if fresh >> 80 > *last >> 80 {
+            fresh
+        } else {
+            *last + 1
+        };
+        let id = (0..26)
+            .map(|i| CROCKFORD[((*last >> (125 - 5 * i)) & 31) as usize] as char)
+            .collect();
+        Ok(ItemId::Text(id))
+    }
+
+    fn parse(&self, raw: &str) -> Option<ItemId> {
+        let id = raw.to_ascii_uppercase();
+        let well_formed = id.len() == 26
+            // The first character only carries the top 3 of 128 bits
+            && id.as_bytes()[0] <= b'7'
+            && id.bytes().all(|c| CROCKFORD.contains(&c));
+        well_formed.then_some(ItemId::Text(id))
+    }
+}
+
+/// The `:id` of an item route, parsed by the configured [`IdGenerator`].
+/// An id the scheme can't produce is a 400.
+pub struct ItemPath(pub ItemId);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ItemPath
+where
+    S: Send + Sync,
+    Arc<dyn IdGenerator>: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(raw) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| ApiError::BadRequest(rejection.body_text()))?;
+        Arc::<dyn IdGenerator>::from_ref(state)
+            .parse(&raw)
+            .map(Self)
+            .ok_or_else(|| ApiError::BadRequest(format!("{raw:?} is not a valid item id")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::{body_json, json_id, send, test_state_with};
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use std::collections::HashSet;
+
+    /// Generates `count` ids, as creates would.
+    fn generate(scheme: IdScheme, count: usize) -> Vec<ItemId> {
+        let generator = generator(scheme);
+        (0..count).map(|_| generator.generate().unwrap()).collect()
+    }
+
+    #[test]
+    fn every_scheme_generates_unique_ids_it_parses_back() {
+        for scheme in [
+            IdScheme::Sequential,
+            IdScheme::UuidV4,
+            IdScheme::UuidV7,
+            IdScheme::Ulid,
+        ] {
+            let ids = generate(scheme, 2000);
+            assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 2000, "{scheme:?}");
+            let generator = generator(scheme);
+            for id in &ids {
+                assert_eq!(
+                    generator.parse(&id.to_string()).as_ref(),
+                    Some(id),
+                    "{scheme:?}"
+                );
+            }
+        }
+
+        let sequential = generate(IdScheme::Sequential, 3);
+        assert_eq!(sequential, [1.into(), 2.into(), 3.into()]);
+        let ItemId::Text(uuid) = &generate(IdScheme::UuidV4, 1)[0] else {
+            panic!("UUIDs are strings");
+        };
+        assert_eq!((uuid.len(), &uuid[14..15]), (36, "4"), "{uuid}");
+        let ItemId::Text(ulid) = &generate(IdScheme::Ulid, 1)[0] else {
+            panic!("ULIDs are strings");
+        };
+        assert_eq!(ulid.len(), 26, "{ulid}");
+    }
+
+    #[test]
+    fn time_ordered_ids_increase() {
+        for scheme in [IdScheme::UuidV7, IdScheme::Ulid] {
+            // Far more ids than milliseconds pass
+            let ids = generate(scheme, 10_000);
+            assert!(ids.windows(2).all(|pair| pair[0] < pair[1]), "{scheme:?}");
+        }
+    }
+
+    #[test]
+    fn ids_of_another_scheme_are_rejected() {
+        let v4 = Uuid::new_v4().to_string();
+        let v7 = Uuid::now_v7().to_string();
+        assert_eq!(generator(IdScheme::Sequential).parse("0"), None);
+        assert_eq!(generator(IdScheme::Sequential).parse(&v4), None);
+        assert_eq!(generator(IdScheme::UuidV4).parse(&v7), None);
+        assert_eq!(generator(IdScheme::UuidV7).parse(&v4), None);
+        assert_eq!(generator(IdScheme::Ulid).parse("42"), None);
+        // ULIDs are case-insensitive and normalized to upper case
+        assert_eq!(
+            generator(IdScheme::Ulid).parse("01arz3ndektsv4rrffq69g5fav"),
+            Some(ItemId::Text("01ARZ3NDEKTSV4RRFFQ69G5FAV".to_string()))
+        );
+    }
+
+    #[test]
+    fn numeric_ids_are_read_in_either_form() {
+        let id = ItemId::from(u32::MAX);
+        let written = serde_json::to_string(&id).unwrap();
+        if cfg!(feature = "string-ids") {
+            assert_eq!(written, r#""4294967295""#);
+        } else {
+            assert_eq!(written, "4294967295");
+        }
+        for input in ["4294967295", r#""4294967295""#] {
+            assert_eq!(
+                serde_json::from_str::<ItemId>(input).unwrap(),
+                id,
+                "{input}"
+            );
+        }
+        let ulid = r#""01ARZ3NDEKTSV4RRFFQ69G5FAV""#;
+        assert!(matches!(
+            serde_json::from_str::<ItemId>(ulid).unwrap(),
+            ItemId::Text(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn large_ids_round_trip_through_the_api() {
+        let state = test_state_with(Config::default());
+        let response = send(
+            &state,
+            Request::put("/items/4294967295")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"name":"Widget","description":""}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let written = body_json(response).await["data"]["id"].clone();
+        assert_eq!(written, json_id(u32::MAX));
+        assert_eq!(written.is_string(), cfg!(feature = "string-ids"));
+
+        // Sent back as it was written, it finds the same item
+        let response = send(
+            &state,
+            Request::post("/items/batch-get")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "ids": [written] }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await;
+        let data = &body_json(response).await["data"];
+        assert_eq!(data["items"][0]["name"], "Widget");
+        assert_eq!(data["missing"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn creates_after_the_highest_id_is_taken_get_507() {
+        let state = test_state_with(Config::default());
+        let put = Request::put(format!("/items/{}", u32::MAX))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"name":"last","description":""}"#))
+            .unwrap();
+        assert_eq!(send(&state, put).await.status(), StatusCode::CREATED);
+
+        let create = || {
+            Request::post("/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"name":"next","description":""}"#))
+                .unwrap()
+        };
+        let response = send(&state, create()).await;
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+        assert_eq!(
+            body_json(response).await["message"],
+            "No sequential ids left: the highest id, 4294967295, has been given out"
+        );
+        assert_eq!(state.store.read().await.len(), 1);
+
+        let bulk = Request::post("/items/bulk")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"[{"name":"a","description":""}]"#))
+            .unwrap();
+        assert_eq!(
+            send(&state, bulk).await.status(),
+            StatusCode::INSUFFICIENT_STORAGE
+        );
+        assert_eq!(state.store.read().await.len(), 1, "nothing created");
+
+        // Its going away (expiry, eviction) doesn't give the id back
+        state.store.write().await.clear();
+        let response = send(&state, create()).await;
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+    }
+
+    #[tokio::test]
+    async fn ids_of_removed_items_are_never_reissued() {
+        let state = test_state_with(Config::default());
+        let create = || {
+            Request::post("/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"name":"a","description":""}"#))
+                .unwrap()
+        };
+        assert_eq!(send(&state, create()).await.status(), StatusCode::CREATED);
+        let put = Request::put("/items/10")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"name":"b","description":""}"#))
+            .unwrap();
+        assert_eq!(send(&state, put).await.status(), StatusCode::CREATED);
+        // As expiry or eviction would
+        state.store.write().await.remove(&ItemId::from(10));
+
+        let created = body_json(send(&state, create()).await).await;
+        assert_eq!(created["data"]["id"], json_id(11));
+        state.store.write().await.remove(&ItemId::from(11));
+        let created = body_json(send(&state, create()).await).await;
+        assert_eq!(created["data"]["id"], json_id(12));
+    }
+
+    #[tokio::test]
+    async fn routes_use_the_configured_scheme() {
+        let state = test_state_with(Config {
+            id_scheme: IdScheme::UuidV7,
+            ..Config::default()
+        });
+        let response = send(
+            &state,
+            Request::post("/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"name":"Widget","description":""}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let id = body_json(response).await["data"]["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let get = |id: &str| {
+            Request::get(format!("/items/{id}"))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = send(&state, get(&id)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["data"]["id"], id.as_str());
+        // Upper case is the same UUID
+        let response = send(&state, get(&id.to_uppercase())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send(&state, get("1")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_json(response).await["message"],
+            r#""1" is not a valid item id"#
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use axum::{body::Body, extract::State, http::StatusCode, response::Json};
+use futures::StreamExt;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::{telemetry, ApiResponse};
+
+/// Longest accepted event line, in bytes. Longer lines are rejected without
+/// being buffered in full.
+pub const MAX_EVENT_BYTES: usize = 64 * 1024;
+
+/// The most recently ingested events, oldest dropped first once `capacity`
+/// is reached. Stands in for a real sink such as a log pipeline or queue.
+pub struct EventBuffer {
+    capacity: usize,
+    events: Mutex<VecDeque<serde_json::Value>>,
+}
+
+impl EventBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn push(&self, event: serde_json::Value) {
+        let mut events = self.events.lock().unwrap();
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    #[cfg(test)]
+    pub fn snapshot(&self) -> Vec<serde_json::Value> {
+        self.events.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct IngestSummary {
+    pub accepted: usize,
+    pub rejected: usize,
+}
+
+/// Splits a byte stream into lines, holding at most [`MAX_EVENT_BYTES`] of an
+/// unfinished line. Overlong lines come out as `None`.
+#[derive(Default)]
+struct LineSplitter {
+    partial: Vec<u8>,
+    overlong: bool,
+}
+
+impl LineSplitter {
+    fn push(&mut self, mut chunk: &[u8], lines: &mut Vec<Option<Vec<u8>>>) {
+        while let Some(end) = chunk.iter().position(|b| *b == b'\n') {
+            self.append(&chunk[..end]);
+            lines.push(self.take());
+            chunk = &chunk[end + 1..];
+        }
+        self.append(chunk);
+    }
+
+    fn finish(&mut self, lines: &mut Vec<Option<Vec<u8>>>) {
+        if self.overlong || !self.partial.is_empty() {
+            lines.push(self.take());
+        }
+    }
+
+    fn append(&mut self, bytes: &[u8]) {
+        if self.partial.len() + bytes.len() > MAX_EVENT_BYTES {
+            self.overlong = true;
+            self.partial.clear();
+        } else if !self.overlong {
+            self.partial.extend_from_slice(bytes);
+        }
+    }
+
+    fn take(&mut self) -> Option<Vec<u8>> {
+        let line = std::mem::take(&mut self.partial);
+        (!std::mem::take(&mut self.overlong)).then_some(line)
+    }
+}
+
+/// Ingests an NDJSON stream of events, one JSON object per line.
+///
+/// Lines are parsed and stored as they arrive, so the body is never held in
+/// full. Blank lines are ignored; lines that aren't a JSON object, or are
+/// longer than [`MAX_EVENT_BYTES`], are counted as rejected and skipped
+/// without affecting the rest of the stream.
+pub async fn ingest_events(
+    State(events): State<Arc<EventBuffer>>,
+    body: Body,
+) -> (StatusCode, Json<ApiResponse<IngestSummary>>) {
+    let mut summary = IngestSummary::default();
+    let mut chunks = body.into_data_stream();
+    let mut splitter = LineSplitter::default();
+    let mut lines = Vec::new();
+    let mut read_error = None;
+
+    loop {
+        match chunks.next().await {
+            Some(Ok(chunk)) => splitter.push(&chunk, &mut lines),
+            Some(Err(err)) => {
+                read_error = Some(format!("Failed to read body: {err}"));
+                break;
+            }
+            None => {
+                splitter.finish(&mut lines);
+                break;
+            }
+        }
+        for line in lines.drain(..) {
+            store_line(&events, line, &mut summary);
+        }
+    }
+    for line in lines.drain(..) {
+        store_line(&events, line, &mut summary);
+    }
+
+    telemetry::record(|| {
+        metrics::counter!("events_ingested_total").increment(summary.accepted as u64);
+        metrics::counter!("events_rejected_total").increment(summary.rejected as u64);
+    });
+
+    let (status, message) = match read_error {
+        Some(message) => (StatusCode::BAD_REQUEST, message),
+        None => (
+            StatusCode::OK,
+            format!(
+                "{} events accepted, {} rejected",
+                summary.accepted, summary.rejected
+            ),
+        ),
+    };
+    (
+        status,
+        Json(ApiResponse {
+            success: status.is_success(),
+            data: Some(summary),
+            message,
+        }),
+    )
+}
+
+fn store_line(events: &EventBuffer, line: Option<Vec<u8>>, summary: &mut IngestSummary) {
+    let Some(line) = line else {
+        summary.rejected += 1;
+        return;
+    };
+    if line.trim_ascii().is_empty() {
+        return;
+    }
+    match serde_json::from_slice::<serde_json::Value>(&line) {
+        Ok(event) if event.is_object() => {
+            events.push(event);
+            summary.accepted += 1;
+        }
+        _ => summary.rejected += 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{body_json, send, test_state};
+    use axum::http::{header, Request};
+    use futures::stream;
+
+    #[tokio::test]
+    async fn bad_lines_are_counted_and_skipped() {
+        let state = test_state();
+        let payload = concat!(
+            "{\"level\":\"info\",\"msg\":\"one\"}\n",
+            "{\"level\":\"warn\",\"msg\":\"two\"}\r\n",
+            "not json\n",
+            "\n",
+            "[1,2]\n",
+            "{\"level\":\"error\",\"msg\":\"three\"}",
+        );
+        // Split mid-line so events straddle chunk boundaries
+        let chunks: Vec<Result<Vec<u8>, std::convert::Infallible>> = payload
+            .as_bytes()
+            .chunks(7)
+            .map(|chunk| Ok(chunk.to_vec()))
+            .collect();
+
+        let response = send(
+            &state,
+            Request::post("/ingest")
+                .header(header::CONTENT_TYPE, "application/x-ndjson")
+                .body(Body::from_stream(stream::iter(chunks)))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_json(response).await;
+        assert_eq!(body["data"]["accepted"], 3);
+        assert_eq!(body["data"]["rejected"], 2);
+
+        let messages: Vec<_> = state
+            .events
+            .snapshot()
+            .iter()
+            .map(|event| event["msg"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(messages, ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn overlong_lines_are_rejected_without_buffering() {
+        let mut splitter = LineSplitter::default();
+        let mut lines = Vec::new();
+
+        splitter.push(&vec![b'x'; MAX_EVENT_BYTES], &mut lines);
+        splitter.push(b"xx\n{}", &mut lines);
+        assert!(splitter.partial.len() <= MAX_EVENT_BYTES);
+        splitter.finish(&mut lines);
+
+        assert_eq!(lines, [None, Some(b"{}".to_vec())]);
+    }
+
+    #[test]
+    fn buffer_drops_the_oldest_events_past_capacity() {
+        let buffer = EventBuffer::new(2);
+        for n in 0..3 {
+            buffer.push(serde_json::json!({ "n": n }));
+        }
+        assert_eq!(
+            buffer.snapshot(),
+            [serde_json::json!({"n": 1}), serde_json::json!({"n": 2})]
+        );
+    }
+}
@@ -0,0 +1,2469 @@
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, HeaderMap, HeaderName, StatusCode, Uri},
+    response::{IntoResponse, Json, Response},
+};
+use futures::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+
+use crate::capacity::Capacity;
+use crate::changes::Change;
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::expiry::Expiry;
+use crate::extract::{JsonBody, Payload};
+use crate::history::{ItemDiff, ItemHistory};
+use crate::ids::{IdGenerator, ItemId, ItemPath};
+use crate::json_stream::{ArraySplitter, SplitError};
+use crate::list_query::{ListQuery, Pagination, RequestedView, View};
+use crate::quota::API_KEY;
+use crate::similarity::Similarity;
+use crate::{ApiResponse, AppState};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "camel-case-api", serde(rename_all = "camelCase"))]
+pub struct Item {
+    pub id: ItemId,
+    pub name: String,
+    pub description: String,
+    /// Creation time in seconds since the Unix epoch.
+    pub created_at: u64,
+    /// Time of the last change in seconds since the Unix epoch; equal to
+    /// `created_at` until the item is modified.
+    pub updated_at: u64,
+}
+
+/// The fields of an [`Item`] sent for [`View::Compact`].
+#[derive(Serialize, Debug)]
+pub struct ItemSummary {
+    pub id: ItemId,
+    pub name: String,
+}
+
+/// An [`Item`] as a response carries it, depending on the requested [`View`].
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum ItemRepresentation {
+    Compact(ItemSummary),
+    Full(Item),
+}
+
+impl Item {
+    /// Entity tag of the item's current content, sent as `ETag` and checked
+    /// against `If-Match`. Any change to the item changes it.
+    pub fn etag(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        format!("\"{:016x}\"", hasher.finish())
+    }
+
+    /// Whether the item changed since it was created.
+    pub fn is_modified(&self) -> bool {
+        self.updated_at > self.created_at
+    }
+
+    pub fn represent(&self, view: View) -> ItemRepresentation {
+        match view {
+            View::Compact => ItemRepresentation::Compact(ItemSummary {
+                id: self.id.clone(),
+                name: self.name.clone(),
+            }),
+            View::Full => ItemRepresentation::Full(self.clone()),
+        }
+    }
+}
+
+/// Builds an [`Item`] for tests and seed data, defaulting every field that
+/// isn't set: the name and description derive from the id, and the item is
+/// created now.
+#[cfg(test)]
+pub struct ItemBuilder {
+    id: ItemId,
+    name: Option<String>,
+    description: Option<String>,
+    created_at: Option<u64>,
+    updated_at: Option<u64>,
+}
+
+#[cfg(test)]
+impl ItemBuilder {
+    pub fn new(id: impl Into<ItemId>) -> Self {
+        Self {
+            id: id.into(),
+            name: None,
+            description: None,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn created_at(mut self, secs: u64) -> Self {
+        self.created_at = Some(secs);
+        self
+    }
+
+    /// Defaults to the creation time.
+    pub fn updated_at(mut self, secs: u64) -> Self {
+        self.updated_at = Some(secs);
+        self
+    }
+
+    pub fn build(self) -> Item {
+        let id = self.id;
+        let created_at = self
+            .created_at
+            .unwrap_or_else(|| crate::clock::unix_secs(&crate::clock::SystemClock));
+        Item {
+            name: self.name.unwrap_or_else(|| format!("item-{id}")),
+            description: self
+                .description
+                .unwrap_or_else(|| format!("description {id}")),
+            id,
+            created_at,
+            updated_at: self.updated_at.unwrap_or(created_at),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "camel-case-api", serde(rename_all = "camelCase"))]
+pub struct CreateItemRequest {
+    pub name: String,
+    pub description: String,
+}
+
+impl Payload for CreateItemRequest {
+    const FIELDS: &'static [&'static str] = &["name", "description"];
+}
+
+// In-memory storage for demo purposes. Items are shared rather than owned
+// by the map so readers can take a snapshot by copying pointers; see
+// [`snapshot`].
+pub type ItemStore = Arc<tokio::sync::RwLock<HashMap<ItemId, Arc<Item>>>>;
+
+/// The live items as of now, in no particular order.
+///
+/// Only pointers are copied under the read lock, which is released before
+/// the caller filters, sorts or serializes anything, so a large listing
+/// holds writers up for that copy rather than for the whole response.
+pub async fn snapshot(store: &ItemStore, expiry: &Expiry) -> Vec<Arc<Item>> {
+    let items = store.read().await;
+    items
+        .values()
+        .filter(|item| !expiry.is_expired(item))
+        .cloned()
+        .collect()
+}
+
+/// Lists items matching the [`ListQuery`] as a [`Page`](crate::list_query::Page),
+/// at most `DEFAULT_LIST_LIMIT` of them unless the request sets `limit`.
+/// Items are [`View::Compact`] unless the request asks for `view=full`.
+///
+/// With `Accept: application/x-ndjson` the page is streamed one item per
+/// line, the same way as [`export_items`], instead of as a wrapped array.
+/// Pages of at least `STREAM_LIST_MIN_ITEMS` items keep the wrapped array
+/// but stream it item by item rather than serializing it in one go.
+pub async fn get_items(
+    State(store): State<ItemStore>,
+    State(expiry): State<Expiry>,
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    uri: Uri,
+    mut query: ListQuery,
+) -> Response {
+    query.pagination = query.pagination.or_limit(config.default_list_limit);
+    let view = query.view.unwrap_or(View::Compact);
+
+    let wants_ndjson = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|accept| accept.contains(NDJSON));
+
+    let live = snapshot(&store, &expiry).await;
+    let page = query.select(live.iter().map(Arc::as_ref));
+
+    if wants_ndjson {
+        let ids = page.items.iter().map(|item| item.id.clone()).collect();
+        return ndjson_response(store, expiry, ids, view);
+    }
+
+    if config
+        .stream_list_min_items
+        .is_some_and(|min| page.items.len() >= min)
+    {
+        let mut page = page.map(|item| item.id.clone());
+        let elements = serialized_items(store, expiry, std::mem::take(&mut page.items), view);
+        return page.into_streamed_response(&uri, "Items retrieved successfully", elements);
+    }
+
+    page.map(|item| item.represent(view))
+        .into_response(&uri, "Items retrieved successfully")
+}
+
+/// Streams every item as newline-delimited JSON.
+pub async fn export_items(
+    State(store): State<ItemStore>,
+    State(expiry): State<Expiry>,
+) -> Response {
+    let mut ids: Vec<ItemId> = store.read().await.keys().cloned().collect();
+    ids.sort_unstable();
+    ndjson_response(store, expiry, ids, View::Full)
+}
+
+const NDJSON: &str = "application/x-ndjson";
+
+/// Serializes the items with the given ids, in order and in the given
+/// `view`, one at a time as the stream is polled.
+///
+/// Only the ids are snapshotted up front; each item is looked up and
+/// serialized as the client reads, so memory stays bounded by the id list
+/// rather than the items themselves. Items deleted or expired mid-stream are
+/// skipped.
+fn serialized_items(
+    store: ItemStore,
+    expiry: Expiry,
+    ids: Vec<ItemId>,
+    view: View,
+) -> impl Stream<Item = Vec<u8>> + Send + 'static {
+    stream::iter(ids).filter_map(move |id| {
+        let store = store.clone();
+        let expiry = expiry.clone();
+        async move {
+            let item = store
+                .read()
+                .await
+                .get(&id)
+                .filter(|item| !expiry.is_expired(item))
+                .map(|item| item.represent(view))?;
+            serde_json::to_vec(&item).ok()
+        }
+    })
+}
+
+/// Streams the items with the given ids, in order, one JSON object per line
+/// in the given `view`. See [`serialized_items`].
+fn ndjson_response(store: ItemStore, expiry: Expiry, ids: Vec<ItemId>, view: View) -> Response {
+    let lines = serialized_items(store, expiry, ids, view).map(|mut line| {
+        line.push(b'\n');
+        Ok::<_, std::convert::Infallible>(line)
+    });
+
+    ([(header::CONTENT_TYPE, NDJSON)], Body::from_stream(lines)).into_response()
+}
+
+/// Gets one item, in full unless the request asks for `view=compact`, with
+/// its [`Item::etag`] as `ETag`.
+pub async fn get_item(
+    ItemPath(id): ItemPath,
+    view: RequestedView,
+    State(store): State<ItemStore>,
+    State(expiry): State<Expiry>,
+    State(capacity): State<Arc<Capacity>>,
+) -> Result<Response, StatusCode> {
+    let items = store.read().await;
+
+    if let Some(item) = items.get(&id).filter(|item| !expiry.is_expired(item)) {
+        capacity.touch(&id);
+        let body = Json(ApiResponse {
+            success: true,
+            data: Some(item.represent(view.or(View::Full))),
+            message: "Item found".to_string(),
+        });
+        Ok(([(header::ETAG, item.etag())], body).into_response())
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+/// Number of related items returned when `limit` isn't given.
+const DEFAULT_RELATED_LIMIT: usize = 5;
+
+/// Lists the items whose names are most similar to the given item's, most
+/// similar first and never including the item itself. See [`Similarity`] for
+/// how names are compared; ties go to the lower id. Paged like every list
+/// endpoint, with a default `limit` of [`DEFAULT_RELATED_LIMIT`].
+pub async fn get_related_items(
+    ItemPath(id): ItemPath,
+    uri: Uri,
+    pagination: Pagination,
+    State(store): State<ItemStore>,
+    State(expiry): State<Expiry>,
+) -> Result<Response, StatusCode> {
+    let live = snapshot(&store, &expiry).await;
+    let target = live
+        .iter()
+        .find(|item| item.id == id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let mut related: Vec<(Similarity, &Item)> = live
+        .iter()
+        .filter(|item| item.id != id)
+        .map(|item| (Similarity::between(&target.name, &item.name), item.as_ref()))
+        .collect();
+    related.sort_by(|(a, x), (b, y)| a.cmp(b).then_with(|| x.id.cmp(&y.id)));
+
+    Ok(pagination
+        .or_limit(DEFAULT_RELATED_LIMIT)
+        .page(related)
+        .map(|(_, item)| item.clone())
+        .into_response(&uri, "Related items retrieved successfully"))
+}
+
+/// Query of `GET /items/:id/diff`: the two version numbers to compare.
+#[derive(Deserialize)]
+pub struct DiffRange {
+    pub from: u32,
+    pub to: u32,
+}
+
+/// Lists the fields that changed between two versions of an item, numbered
+/// from 1 as created; see [`ItemHistory`]. A version that was never written,
+/// or is older than `ITEM_HISTORY_LIMIT` versions, is a 404. `from == to`
+/// is an empty diff.
+pub async fn diff_item(
+    ItemPath(id): ItemPath,
+    Query(range): Query<DiffRange>,
+    State(store): State<ItemStore>,
+    State(expiry): State<Expiry>,
+    State(history): State<Arc<ItemHistory>>,
+) -> Result<Json<ApiResponse<ItemDiff>>, ApiError> {
+    let items = store.read().await;
+    let current = items
+        .get(&id)
+        .filter(|item| !expiry.is_expired(item))
+        .ok_or_else(|| ApiError::NotFound(format!("Item {id} not found")))?;
+    let (from, to) = history
+        .versions(current, range.from, range.to)
+        .map_err(|version| ApiError::NotFound(format!("Item {id} has no version {version}")))?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        data: Some(ItemDiff::between((range.from, &from), (range.to, &to))),
+        message: "Item diff retrieved successfully".to_string(),
+    }))
+}
+
+/// Body of `GET /items/stats`: aggregates over the live items.
+#[derive(Serialize, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "camel-case-api", serde(rename_all = "camelCase"))]
+pub struct ItemStats {
+    pub total: usize,
+    /// Items by whether they changed since they were created: `modified`
+    /// or `unmodified`.
+    pub by_state: BTreeMap<&'static str, usize>,
+    /// In characters, as `MAX_DESCRIPTION_LEN` counts them; 0 when empty.
+    pub avg_description_len: f64,
+    pub max_description_len: usize,
+    pub oldest_created_at: Option<u64>,
+    pub newest_created_at: Option<u64>,
+}
+
+impl ItemStats {
+    /// Aggregates `items` in one pass.
+    pub fn of<'a>(items: impl IntoIterator<Item = &'a Item>) -> Self {
+        let mut stats = Self::default();
+        let mut description_chars = 0;
+        for item in items {
+            let len = item.description.chars().count();
+            let state = if item.is_modified() {
+                "modified"
+            } else {
+                "unmodified"
+            };
+            stats.total += 1;
+            *stats.by_state.entry(state).or_default() += 1;
+            description_chars += len;
+            stats.max_description_len = stats.max_description_len.max(len);
+            let created = item.created_at;
+            stats.oldest_created_at =
+                Some(stats.oldest_created_at.map_or(created, |t| t.min(created)));
+            stats.newest_created_at =
+                Some(stats.newest_created_at.map_or(created, |t| t.max(created)));
+        }
+        if stats.total > 0 {
+            stats.avg_description_len = description_chars as f64 / stats.total as f64;
+        }
+        stats
+    }
+}
+
+/// Aggregate statistics over the live items, computed under the read lock.
+pub async fn item_stats(
+    State(store): State<ItemStore>,
+    State(expiry): State<Expiry>,
+) -> Json<ApiResponse<ItemStats>> {
+    let items = store.read().await;
+    let stats = ItemStats::of(
+        items
+            .values()
+            .filter(|item| !expiry.is_expired(item))
+            .map(Arc::as_ref),
+    );
+
+    Json(ApiResponse {
+        success: true,
+        data: Some(stats),
+        message: "Item statistics retrieved successfully".to_string(),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct BatchGetRequest {
+    pub ids: Vec<ItemId>,
+}
+
+impl Payload for BatchGetRequest {
+    const FIELDS: &'static [&'static str] = &["ids"];
+}
+
+#[derive(Serialize, Debug)]
+pub struct BatchGetResponse {
+    /// Found items, in the order their ids were requested.
+    pub items: Vec<Item>,
+    /// Requested ids with no item, in request order.
+    pub missing: Vec<ItemId>,
+}
+
+/// Looks up many items in one read-locked pass, partitioning the requested
+/// ids into found items and missing ids, both in request order. Repeated
+/// ids are answered once. At most `MAX_BATCH_GET_IDS` ids per request.
+pub async fn batch_get_items(
+    State(state): State<AppState>,
+    JsonBody(request): JsonBody<BatchGetRequest>,
+) -> Result<Json<ApiResponse<BatchGetResponse>>, ApiError> {
+    let max = state.config.max_batch_get_ids;
+    if request.ids.len() > max {
+        return Err(ApiError::BadRequest(format!(
+            "At most {max} ids per request, got {}",
+            request.ids.len()
+        )));
+    }
+
+    let mut seen = HashSet::new();
+    let mut response = BatchGetResponse {
+        items: Vec::new(),
+        missing: Vec::new(),
+    };
+    let items = state.store.read().await;
+    for id in request.ids.into_iter().filter(|id| seen.insert(id.clone())) {
+        match items.get(&id).filter(|item| !state.expiry.is_expired(item)) {
+            Some(item) => {
+                state.capacity.touch(&id);
+                response.items.push(Item::clone(item));
+            }
+            None => response.missing.push(id),
+        }
+    }
+    drop(items);
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!(
+            "{} items found, {} missing",
+            response.items.len(),
+            response.missing.len()
+        ),
+        data: Some(response),
+    }))
+}
+
+/// Creates an item, answering 201. An identical payload submitted again
+/// within `CREATE_DEDUPE_WINDOW_MS` (by the same client, unless
+/// `CREATE_DEDUPE_KEY=payload`) gets the first item back with 200; with
+/// `UNIQUE_NAME` any other create reusing a live item's name gets 409.
+pub async fn create_item(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    JsonBody(payload): JsonBody<CreateItemRequest>,
+) -> Result<(StatusCode, Json<ApiResponse<Item>>), ApiError> {
+    check_description(&state.config, &payload.description).map_err(ApiError::Unprocessable)?;
+    let client = headers.get(&API_KEY).and_then(|value| value.to_str().ok());
+    let mut items = state.store.write().await;
+
+    if let Some(existing) = state
+        .dedupe
+        .recent(&payload, client)
+        .and_then(|id| items.get(&id))
+        .filter(|item| !state.expiry.is_expired(item))
+        // Not if it was replaced since (`PUT`, `PATCH`, an import)
+        .filter(|item| {
+            item.name.trim() == payload.name.trim()
+                && item.description.trim() == payload.description.trim()
+        })
+    {
+        return Ok((
+            StatusCode::OK,
+            Json(ApiResponse {
+                success: true,
+                data: Some(Item::clone(existing)),
+                message: "Duplicate submission, returning the existing item".to_string(),
+            }),
+        ));
+    }
+
+    if let Some(message) = name_conflict(&state.config, &items, &state.expiry, &payload.name, None)
+    {
+        return Err(ApiError::Conflict(message));
+    }
+    state
+        .ids
+        .check_available(1)
+        .and_then(|()| state.capacity.make_room(&mut items, 1))
+        .map_err(ApiError::InsufficientStorage)?;
+    let id = state
+        .ids
+        .generate()
+        .map_err(ApiError::InsufficientStorage)?;
+    state.dedupe.record(&payload, client, id.clone());
+    let now = state.expiry.now_secs();
+    let item = Item {
+        id: id.clone(),
+        name: payload.name,
+        description: payload.description,
+        created_at: now,
+        updated_at: now,
+    };
+
+    state.history.record(None, &item);
+    state.capacity.touch(&id);
+    state.changes.publish(Change::Created, &item);
+    items.insert(id, Arc::new(item.clone()));
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse {
+            success: true,
+            data: Some(item),
+            message: "Item created successfully".to_string(),
+        }),
+    ))
+}
+
+/// Creates or replaces the item at a client-chosen id, which must be one
+/// the `ID_SCHEME` could have generated (sequential ids start at 1).
+///
+/// Unlike `POST /items`, which always creates under a new id, this
+/// is idempotent: it answers 201 with a `Location` header when nothing (or
+/// only an expired item) was at `id`, and 200 when it replaced a live item.
+/// A replacement keeps the original `created_at`. With `UNIQUE_NAME`, taking
+/// a name another live item has is a 409.
+pub async fn put_item(
+    ItemPath(id): ItemPath,
+    State(state): State<AppState>,
+    JsonBody(payload): JsonBody<CreateItemRequest>,
+) -> Result<Response, ApiError> {
+    check_description(&state.config, &payload.description).map_err(ApiError::Unprocessable)?;
+
+    let now = state.expiry.now_secs();
+    let mut items = state.store.write().await;
+    if let Some(message) = name_conflict(
+        &state.config,
+        &items,
+        &state.expiry,
+        &payload.name,
+        Some(&id),
+    ) {
+        return Err(ApiError::Conflict(message));
+    }
+    if !items.contains_key(&id) {
+        state
+            .capacity
+            .make_room(&mut items, 1)
+            .map_err(ApiError::InsufficientStorage)?;
+    }
+    let replaced = items.get(&id).filter(|item| !state.expiry.is_expired(item));
+    let item = Item {
+        id: id.clone(),
+        name: payload.name,
+        description: payload.description,
+        created_at: replaced.map_or(now, |item| item.created_at),
+        updated_at: now,
+    };
+    let created = replaced.is_none();
+    state.history.record(replaced.map(Arc::as_ref), &item);
+    state.capacity.touch(&id);
+    state.ids.observe(&id);
+    let change = if created {
+        Change::Created
+    } else {
+        Change::Updated
+    };
+    state.changes.publish(change, &item);
+    items.insert(id.clone(), Arc::new(item.clone()));
+
+    let response = Json(ApiResponse {
+        success: true,
+        data: Some(item),
+        message: if created {
+            "Item created successfully"
+        } else {
+            "Item replaced successfully"
+        }
+        .to_string(),
+    });
+    Ok(if created {
+        (
+            StatusCode::CREATED,
+            [(header::LOCATION, format!("/items/{id}"))],
+            response,
+        )
+            .into_response()
+    } else {
+        response.into_response()
+    })
+}
+
+/// Body of `PATCH /items/:id`: the fields to change, leaving out the rest.
+#[derive(Deserialize)]
+pub struct ItemPatch {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Payload for ItemPatch {
+    const FIELDS: &'static [&'static str] = &["name", "description"];
+}
+
+impl ItemPatch {
+    fn apply(&self, item: &Item, now: u64) -> Item {
+        Item {
+            name: self.name.clone().unwrap_or_else(|| item.name.clone()),
+            description: self
+                .description
+                .clone()
+                .unwrap_or_else(|| item.description.clone()),
+            updated_at: now,
+            ..item.clone()
+        }
+    }
+}
+
+/// Header asking `PATCH` to re-apply the change to the latest version, up
+/// to this many times, instead of failing on a concurrent update.
+pub static RETRY_ON_CONFLICT: HeaderName = HeaderName::from_static("retry-on-conflict");
+
+/// Most re-applications one `Retry-On-Conflict` request may ask for.
+pub const MAX_CONFLICT_RETRIES: u32 = 10;
+
+/// Changes the fields given in the body and keeps the rest.
+///
+/// With `If-Match`, the change applies only to the version with that
+/// [`Item::etag`]; a stale tag is a 412 and the client re-fetches. A
+/// partial change that doesn't depend on what others may have changed (say,
+/// one that only sets `description`) can send `Retry-On-Conflict: N`
+/// instead: on a stale tag, or when another write lands between reading the
+/// item and writing it back, the change is re-applied to the latest version
+/// up to N times (at most [`MAX_CONFLICT_RETRIES`]) before giving up with
+/// 409. Only send it for changes that are safe to apply in any order.
+pub async fn patch_item(
+    ItemPath(id): ItemPath,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    JsonBody(patch): JsonBody<ItemPatch>,
+) -> Result<Response, ApiError> {
+    if let Some(description) = &patch.description {
+        check_description(&state.config, description).map_err(ApiError::Unprocessable)?;
+    }
+    let retries = match headers.get(&RETRY_ON_CONFLICT) {
+        None => 0,
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|raw| raw.trim().parse::<u32>().ok())
+            .filter(|retries| *retries <= MAX_CONFLICT_RETRIES)
+            .ok_or_else(|| {
+                ApiError::BadRequest(format!(
+                    "Retry-On-Conflict must be a number of retries from 0 to {MAX_CONFLICT_RETRIES}"
+                ))
+            })?,
+    };
+    let mut expected = headers
+        .get(header::IF_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .filter(|tag| *tag != "*")
+        .map(str::to_string);
+    let not_found = || ApiError::NotFound(format!("Item {id} not found"));
+
+    let mut conflicts = 0;
+    loop {
+        let (base, patched) = {
+            let items = state.store.read().await;
+            let item = items
+                .get(&id)
+                .filter(|item| !state.expiry.is_expired(item))
+                .ok_or_else(not_found)?;
+            (item.etag(), patch.apply(item, state.expiry.now_secs()))
+        };
+
+        let stale = expected.as_ref().is_some_and(|tag| *tag != base);
+        if !stale {
+            let mut items = state.store.write().await;
+            if let Some(message) = name_conflict(
+                &state.config,
+                &items,
+                &state.expiry,
+                &patched.name,
+                Some(&id),
+            ) {
+                return Err(ApiError::Conflict(message));
+            }
+            let current = items
+                .get(&id)
+                .filter(|item| !state.expiry.is_expired(item))
+                .ok_or_else(not_found)?;
+            // Unchanged since it was read, so the patch applies as computed
+            if current.etag() == base {
+                let etag = patched.etag();
+                state.history.record(Some(current), &patched);
+                state.capacity.touch(&id);
+                state.changes.publish(Change::Updated, &patched);
+                items.insert(id.clone(), Arc::new(patched.clone()));
+                let body = Json(ApiResponse {
+                    success: true,
+                    data: Some(patched),
+                    message: "Item updated successfully".to_string(),
+                });
+                return Ok(([(header::ETAG, etag)], body).into_response());
+            }
+        }
+
+        if conflicts == retries {
+            return Err(if stale && retries == 0 {
+                ApiError::PreconditionFailed(format!(
+                    "Item {id} has changed; its current ETag is {base}"
+                ))
+            } else {
+                ApiError::Conflict(format!(
+                    "Item {id} kept changing during the update, gave up after {retries} retries"
+                ))
+            });
+        }
+        conflicts += 1;
+        // The retry rebases onto whatever is current now
+        expected = None;
+    }
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    /// Create every item or none of them.
+    #[default]
+    Atomic,
+    /// Create the valid items and report the rest.
+    BestEffort,
+}
+
+#[derive(Deserialize)]
+pub struct BulkParams {
+    #[serde(default)]
+    mode: BulkMode,
+}
+
+/// Outcome for one entry of a best-effort bulk request.
+#[derive(Serialize, Debug)]
+#[cfg_attr(feature = "camel-case-api", serde(rename_all = "camelCase"))]
+pub struct BulkResult {
+    /// Position of the entry in the request.
+    pub index: usize,
+    /// 201 if the item was created, otherwise why it wasn't.
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<ItemId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Creates the items in the payload; `mode` decides what happens when some
+/// are invalid.
+///
+/// In `atomic` mode (the default) every item is created or none are: a
+/// malformed entry, a description over `MAX_DESCRIPTION_LEN`, or a payload
+/// naming the same item twice (names act as the unique key), is rejected with
+/// 422 before the store is touched. In
+/// `best_effort` mode valid entries are created regardless and the answer is
+/// 207 Multi-Status with a [`BulkResult`] per entry, in request order; a
+/// repeated name fails every occurrence after the first. With `UNIQUE_NAME`,
+/// names already taken in the store are a 409 for the whole batch, or for
+/// that entry in `best_effort` mode.
+///
+/// Ids are assigned under a single write lock and therefore never collide
+/// within the batch.
+pub async fn bulk_create_items(
+    State(state): State<AppState>,
+    Query(params): Query<BulkParams>,
+    Json(payload): Json<Vec<serde_json::Value>>,
+) -> Result<Response, (StatusCode, Json<ApiResponse<()>>)> {
+    let AppState {
+        store,
+        expiry,
+        config,
+        ids,
+        capacity,
+        changes,
+        ..
+    } = &state;
+    let created_at = expiry.now_secs();
+
+    if params.mode == BulkMode::BestEffort {
+        let mut items = store.write().await;
+        let mut seen = HashSet::new();
+        let results: Vec<BulkResult> = payload
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let failed = |error: String| BulkResult {
+                    index,
+                    status: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                    id: None,
+                    error: Some(error),
+                };
+                let entry = match serde_json::from_value::<CreateItemRequest>(entry) {
+                    Ok(entry) => entry,
+                    Err(e) => return failed(format!("Invalid item: {e}")),
+                };
+                if let Err(message) = check_description(config, &entry.description) {
+                    return failed(message);
+                }
+                if !seen.insert(entry.name.clone()) {
+                    return failed(format!("Duplicate name within the batch: {:?}", entry.name));
+                }
+                if let Some(message) = name_conflict(config, &items, expiry, &entry.name, None) {
+                    return BulkResult {
+                        status: StatusCode::CONFLICT.as_u16(),
+                        ..failed(message)
+                    };
+                }
+                if let Err(message) = ids
+                    .check_available(1)
+                    .and_then(|()| capacity.make_room(&mut items, 1))
+                {
+                    return BulkResult {
+                        status: StatusCode::INSUFFICIENT_STORAGE.as_u16(),
+                        ..failed(message)
+                    };
+                }
+                let item = match insert_new(ids.as_ref(), &mut items, entry, created_at) {
+                    Ok(item) => item,
+                    Err(message) => {
+                        return BulkResult {
+                            status: StatusCode::INSUFFICIENT_STORAGE.as_u16(),
+                            ..failed(message)
+                        }
+                    }
+                };
+                capacity.touch(&item.id);
+                changes.publish(Change::Created, &item);
+                BulkResult {
+                    index,
+                    status: StatusCode::CREATED.as_u16(),
+                    id: Some(item.id),
+                    error: None,
+                }
+            })
+            .collect();
+
+        let created = results.iter().filter(|result| result.id.is_some()).count();
+        return Ok((
+            StatusCode::MULTI_STATUS,
+            Json(ApiResponse {
+                success: created == results.len(),
+                message: format!("{created} of {} items created", results.len()),
+                data: Some(results),
+            }),
+        )
+            .into_response());
+    }
+
+    let payload: Vec<CreateItemRequest> = serde_json::from_value(payload.into()).map_err(|e| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ApiResponse::error(format!("Invalid item: {e}")),
+        )
+    })?;
+    if let Some(message) = duplicate_names(&payload) {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ApiResponse::error(message),
+        ));
+    }
+    for (index, entry) in payload.iter().enumerate() {
+        check_description(config, &entry.description).map_err(|message| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ApiResponse::error(format!("Item at index {index} is invalid: {message}")),
+            )
+        })?;
+    }
+
+    let mut items = store.write().await;
+    if let Some(message) = payload
+        .iter()
+        .find_map(|entry| name_conflict(config, &items, expiry, &entry.name, None))
+    {
+        return Err((StatusCode::CONFLICT, ApiResponse::error(message)));
+    }
+    // Both checked before evicting anything, so a refused batch loses nothing
+    ids.check_available(payload.len())
+        .and_then(|()| capacity.make_room(&mut items, payload.len()))
+        .map_err(|message| {
+            (
+                StatusCode::INSUFFICIENT_STORAGE,
+                ApiResponse::error(message),
+            )
+        })?;
+    let mut created: Vec<Item> = Vec::with_capacity(payload.len());
+    for entry in payload {
+        match insert_new(ids.as_ref(), &mut items, entry, created_at) {
+            Ok(item) => created.push(item),
+            Err(message) => {
+                // All or nothing: take back what this batch already added
+                for item in &created {
+                    items.remove(&item.id);
+                }
+                return Err((
+                    StatusCode::INSUFFICIENT_STORAGE,
+                    ApiResponse::error(message),
+                ));
+            }
+        }
+    }
+    for item in &created {
+        capacity.touch(&item.id);
+        changes.publish(Change::Created, item);
+    }
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("{} items created successfully", created.len()),
+        data: Some(created),
+    })
+    .into_response())
+}
+
+/// Rejects a description longer than `MAX_DESCRIPTION_LEN` characters.
+/// Every path that sets a description goes through this, so they all agree
+/// on the limit.
+fn check_description(config: &Config, description: &str) -> Result<(), String> {
+    let len = description.chars().count();
+    if len > config.max_description_len {
+        return Err(format!(
+            "description is {len} characters long, the limit is {}",
+            config.max_description_len
+        ));
+    }
+    Ok(())
+}
+
+/// With `UNIQUE_NAME` on, describes the conflict if a live item other than
+/// `id` already has `name`. Callers hold the store's write lock across this
+/// check and their write, so two requests can't both claim a name.
+fn name_conflict(
+    config: &Config,
+    items: &HashMap<ItemId, Arc<Item>>,
+    expiry: &Expiry,
+    name: &str,
+    id: Option<&ItemId>,
+) -> Option<String> {
+    let taken = config.unique_name
+        && items
+            .values()
+            .any(|item| item.name == name && Some(&item.id) != id && !expiry.is_expired(item));
+    taken.then(|| format!("An item named {name:?} already exists"))
+}
+
+/// Stores `entry` as a new item under a newly generated id; the error is
+/// the generator's when it has no id left.
+fn insert_new(
+    ids: &dyn IdGenerator,
+    items: &mut HashMap<ItemId, Arc<Item>>,
+    entry: CreateItemRequest,
+    created_at: u64,
+) -> Result<Item, String> {
+    let id = ids.generate()?;
+    let item = Item {
+        id: id.clone(),
+        name: entry.name,
+        description: entry.description,
+        created_at,
+        updated_at: created_at,
+    };
+    items.insert(id, Arc::new(item.clone()));
+    Ok(item)
+}
+
+/// Describes every name that appears more than once in `entries`, with the
+/// indices it appears at, or `None` if all names are distinct.
+fn duplicate_names(entries: &[CreateItemRequest]) -> Option<String> {
+    let mut indices_by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        indices_by_name.entry(&entry.name).or_default().push(index);
+    }
+
+    let mut duplicates: Vec<(&str, Vec<usize>)> = indices_by_name
+        .into_iter()
+        .filter(|(_, indices)| indices.len() > 1)
+        .collect();
+    if duplicates.is_empty() {
+        return None;
+    }
+    duplicates.sort_by_key(|(_, indices)| indices[0]);
+
+    let described: Vec<String> = duplicates
+        .iter()
+        .map(|(name, indices)| format!("{name:?} at indices {indices:?}"))
+        .collect();
+    Some(format!(
+        "Duplicate names within the batch: {}",
+        described.join(", ")
+    ))
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Upsert by name: update existing items, create new ones.
+    #[default]
+    Merge,
+    /// Clear the store, then load the payload.
+    Replace,
+}
+
+#[derive(Deserialize)]
+pub struct ImportParams {
+    #[serde(default)]
+    mode: ImportMode,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct ImportSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    /// Number of write-lock batches the import was applied in.
+    pub batches: usize,
+}
+
+/// Entries parsed before they are applied under one write-lock acquisition.
+const IMPORT_BATCH_SIZE: usize = 500;
+
+/// Longest import entry, in bytes of JSON. Larger entries are refused with
+/// 413 without being buffered in full.
+const MAX_IMPORT_ENTRY_BYTES: usize = 1024 * 1024;
+
+/// Imports a JSON array of items keyed by name.
+///
+/// The body is parsed as it streams in and applied in batches of
+/// [`IMPORT_BATCH_SIZE`], so neither the raw body nor the parsed entries are
+/// ever held in full and readers are not blocked for the whole upload. An
+/// entry longer than [`MAX_IMPORT_ENTRY_BYTES`] is refused with 413 rather
+/// than buffered.
+///
+/// In `merge` mode an entry whose name matches an existing item updates that
+/// item's description (or is skipped if nothing changed); other entries are
+/// created. `replace` clears the store first, so every distinct name is
+/// created and repeated names within the payload collapse the same way.
+///
+/// Imports are not atomic: when an entry is malformed, the payload exceeds
+/// `MAX_IMPORT_ITEMS`, or the store is full (`MAX_ITEMS`, 507) or out of
+/// ids, everything before it has already been applied. The error names the
+/// zero-based index of the offending entry and the summary covers what was
+/// applied.
+pub async fn import_items(
+    State(state): State<AppState>,
+    Query(params): Query<ImportParams>,
+    body: Body,
+) -> (StatusCode, Json<ApiResponse<ImportSummary>>) {
+    let mut summary = ImportSummary::default();
+    if params.mode == ImportMode::Replace {
+        // Every item goes, and new ones may be given the same ids
+        state.history.retain(|_| false);
+    }
+
+    let (status, message) = match stream_import(&state, params.mode, body, &mut summary).await {
+        Ok(()) => (StatusCode::OK, "Items imported successfully".to_string()),
+        Err((status, message)) => (status, message),
+    };
+    // Even a failed import may have written (or, replacing, cleared) items
+    state.changes.resync("import");
+
+    (
+        status,
+        Json(ApiResponse {
+            success: status.is_success(),
+            data: Some(summary),
+            message,
+        }),
+    )
+}
+
+async fn stream_import(
+    state: &AppState,
+    mode: ImportMode,
+    body: Body,
+    summary: &mut ImportSummary,
+) -> Result<(), (StatusCode, String)> {
+    if mode == ImportMode::Replace {
+        state.store.write().await.clear();
+    }
+    let max_items = state.config.max_import_items;
+
+    let mut chunks = body.into_data_stream();
+    let mut splitter = ArraySplitter::new(MAX_IMPORT_ENTRY_BYTES);
+    let mut elements = Vec::new();
+    let mut pending = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    let mut index = 0;
+
+    let result = async {
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|err| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to read body: {err}"),
+                )
+            })?;
+            splitter
+                .push(&chunk, &mut elements)
+                .map_err(|err| match err {
+                    SplitError::ElementTooLarge { index, max } => (
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        format!("Item at index {index} is over {max} bytes"),
+                    ),
+                    err => (StatusCode::BAD_REQUEST, format!("Invalid import: {err}")),
+                })?;
+
+            for raw in elements.drain(..) {
+                if index >= max_items {
+                    return Err((
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        format!("Item at index {index} exceeds the limit of {max_items} items"),
+                    ));
+                }
+                let entry: CreateItemRequest = serde_json::from_slice(&raw).map_err(|err| {
+                    (
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        format!("Item at index {index} is invalid: {err}"),
+                    )
+                })?;
+                check_description(&state.config, &entry.description).map_err(|message| {
+                    (
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        format!("Item at index {index} is invalid: {message}"),
+                    )
+                })?;
+                pending.push(entry);
+                index += 1;
+
+                if pending.len() == IMPORT_BATCH_SIZE {
+                    apply_import_batch(state, &mut pending, summary).await?;
+                }
+            }
+        }
+        splitter
+            .finish()
+            .map_err(|err| (StatusCode::BAD_REQUEST, format!("Invalid import: {err}")))
+    }
+    .await;
+
+    // Entries parsed before a failure are still applied, so the summary
+    // matches the store.
+    let applied = apply_import_batch(state, &mut pending, summary).await;
+    result.and(applied)
+}
+
+/// Applies a batch of parsed entries under one write lock. Fails with 507,
+/// leaving the rest of the batch out, at an entry the store has no room or
+/// id for.
+async fn apply_import_batch(
+    state: &AppState,
+    batch: &mut Vec<CreateItemRequest>,
+    summary: &mut ImportSummary,
+) -> Result<(), (StatusCode, String)> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+
+    let created_at = state.expiry.now_secs();
+    let mut items = state.store.write().await;
+    let mut ids_by_name: HashMap<String, ItemId> = items
+        .values()
+        .map(|item| (item.name.clone(), item.id.clone()))
+        .collect();
+
+    for entry in batch.drain(..) {
+        match ids_by_name
+            .get(&entry.name)
+            .and_then(|id| items.get_mut(id))
+        {
+            Some(existing) if existing.description == entry.description => summary.skipped += 1,
+            Some(existing) => {
+                let existing = Arc::make_mut(existing);
+                existing.description = entry.description;
+                existing.updated_at = created_at;
+                summary.updated += 1;
+            }
+            None => {
+                if let Err(message) = state
+                    .ids
+                    .check_available(1)
+                    .and_then(|()| state.capacity.make_room(&mut items, 1))
+                {
+                    summary.batches += 1;
+                    return Err((StatusCode::INSUFFICIENT_STORAGE, message));
+                }
+                let id = match state.ids.generate() {
+                    Ok(id) => id,
+                    Err(message) => {
+                        summary.batches += 1;
+                        return Err((StatusCode::INSUFFICIENT_STORAGE, message));
+                    }
+                };
+                state.capacity.touch(&id);
+                ids_by_name.insert(entry.name.clone(), id.clone());
+                items.insert(
+                    id.clone(),
+                    Arc::new(Item {
+                        id,
+                        name: entry.name,
+                        description: entry.description,
+                        created_at,
+                        updated_at: created_at,
+                    }),
+                );
+                summary.created += 1;
+            }
+        }
+    }
+    summary.batches += 1;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{body_json, json_id, seed, send, test_state, test_state_with};
+    use axum::{body::to_bytes, http::Request};
+    use std::time::Duration;
+
+    #[cfg(feature = "camel-case-api")]
+    #[tokio::test]
+    async fn api_uses_camel_case_field_names() {
+        let state = test_state();
+
+        let response = send(
+            &state,
+            Request::post("/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"name":"Widget","description":"A widget"}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let body = body_json(response).await;
+        let keys = |value: &serde_json::Value| {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(keys(&body), ["data", "message", "success"]);
+        assert_eq!(
+            keys(&body["data"]),
+            ["createdAt", "description", "id", "name", "updatedAt"]
+        );
+        assert_eq!(body["data"]["description"], "A widget");
+    }
+
+    #[test]
+    fn item_builder_fills_defaults_and_takes_overrides() {
+        let item = ItemBuilder::new(7).build();
+        assert_eq!(item.id, ItemId::from(7));
+        assert_eq!(item.name, "item-7");
+        assert_eq!(item.description, "description 7");
+        assert!(item.created_at > 1_600_000_000, "defaults to now");
+
+        let item = ItemBuilder::new(7)
+            .name("custom")
+            .description("")
+            .created_at(42)
+            .build();
+        assert_eq!(item.name, "custom");
+        assert_eq!(item.description, "");
+        assert_eq!(item.created_at, 42);
+    }
+
+    #[tokio::test]
+    async fn export_streams_one_item_per_line() {
+        let state = test_state();
+        seed(&state, 25).await;
+
+        let response = send(
+            &state,
+            Request::get("/items/export").body(Body::empty()).unwrap(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-ndjson"
+        );
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let items: Vec<Item> = body
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(items.len(), 25);
+        assert_eq!(items[0].name, "item-1");
+    }
+
+    fn import_request(mode: &str, body: &str) -> Request<Body> {
+        Request::post(format!("/items/import?mode={mode}"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn import_merge_updates_existing_and_creates_new() {
+        let state = test_state();
+        seed(&state, 2).await;
+
+        let response = send(
+            &state,
+            import_request(
+                "merge",
+                r#"[
+                    {"name":"item-1","description":"changed"},
+                    {"name":"item-2","description":"description 2"},
+                    {"name":"fresh","description":"new item"}
+                ]"#,
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_json(response).await;
+        assert_eq!(body["data"]["created"], 1);
+        assert_eq!(body["data"]["updated"], 1);
+        assert_eq!(body["data"]["skipped"], 1);
+
+        let items = state.store.read().await;
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[&ItemId::from(1)].description, "changed");
+        assert!(items.values().any(|item| item.name == "fresh"));
+    }
+
+    #[tokio::test]
+    async fn import_replace_wipes_the_store_first() {
+        let state = test_state();
+        seed(&state, 3).await;
+
+        let response = send(
+            &state,
+            import_request("replace", r#"[{"name":"only","description":"one"}]"#),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_json(response).await;
+        assert_eq!(body["data"]["created"], 1);
+        assert_eq!(body["data"]["updated"], 0);
+
+        let items = state.store.read().await;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items.values().next().unwrap().name, "only");
+    }
+
+    #[tokio::test]
+    async fn import_streams_large_bodies_in_bounded_batches() {
+        let state = test_state();
+        let total = IMPORT_BATCH_SIZE * 2 + 7;
+
+        // Deliver the array in small, arbitrarily split chunks
+        let mut payload = String::from("[");
+        for i in 0..total {
+            if i > 0 {
+                payload.push(',');
+            }
+            payload.push_str(&format!(r#"{{"name":"bulk-{i}","description":"d"}}"#));
+        }
+        payload.push(']');
+        let chunks: Vec<Result<Vec<u8>, std::convert::Infallible>> = payload
+            .into_bytes()
+            .chunks(97)
+            .map(|chunk| Ok(chunk.to_vec()))
+            .collect();
+
+        let response = send(
+            &state,
+            Request::post("/items/import")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from_stream(stream::iter(chunks)))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_json(response).await;
+        assert_eq!(body["data"]["created"], total);
+        assert_eq!(body["data"]["batches"], 3);
+        assert_eq!(state.store.read().await.len(), total);
+    }
+
+    #[tokio::test]
+    async fn import_reports_the_failing_index_and_keeps_earlier_entries() {
+        let state = test_state();
+
+        let response = send(
+            &state,
+            import_request(
+                "merge",
+                r#"[
+                    {"name":"a","description":"one"},
+                    {"name":"b","description":"two"},
+                    {"name":"c"}
+                ]"#,
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = body_json(response).await;
+        assert_eq!(body["success"], false);
+        assert!(
+            body["message"].as_str().unwrap().contains("index 2"),
+            "{}",
+            body["message"]
+        );
+        assert_eq!(body["data"]["created"], 2);
+        assert_eq!(state.store.read().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn import_over_the_item_cap_is_rejected() {
+        let state = test_state_with(Config {
+            max_import_items: 2,
+            ..Config::default()
+        });
+
+        let response = send(
+            &state,
+            import_request(
+                "merge",
+                r#"[{"name":"a","description":""},{"name":"b","description":""},{"name":"c","description":""}]"#,
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let body = body_json(response).await;
+        assert!(body["message"].as_str().unwrap().contains("index 2"));
+        assert_eq!(state.store.read().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn oversized_import_entries_are_rejected_without_buffering_them() {
+        let state = test_state();
+        let huge = vec![b'x'; MAX_IMPORT_ENTRY_BYTES];
+        let chunks: Vec<Result<Vec<u8>, std::convert::Infallible>> = vec![
+            Ok(br#"[{"name":"a","description":""},{"name":"b","description":""#.to_vec()),
+            Ok(huge.clone()),
+            Ok(huge),
+        ];
+        let request = Request::post("/items/import")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from_stream(futures::stream::iter(chunks)))
+            .unwrap();
+
+        let response = send(&state, request).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            body_json(response).await["message"],
+            format!("Item at index 1 is over {MAX_IMPORT_ENTRY_BYTES} bytes")
+        );
+        assert_eq!(
+            state.store.read().await.len(),
+            1,
+            "earlier entries are kept"
+        );
+    }
+
+    #[tokio::test]
+    async fn bulk_create_rejects_duplicate_names_within_the_batch() {
+        let state = test_state();
+        seed(&state, 1).await;
+
+        let response = send(
+            &state,
+            Request::post("/items/bulk")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    r#"[
+                        {"name":"twin","description":"first"},
+                        {"name":"solo","description":"only"},
+                        {"name":"twin","description":"second"}
+                    ]"#,
+                ))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = body_json(response).await;
+        assert_eq!(body["success"], false);
+        assert_eq!(
+            body["message"],
+            r#"Duplicate names within the batch: "twin" at indices [0, 2]"#
+        );
+        assert_eq!(state.store.read().await.len(), 1, "store must be untouched");
+    }
+
+    #[tokio::test]
+    async fn get_items_filters_sorts_and_pages() {
+        let state = test_state();
+        seed(&state, 12).await;
+
+        let response = send(
+            &state,
+            Request::get("/items?q=ITEM-1&sort=name&order=desc&offset=1&limit=2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // item-1, item-10, item-11, item-12 match; descending by name
+        let body = body_json(response).await;
+        let names: Vec<&str> = body["data"]["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["item-11", "item-10"]);
+    }
+
+    #[tokio::test]
+    async fn lists_are_compact_and_single_gets_full_by_default() {
+        let state = test_state();
+        seed(&state, 3).await;
+        let get = |uri: &str| {
+            let state = state.clone();
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            async move { send(&state, request).await }
+        };
+
+        let list = body_json(get("/items").await).await;
+        let first = &list["data"]["items"][0];
+        assert_eq!(first["id"], json_id(1));
+        assert_eq!(first["name"], "item-1");
+        assert!(first.get("description").is_none(), "{first}");
+
+        let item = body_json(get("/items/1").await).await;
+        assert!(item["data"]["description"].is_string(), "{item}");
+
+        // Either endpoint takes the other view when asked
+        let full_list = body_json(get("/items?view=full").await).await;
+        assert!(full_list["data"]["items"][0]["description"].is_string());
+        let compact_item = body_json(get("/items/1?view=compact").await).await;
+        assert!(compact_item["data"].get("description").is_none());
+
+        for uri in ["/items?view=summary", "/items/1?view=everything"] {
+            assert_eq!(get(uri).await.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn streamed_lists_match_buffered_ones() {
+        let buffered = test_state();
+        let streamed = test_state_with(Config {
+            stream_list_min_items: Some(0),
+            ..Config::default()
+        });
+        seed(&buffered, 30).await;
+        seed(&streamed, 30).await;
+
+        for uri in [
+            "/items?limit=10&offset=5&view=full",
+            "/items?q=item-2&sort=name&order=desc",
+            "/items?offset=100",
+        ] {
+            let get = |state: &AppState| {
+                let (state, request) = (state.clone(), Request::get(uri).body(Body::empty()));
+                async move { send(&state, request.unwrap()).await }
+            };
+            let (expected, actual) = (get(&buffered).await, get(&streamed).await);
+            assert_eq!(actual.status(), StatusCode::OK, "{uri}");
+            assert_eq!(
+                expected.headers().get(header::LINK),
+                actual.headers().get(header::LINK),
+                "{uri}"
+            );
+            assert_eq!(body_json(actual).await, body_json(expected).await, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn get_items_streams_ndjson_when_asked() {
+        let state = test_state();
+        seed(&state, 30).await;
+
+        let response = send(
+            &state,
+            Request::get("/items?offset=5&limit=20&view=full")
+                .header(header::ACCEPT, "application/x-ndjson")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], NDJSON);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.ends_with('\n'));
+        let items: Vec<Item> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(items.len(), 20);
+        assert_eq!(items[0].id, ItemId::from(6));
+        assert_eq!(items[19].id, ItemId::from(25));
+    }
+
+    #[tokio::test]
+    async fn writes_proceed_while_a_snapshot_is_read() {
+        let state = test_state();
+        seed(&state, 1000).await;
+
+        let listing = send(
+            &state,
+            Request::get("/items?limit=1000")
+                .header(header::ACCEPT, NDJSON)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        let mut lines = listing.into_body().into_data_stream();
+        let first = lines.next().await.unwrap().unwrap();
+        assert!(first.starts_with(br#"{"id":"#));
+
+        // The listing is still open, partway through its items, yet holds
+        // no lock, so a write goes straight through rather than waiting
+        let create = send(
+            &state,
+            Request::post("/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"name":"Widget","description":""}"#))
+                .unwrap(),
+        );
+        let response = tokio::time::timeout(Duration::from_secs(1), create)
+            .await
+            .expect("the write waited for the reader");
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // And the listing carries on with the items it started with
+        let mut rest = Vec::new();
+        while let Some(chunk) = lines.next().await {
+            rest.extend_from_slice(&chunk.unwrap());
+        }
+        let listed = 1 + rest.iter().filter(|&&byte| byte == b'\n').count();
+        assert_eq!(listed, 1000);
+        assert!(!String::from_utf8(rest).unwrap().contains("Widget"));
+    }
+
+    #[tokio::test]
+    async fn create_item_requires_a_body() {
+        let state = test_state();
+        let expected =
+            "Request body is required: expected a JSON object with fields name, description";
+
+        // No body at all, not even a content type
+        let response = send(&state, Request::post("/items").body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(body_json(response).await["message"], expected);
+
+        // Explicit zero-length JSON body
+        let response = send(
+            &state,
+            Request::post("/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::CONTENT_LENGTH, "0")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = body_json(response).await;
+        assert_eq!(body["success"], false);
+        assert_eq!(body["message"], expected);
+
+        // Present but incomplete is a different error
+        let response = send(
+            &state,
+            Request::post("/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"name":"x"}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let message = body_json(response).await["message"].to_string();
+        assert!(message.contains("description"), "{message}");
+        assert!(state.store.read().await.is_empty());
+    }
+
+    fn batch_get(body: &str) -> Request<Body> {
+        Request::post("/items/batch-get")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn batch_get_partitions_found_and_missing_in_request_order() {
+        let state = test_state();
+        seed(&state, 3).await;
+
+        let response = send(&state, batch_get(r#"{"ids":[3,99,1,42,3,2]}"#)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let data = &body_json(response).await["data"];
+        let ids: Vec<_> = data["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["id"].clone())
+            .collect();
+        assert_eq!(ids, [3, 1, 2].map(json_id));
+        assert_eq!(data["items"][0]["name"], "item-3");
+        assert_eq!(
+            data["missing"],
+            serde_json::json!([json_id(99), json_id(42)])
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_get_caps_the_number_of_ids() {
+        let state = test_state_with(Config {
+            max_batch_get_ids: 2,
+            ..Config::default()
+        });
+
+        let response = send(&state, batch_get(r#"{"ids":[1,2,3]}"#)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_json(response).await["message"],
+            "At most 2 ids per request, got 3"
+        );
+    }
+
+    #[tokio::test]
+    async fn related_items_are_ranked_by_name_similarity() {
+        let state = test_state();
+        {
+            let mut items = state.store.write().await;
+            for (id, name) in [
+                (1, "Blue Widget"),
+                (2, "Garden Hose"),
+                (3, "Blue Widget Pro"),
+                (4, "Red Widget"),
+                (5, "Blue Gadget"),
+                (6, "Bloo Widgit"),
+            ] {
+                items.insert(id.into(), ItemBuilder::new(id).name(name).build().into());
+            }
+        }
+
+        let response = send(
+            &state,
+            Request::get("/items/1/related?limit=4")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let names: Vec<String> = body_json(response).await["data"]["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Blue Widget Pro",
+                "Blue Gadget",
+                "Red Widget",
+                "Bloo Widgit"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn related_items_need_an_existing_item() {
+        let state = test_state();
+        let related = |id: u32| {
+            Request::get(format!("/items/{id}/related"))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = send(&state, related(1)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        seed(&state, 1).await;
+        let response = send(&state, related(1)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(response).await["data"]["items"],
+            serde_json::json!([])
+        );
+    }
+
+    #[tokio::test]
+    async fn modified_since_returns_only_items_changed_from_then_on() {
+        let state = test_state();
+        {
+            let mut items = state.store.write().await;
+            for id in 1..=3 {
+                items.insert(
+                    id.into(),
+                    ItemBuilder::new(id).created_at(1_000).build().into(),
+                );
+            }
+            items.insert(
+                ItemId::from(4),
+                ItemBuilder::new(4)
+                    .created_at(1_000)
+                    .updated_at(2_000)
+                    .build()
+                    .into(),
+            );
+        }
+        // Merging an import bumps item-2 to the current time
+        let response = send(
+            &state,
+            import_request("merge", r#"[{"name":"item-2","description":"changed"}]"#),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let list = |query: &str| {
+            Request::get(format!("/items?{query}"))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let ids = |body: serde_json::Value| -> Vec<serde_json::Value> {
+            body["data"]["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|item| item["id"].clone())
+                .collect()
+        };
+
+        // 2000 seconds after the epoch, inclusive
+        let response = send(&state, list("modified_since=1970-01-01T00:33:20Z")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(ids(body_json(response).await), [2, 4].map(json_id));
+
+        let response = send(&state, list("modified_since=1970-01-01T01:33:20%2B01:00")).await;
+        assert_eq!(
+            ids(body_json(response).await),
+            [2, 4].map(json_id),
+            "offsets are honoured"
+        );
+
+        let response = send(&state, list("modified_since=yesterday")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn list_endpoints_share_the_pagination_shape() {
+        let state = test_state();
+        {
+            let mut items = state.store.write().await;
+            for id in 1..=6 {
+                items.insert(
+                    id.into(),
+                    ItemBuilder::new(id)
+                        .name(format!("Widget {id}"))
+                        .build()
+                        .into(),
+                );
+            }
+        }
+
+        let mut shapes = Vec::new();
+        for uri in [
+            "/items?limit=2&offset=1",
+            "/items/1/related?offset=1&limit=2",
+        ] {
+            let response = send(&state, Request::get(uri).body(Body::empty()).unwrap()).await;
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+            let link = response.headers()[header::LINK]
+                .to_str()
+                .unwrap()
+                .to_string();
+            assert!(
+                link.ends_with(r#"limit=2&offset=3>; rel="next""#),
+                "{uri}: {link}"
+            );
+
+            let page = body_json(response).await["data"].take();
+            assert_eq!(page["items"].as_array().unwrap().len(), 2, "{uri}");
+            assert_eq!(page["offset"], 1, "{uri}");
+            assert_eq!(page["limit"], 2, "{uri}");
+            let mut keys: Vec<String> = page.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            shapes.push(keys);
+        }
+        assert_eq!(shapes[0], shapes[1]);
+
+        // The related page leaves out item 1 itself
+        let page = |uri: &str| {
+            let state = state.clone();
+            let uri = uri.to_string();
+            async move {
+                let response = send(&state, Request::get(uri).body(Body::empty()).unwrap()).await;
+                assert!(response.headers().get(header::LINK).is_none());
+                body_json(response).await["data"].take()
+            }
+        };
+        assert_eq!(page("/items?offset=4").await["total"], 6);
+        assert_eq!(page("/items/1/related?offset=4").await["total"], 5);
+    }
+
+    #[tokio::test]
+    async fn unbounded_listings_are_capped_at_the_default_limit() {
+        let state = test_state_with(Config {
+            default_list_limit: 5,
+            ..Config::default()
+        });
+        seed(&state, 12).await;
+        let list = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let response = send(&state, list("/items")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::LINK],
+            r#"</items?offset=5>; rel="next""#
+        );
+        let page = body_json(response).await["data"].take();
+        assert_eq!(page["items"].as_array().unwrap().len(), 5);
+        assert_eq!(page["total"], 12);
+        assert_eq!(page["truncated"], true);
+
+        // Following the hint picks up where the first page stopped
+        let page = body_json(send(&state, list("/items?offset=10")).await).await["data"].take();
+        assert_eq!(page["items"][0]["id"], json_id(11));
+        assert_eq!(page["truncated"], false, "nothing left to fetch");
+
+        // An explicit limit above the default is honoured
+        let page = body_json(send(&state, list("/items?limit=20")).await).await["data"].take();
+        assert_eq!(page["items"].as_array().unwrap().len(), 12);
+        assert_eq!(page["truncated"], false);
+    }
+
+    #[tokio::test]
+    async fn best_effort_bulk_create_reports_each_item() {
+        let state = test_state();
+        seed(&state, 1).await;
+
+        let response = send(
+            &state,
+            Request::post("/items/bulk?mode=best_effort")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    r#"[
+                        {"name":"first","description":"ok"},
+                        {"name":"nameless"},
+                        {"name":"third","description":"ok"},
+                        {"name":"first","description":"again"}
+                    ]"#,
+                ))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+
+        let body = body_json(response).await;
+        assert_eq!(body["success"], false);
+        assert_eq!(body["message"], "2 of 4 items created");
+        let results = body["data"].as_array().unwrap();
+        let statuses: Vec<u64> = results
+            .iter()
+            .map(|r| r["status"].as_u64().unwrap())
+            .collect();
+        assert_eq!(statuses, [201, 422, 201, 422]);
+        assert_eq!(results[0]["id"], json_id(2));
+        assert_eq!(results[2]["id"], json_id(3));
+        assert!(results[1]["error"]
+            .as_str()
+            .unwrap()
+            .contains("missing field `description`"));
+        assert!(results[1].get("id").is_none());
+        assert_eq!(
+            results[3]["error"],
+            r#"Duplicate name within the batch: "first""#
+        );
+
+        let items = state.store.read().await;
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[&ItemId::from(3)].name, "third");
+    }
+
+    fn put(id: &str, body: &str) -> Request<Body> {
+        Request::put(format!("/items/{id}"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn put_creates_at_the_client_chosen_id() {
+        let state = test_state();
+        seed(&state, 2).await;
+
+        let response = send(
+            &state,
+            put("42", r#"{"name":"answer","description":"new"}"#),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[header::LOCATION], "/items/42");
+        assert_eq!(body_json(response).await["data"]["id"], json_id(42));
+        assert_eq!(state.store.read().await[&ItemId::from(42)].name, "answer");
+
+        // Server-assigned ids continue past the client's
+        let response = send(
+            &state,
+            Request::post("/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"name":"next","description":""}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(body_json(response).await["data"]["id"], json_id(43));
+
+        let response = send(&state, put("0", r#"{"name":"zero","description":""}"#)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = send(&state, put("abc", r#"{"name":"abc","description":""}"#)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn put_replaces_an_existing_item() {
+        let state = test_state();
+        state.store.write().await.insert(
+            ItemId::from(7),
+            ItemBuilder::new(7).created_at(1_000).build().into(),
+        );
+
+        let response = send(&state, put("7", r#"{"name":"renamed","description":"v2"}"#)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::LOCATION).is_none());
+        assert_eq!(
+            body_json(response).await["message"],
+            "Item replaced successfully"
+        );
+
+        let items = state.store.read().await;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[&ItemId::from(7)].name, "renamed");
+        assert_eq!(items[&ItemId::from(7)].description, "v2");
+        assert_eq!(items[&ItemId::from(7)].created_at, 1_000);
+        assert!(items[&ItemId::from(7)].updated_at > 1_000);
+    }
+
+    #[tokio::test]
+    async fn offsets_past_the_end_give_an_empty_page() {
+        let state = test_state();
+        seed(&state, 3).await;
+
+        let response = send(
+            &state,
+            Request::get("/items?offset=999999")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::LINK).is_none());
+        let page = body_json(response).await["data"].take();
+        assert_eq!(page["items"], serde_json::json!([]));
+        assert_eq!(page["total"], 3);
+        assert_eq!(page["offset"], 999999);
+        assert_eq!(page["truncated"], false);
+    }
+
+    #[tokio::test]
+    async fn offsets_beyond_the_maximum_are_rejected() {
+        let state = test_state();
+        seed(&state, 3).await;
+
+        for uri in [
+            "/items?offset=10000000000",
+            "/items/1/related?offset=10000000000",
+        ] {
+            let response = send(&state, Request::get(uri).body(Body::empty()).unwrap()).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+            assert_eq!(
+                body_json(response).await["message"],
+                "offset must be at most 1000000, got 10000000000"
+            );
+        }
+    }
+
+    fn create(name: &str) -> Request<Body> {
+        Request::post("/items")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(format!(
+                r#"{{"name":"{name}","description":""}}"#
+            )))
+            .unwrap()
+    }
+
+    fn patch(body: &str, headers: &[(&str, &str)]) -> Request<Body> {
+        let mut request =
+            Request::patch("/items/1").header(header::CONTENT_TYPE, "application/merge-patch+json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(Body::from(body.to_string())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn diffs_attribute_each_change_to_its_version() {
+        let state = test_state();
+        seed(&state, 1).await;
+        let original = state.store.read().await[&ItemId::from(1)].clone();
+
+        // Version 2 renames, version 3 describes
+        for body in [r#"{"name":"renamed"}"#, r#"{"description":"described"}"#] {
+            assert_eq!(
+                send(&state, patch(body, &[])).await.status(),
+                StatusCode::OK
+            );
+        }
+
+        let diff = |from: u32, to: u32| {
+            let state = state.clone();
+            async move {
+                let uri = format!("/items/1/diff?from={from}&to={to}");
+                send(&state, Request::get(uri).body(Body::empty()).unwrap()).await
+            }
+        };
+        let changes = |from: u32, to: u32| {
+            let diff = diff(from, to);
+            async move {
+                let response = diff.await;
+                assert_eq!(response.status(), StatusCode::OK, "{from}..{to}");
+                body_json(response).await["data"]["changes"].clone()
+            }
+        };
+
+        assert_eq!(
+            changes(1, 2).await,
+            serde_json::json!([{"field": "name", "from": original.name, "to": "renamed"}])
+        );
+        assert_eq!(
+            changes(2, 3).await,
+            serde_json::json!([
+                {"field": "description", "from": original.description, "to": "described"}
+            ])
+        );
+        assert_eq!(changes(1, 3).await.as_array().unwrap().len(), 2);
+        assert_eq!(changes(3, 3).await, serde_json::json!([]));
+
+        let response = diff(1, 4).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            body_json(response).await["message"],
+            "Item 1 has no version 4"
+        );
+        assert_eq!(diff(0, 1).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn stats_aggregate_the_live_items() {
+        let state = test_state_with(Config {
+            item_ttl: Some(Duration::from_secs(3600)),
+            ..Config::default()
+        });
+        let now = state.expiry.now_secs();
+        let stats = || async {
+            let response = send(
+                &state,
+                Request::get("/items/stats").body(Body::empty()).unwrap(),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+            body_json(response).await["data"].clone()
+        };
+        assert_eq!(
+            stats().await,
+            serde_json::to_value(ItemStats::default()).unwrap()
+        );
+
+        {
+            let mut items = state.store.write().await;
+            for item in [
+                ItemBuilder::new(1).description("").created_at(now - 30),
+                ItemBuilder::new(2)
+                    .description("héllo")
+                    .created_at(now - 20)
+                    .updated_at(now - 5),
+                ItemBuilder::new(3)
+                    .description("abcdefghij")
+                    .created_at(now - 10),
+                // Expired, so left out
+                ItemBuilder::new(4)
+                    .description("x".repeat(50))
+                    .created_at(now - 7200),
+            ] {
+                let item = item.build();
+                items.insert(item.id.clone(), item.into());
+            }
+        }
+
+        let expected = ItemStats {
+            total: 3,
+            by_state: [("modified", 1), ("unmodified", 2)].into(),
+            avg_description_len: 5.0,
+            max_description_len: 10,
+            oldest_created_at: Some(now - 30),
+            newest_created_at: Some(now - 10),
+        };
+        assert_eq!(stats().await, serde_json::to_value(expected).unwrap());
+    }
+
+    #[tokio::test]
+    async fn patch_retries_on_conflict_only_when_asked() {
+        let state = test_state();
+        seed(&state, 1).await;
+
+        // Two clients read the same version...
+        let fetched = send(
+            &state,
+            Request::get("/items/1").body(Body::empty()).unwrap(),
+        )
+        .await;
+        let etag = fetched.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+        // ...and the first renames the item
+        let response = send(
+            &state,
+            patch(r#"{"name":"renamed"}"#, &[("if-match", &etag)]),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[header::ETAG], etag.as_str());
+
+        // The second's tag is now stale
+        let described = r#"{"description":"patched"}"#;
+        let response = send(&state, patch(described, &[("if-match", &etag)])).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        // Asked to, the server re-applies the change to the latest version
+        let response = send(
+            &state,
+            patch(
+                described,
+                &[("if-match", &etag), ("retry-on-conflict", "2")],
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let new_etag = response.headers()[header::ETAG].clone();
+        let item = &body_json(response).await["data"];
+        assert_eq!(item["name"], "renamed", "the first client's change is kept");
+        assert_eq!(item["description"], "patched");
+
+        let fetched = send(
+            &state,
+            Request::get("/items/1").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(fetched.headers()[header::ETAG], new_etag);
+    }
+
+    #[tokio::test]
+    async fn descriptions_over_the_limit_are_rejected_on_create_and_patch() {
+        let state = test_state_with(Config {
+            max_description_len: 5,
+            ..Config::default()
+        });
+        let create = |description: &str| {
+            Request::post("/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(format!(
+                    r#"{{"name":"item","description":"{description}"}}"#
+                )))
+                .unwrap()
+        };
+
+        // Counted in characters, not bytes
+        let response = send(&state, create("ééééé")).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = send(&state, create("sixsix")).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body_json(response).await["message"],
+            "description is 6 characters long, the limit is 5"
+        );
+
+        let response = send(&state, patch(r#"{"description":"toolong"}"#, &[])).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body_json(response).await["message"],
+            "description is 7 characters long, the limit is 5"
+        );
+        // A patch leaving the description alone isn't checked against it
+        let response = send(&state, patch(r#"{"name":"renamed"}"#, &[])).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            state.store.read().await[&ItemId::from(1)].description,
+            "ééééé"
+        );
+    }
+
+    #[tokio::test]
+    async fn descriptions_over_the_limit_are_rejected_in_bulk_and_imports() {
+        let state = test_state_with(Config {
+            max_description_len: 5,
+            ..Config::default()
+        });
+        let post = |uri: &str, body: &str| {
+            Request::post(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let batch = r#"[{"name":"a","description":"fine"},{"name":"b","description":"sixsix"}]"#;
+
+        let response = send(&state, post("/items/bulk", batch)).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body_json(response).await["message"],
+            "Item at index 1 is invalid: description is 6 characters long, the limit is 5"
+        );
+        assert!(state.store.read().await.is_empty(), "all or nothing");
+
+        let response = send(&state, post("/items/bulk?mode=best_effort", batch)).await;
+        let results = &body_json(response).await["data"];
+        assert_eq!(results[0]["status"], 201);
+        assert_eq!(results[1]["status"], 422);
+        assert_eq!(
+            results[1]["error"],
+            "description is 6 characters long, the limit is 5"
+        );
+
+        let response = send(&state, post("/items/import", batch)).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body_json(response).await["message"],
+            "Item at index 1 is invalid: description is 6 characters long, the limit is 5"
+        );
+        assert!(state
+            .store
+            .read()
+            .await
+            .values()
+            .all(|item| item.description.chars().count() <= 5));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn concurrent_creates_with_a_unique_name_admit_one() {
+        let state = test_state_with(Config {
+            unique_name: true,
+            ..Config::default()
+        });
+
+        for round in 0..20 {
+            let name = format!("contested-{round}");
+            let (a, b) = tokio::join!(
+                tokio::spawn({
+                    let (state, request) = (state.clone(), create(&name));
+                    async move { send(&state, request).await.status() }
+                }),
+                tokio::spawn({
+                    let (state, request) = (state.clone(), create(&name));
+                    async move { send(&state, request).await.status() }
+                }),
+            );
+            let mut statuses = [a.unwrap(), b.unwrap()];
+            statuses.sort();
+            assert_eq!(statuses, [StatusCode::CREATED, StatusCode::CONFLICT]);
+        }
+        assert_eq!(state.store.read().await.len(), 20);
+    }
+
+    #[tokio::test]
+    async fn unique_names_apply_to_updates_and_bulk_creates() {
+        let state = test_state_with(Config {
+            unique_name: true,
+            ..Config::default()
+        });
+        seed(&state, 2).await;
+
+        let response = send(&state, put("2", r#"{"name":"item-1","description":""}"#)).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(
+            body_json(response).await["message"],
+            r#"An item named "item-1" already exists"#
+        );
+        // Keeping its own name is not a conflict
+        let response = send(&state, put("2", r#"{"name":"item-2","description":"v2"}"#)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bulk = |query: &str| {
+            Request::post(format!("/items/bulk{query}"))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    r#"[{"name":"new","description":""},{"name":"item-2","description":""}]"#,
+                ))
+                .unwrap()
+        };
+        assert_eq!(send(&state, bulk("")).await.status(), StatusCode::CONFLICT);
+        assert_eq!(state.store.read().await.len(), 2, "atomic: nothing created");
+
+        let results = body_json(send(&state, bulk("?mode=best_effort")).await).await;
+        assert_eq!(results["data"][0]["status"], 201);
+        assert_eq!(results["data"][1]["status"], 409);
+    }
+
+    #[tokio::test]
+    async fn duplicate_names_are_allowed_by_default() {
+        let state = test_state();
+
+        for _ in 0..2 {
+            assert_eq!(
+                send(&state, create("twin")).await.status(),
+                StatusCode::CREATED
+            );
+        }
+    }
+}
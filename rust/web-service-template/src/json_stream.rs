@@ -0,0 +1,208 @@
+/// Incrementally splits a JSON array arriving in arbitrary chunks into the
+/// raw bytes of its top-level elements, so each element can be deserialized
+/// as soon as it is complete instead of buffering the whole document.
+///
+/// Only structure is tracked (nesting depth and string literals); the
+/// elements themselves are validated when they are deserialized. An element
+/// is buffered up to `max_element_bytes` and rejected beyond, so memory stays
+/// bounded however large a single element is.
+pub struct ArraySplitter {
+    max_element_bytes: usize,
+    /// Elements completed so far, which is the index of the one in progress.
+    completed: usize,
+    position: Position,
+    element: Vec<u8>,
+    depth: usize,
+    /// Whether a top-level comma has been seen, which makes `[,]` an error
+    /// while `[]` is a valid empty array.
+    separated: bool,
+    in_string: bool,
+    escaped: bool,
+}
+
+#[derive(Default, PartialEq, Eq)]
+enum Position {
+    #[default]
+    Before,
+    Inside,
+    After,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SplitError {
+    NotAnArray,
+    EmptyElement,
+    TrailingData,
+    Unterminated,
+    /// The element at `index` is longer than `max` bytes.
+    ElementTooLarge {
+        index: usize,
+        max: usize,
+    },
+}
+
+impl std::fmt::Display for SplitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::NotAnArray => "body must be a JSON array",
+            Self::EmptyElement => "array contains an empty element",
+            Self::TrailingData => "unexpected data after the closing bracket",
+            Self::Unterminated => "body ended before the array was closed",
+            Self::ElementTooLarge { index, max } => {
+                return write!(f, "element at index {index} is over {max} bytes")
+            }
+        })
+    }
+}
+
+impl ArraySplitter {
+    pub fn new(max_element_bytes: usize) -> Self {
+        Self {
+            max_element_bytes,
+            completed: 0,
+            position: Position::Before,
+            element: Vec::new(),
+            depth: 0,
+            separated: false,
+            in_string: false,
+            escaped: false,
+        }
+    }
+
+    /// Feeds the next chunk, appending every element it completes to `out`.
+    pub fn push(&mut self, chunk: &[u8], out: &mut Vec<Vec<u8>>) -> Result<(), SplitError> {
+        for &byte in chunk {
+            match self.position {
+                Position::Before => match byte {
+                    b'[' => self.position = Position::Inside,
+                    b if b.is_ascii_whitespace() => {}
+                    _ => return Err(SplitError::NotAnArray),
+                },
+                Position::After if byte.is_ascii_whitespace() => {}
+                Position::After => return Err(SplitError::TrailingData),
+                Position::Inside => {
+                    self.push_in_array(byte, out)?;
+                    if self.element.len() > self.max_element_bytes {
+                        return Err(SplitError::ElementTooLarge {
+                            index: self.completed,
+                            max: self.max_element_bytes,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the body ended with a complete array.
+    pub fn finish(&self) -> Result<(), SplitError> {
+        match self.position {
+            Position::After => Ok(()),
+            Position::Before => Err(SplitError::NotAnArray),
+            Position::Inside => Err(SplitError::Unterminated),
+        }
+    }
+
+    fn push_in_array(&mut self, byte: u8, out: &mut Vec<Vec<u8>>) -> Result<(), SplitError> {
+        if self.in_string {
+            self.element.push(byte);
+            match byte {
+                _ if self.escaped => self.escaped = false,
+                b'\\' => self.escaped = true,
+                b'"' => self.in_string = false,
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        match byte {
+            b',' | b']' if self.depth == 0 => {
+                let last = byte == b']';
+                if !self.element.is_empty() {
+                    out.push(std::mem::take(&mut self.element));
+                    self.completed += 1;
+                } else if !last || self.separated {
+                    return Err(SplitError::EmptyElement);
+                }
+                if last {
+                    self.position = Position::After;
+                } else {
+                    self.separated = true;
+                }
+            }
+            b if b.is_ascii_whitespace() && self.element.is_empty() => {}
+            b'"' => {
+                self.in_string = true;
+                self.element.push(byte);
+            }
+            b'{' | b'[' => {
+                self.depth += 1;
+                self.element.push(byte);
+            }
+            b'}' | b']' => {
+                // A stray closer leaves the element malformed; deserializing
+                // it reports the error.
+                self.depth = self.depth.saturating_sub(1);
+                self.element.push(byte);
+            }
+            _ => self.element.push(byte),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_in_chunks(input: &str, chunk_size: usize) -> Result<Vec<String>, SplitError> {
+        let mut splitter = ArraySplitter::new(usize::MAX);
+        let mut out = Vec::new();
+        for chunk in input.as_bytes().chunks(chunk_size) {
+            splitter.push(chunk, &mut out)?;
+        }
+        splitter.finish()?;
+        Ok(out
+            .into_iter()
+            .map(|raw| String::from_utf8(raw).unwrap())
+            .collect())
+    }
+
+    #[test]
+    fn splits_elements_regardless_of_chunk_boundaries() {
+        let input = r#" [ {"a":"x,]}\"y"}, [1, [2]] ,3 ] "#;
+        for chunk_size in [1, 2, 7, input.len()] {
+            assert_eq!(
+                split_in_chunks(input, chunk_size).unwrap(),
+                [r#"{"a":"x,]}\"y"}"#, "[1, [2]] ", "3 "],
+                "chunk size {chunk_size}"
+            );
+        }
+        assert!(split_in_chunks("[]", 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_arrays() {
+        assert_eq!(
+            split_in_chunks(r#"{"a":1}"#, 3),
+            Err(SplitError::NotAnArray)
+        );
+        assert_eq!(split_in_chunks("[1,,2]", 3), Err(SplitError::EmptyElement));
+        assert_eq!(split_in_chunks("[1,]", 3), Err(SplitError::EmptyElement));
+        assert_eq!(split_in_chunks("[1] 2", 3), Err(SplitError::TrailingData));
+        assert_eq!(split_in_chunks("[1, 2", 3), Err(SplitError::Unterminated));
+        assert_eq!(split_in_chunks("", 3), Err(SplitError::NotAnArray));
+    }
+
+    #[test]
+    fn oversized_elements_are_rejected_without_buffering_them() {
+        let mut splitter = ArraySplitter::new(8);
+        let mut out = Vec::new();
+        splitter.push(br#"["short", "#, &mut out).unwrap();
+        let error = splitter.push(&[b'x'; 1024], &mut out).unwrap_err();
+        assert_eq!(error, SplitError::ElementTooLarge { index: 1, max: 8 });
+        assert_eq!(error.to_string(), "element at index 1 is over 8 bytes");
+        assert!(splitter.element.len() <= 9);
+        assert_eq!(out.len(), 1);
+    }
+}
@@ -0,0 +1,640 @@
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRef, FromRequestParts, Query},
+    http::{header, request::Parts, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Json, Response},
+};
+use futures::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::config::Config;
+use crate::items::Item;
+use crate::ApiResponse;
+
+/// Largest page a list endpoint will return in one response.
+pub const MAX_LIST_LIMIT: usize = 1000;
+
+/// Validated `offset` and `limit`, shared by every paginated endpoint so they
+/// all accept and reject the same values. A missing `limit` means "no limit"
+/// unless the endpoint supplies a default with [`Pagination::or_limit`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Pagination {
+    pub offset: usize,
+    pub limit: Option<usize>,
+    /// `limit` is the endpoint's default rather than the client's.
+    defaulted: bool,
+}
+
+impl Pagination {
+    /// Parses `offset` and `limit`, rejecting offsets above `max_offset`.
+    fn from_params(params: &HashMap<String, String>, max_offset: usize) -> Result<Self, Rejection> {
+        let mut pagination = Self::default();
+
+        if let Some(raw) = params.get("offset") {
+            let trimmed = raw.trim();
+            let (negative, digits) = match trimmed.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, trimmed),
+            };
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid(format!("offset must be an integer, got {raw:?}")));
+            }
+            if negative {
+                return Err(invalid(format!("offset must not be negative, got {raw}")));
+            }
+            // Digits that don't fit a usize are past any maximum
+            pagination.offset = match digits.parse::<usize>() {
+                Ok(offset) if offset <= max_offset => offset,
+                _ => {
+                    return Err(invalid(format!(
+                        "offset must be at most {max_offset}, got {raw}"
+                    )))
+                }
+            };
+        }
+
+        if let Some(raw) = params.get("limit") {
+            pagination.limit = match raw.trim().parse::<i64>() {
+                Ok(limit) if (1..=MAX_LIST_LIMIT as i64).contains(&limit) => Some(limit as usize),
+                Ok(_) => {
+                    return Err(invalid(format!(
+                        "limit must be between 1 and {MAX_LIST_LIMIT}, got {raw}"
+                    )))
+                }
+                Err(_) => return Err(invalid(format!("limit must be an integer, got {raw:?}"))),
+            };
+        }
+
+        Ok(pagination)
+    }
+
+    /// Uses `default` when the request didn't give a limit.
+    pub fn or_limit(self, default: usize) -> Self {
+        match self.limit {
+            Some(_) => self,
+            None => Self {
+                limit: Some(default),
+                defaulted: true,
+                ..self
+            },
+        }
+    }
+
+    /// Pages `items`, which must already be filtered and sorted. Every item
+    /// is walked to fill in [`Page::total`], so the cost depends on the
+    /// number of items, never on the offset; an offset past the end gives an
+    /// empty page.
+    pub fn page<T>(&self, items: impl IntoIterator<Item = T>) -> Page<T> {
+        let mut total = 0;
+        let mut page = Vec::new();
+        for item in items {
+            if total >= self.offset && page.len() < self.limit.unwrap_or(usize::MAX) {
+                page.push(item);
+            }
+            total += 1;
+        }
+
+        let end = self.offset + page.len();
+        Page {
+            items: page,
+            total: Some(total),
+            offset: self.offset,
+            limit: self.limit,
+            next_offset: (end < total).then_some(end),
+            truncated: self.defaulted && end < total,
+        }
+    }
+}
+
+/// One page of a list endpoint's results; the `data` of every paginated
+/// response.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "camel-case-api", serde(rename_all = "camelCase"))]
+pub(crate) struct Page<T> {
+    pub items: Vec<T>,
+    /// Results across all pages, or `None` from endpoints that can't count
+    /// them without doing the work of fetching every page.
+    pub total: Option<usize>,
+    pub offset: usize,
+    /// The limit applied, or `None` if the page is unbounded.
+    pub limit: Option<usize>,
+    /// Offset of the following page, or `None` on the last one.
+    pub next_offset: Option<usize>,
+    /// The request gave no `limit` and the endpoint's default cut the
+    /// results short; the rest start at `next_offset`.
+    pub truncated: bool,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            offset: self.offset,
+            limit: self.limit,
+            next_offset: self.next_offset,
+            truncated: self.truncated,
+        }
+    }
+}
+
+impl<T> Page<T> {
+    /// `Link: <...>; rel="next"` pointing at the following page of `uri`, if
+    /// there is one.
+    fn link(&self, uri: &Uri) -> Option<HeaderValue> {
+        self.next_offset
+            .and_then(|offset| HeaderValue::try_from(next_link(uri, offset)).ok())
+    }
+
+    /// Like [`Page::into_response`], but writes the `items` array one element
+    /// at a time from `elements`, each already serialized, as the client
+    /// reads. Only the element being written is held in memory; the page's
+    /// own `items` are ignored.
+    pub fn into_streamed_response(
+        self,
+        uri: &Uri,
+        message: impl Into<String>,
+        elements: impl Stream<Item = Vec<u8>> + Send + 'static,
+    ) -> Response {
+        let link = self.link(uri);
+        let mut page = self.map(|_| ());
+        page.items.clear();
+        let mut prefix = serde_json::to_vec(&ApiResponse {
+            success: true,
+            data: Some(page),
+            message: message.into(),
+        })
+        .expect("a page always serializes");
+        // `items` is the first field of the page, so the first `[` in the
+        // envelope opens it; the elements go between it and its `]`
+        let open = prefix
+            .iter()
+            .position(|&b| b == b'[')
+            .expect("the page has an items array")
+            + 1;
+        let suffix = prefix.split_off(open);
+
+        let elements = elements.enumerate().map(|(index, mut element)| {
+            if index > 0 {
+                element.insert(0, b',');
+            }
+            element
+        });
+        let body = stream::once(async { prefix })
+            .chain(elements)
+            .chain(stream::once(async { suffix }))
+            .map(Ok::<_, std::convert::Infallible>);
+
+        let mut response = (
+            [(header::CONTENT_TYPE, "application/json")],
+            Body::from_stream(body),
+        )
+            .into_response();
+        if let Some(link) = link {
+            response.headers_mut().insert(header::LINK, link);
+        }
+        response
+    }
+}
+
+impl<T: Serialize> Page<T> {
+    /// Wraps the page in an [`ApiResponse`], adding a `Link: <...>; rel="next"`
+    /// header pointing at the following page of `uri` if there is one.
+    pub fn into_response(self, uri: &Uri, message: impl Into<String>) -> Response {
+        let link = self.link(uri);
+        let mut response = Json(ApiResponse {
+            success: true,
+            data: Some(self),
+            message: message.into(),
+        })
+        .into_response();
+        if let Some(link) = link {
+            response.headers_mut().insert(header::LINK, link);
+        }
+        response
+    }
+}
+
+/// `uri` with its `offset` parameter replaced by `offset`, as a Link header
+/// value. Other parameters are kept as sent.
+fn next_link(uri: &Uri, offset: usize) -> String {
+    let mut params: Vec<&str> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty() && pair.split('=').next() != Some("offset"))
+        .collect();
+    let offset = format!("offset={offset}");
+    params.push(&offset);
+    format!("<{}?{}>; rel=\"next\"", uri.path(), params.join("&"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Id,
+    Name,
+    CreatedAt,
+}
+
+/// How much of each item a response carries, chosen with `view`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    /// Just `id` and `name`.
+    Compact,
+    /// Every field.
+    Full,
+}
+
+impl View {
+    /// Parses `view`, leaving the choice to the endpoint when it's absent.
+    fn from_params(params: &HashMap<String, String>) -> Result<Option<Self>, Rejection> {
+        params
+            .get("view")
+            .map(|raw| match raw.as_str() {
+                "compact" => Ok(Self::Compact),
+                "full" => Ok(Self::Full),
+                other => Err(invalid(format!(
+                    "view must be compact or full, got {other:?}"
+                ))),
+            })
+            .transpose()
+    }
+}
+
+/// The `view` a request asked for, for endpoints that take no other list
+/// parameters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct RequestedView(pub Option<View>);
+
+impl RequestedView {
+    /// Uses `default` when the request didn't give a view.
+    pub fn or(self, default: View) -> View {
+        self.0.unwrap_or(default)
+    }
+}
+
+/// Validated list parameters for item listings: [`Pagination`], `sort` (a
+/// comma-separated list of `id`, `name` or `created_at`, later keys breaking
+/// ties in earlier ones), `order` (`asc` or `desc`) and `q`, a
+/// case-insensitive substring match on name and description.
+/// `modified_since`, an RFC 3339 timestamp, keeps items updated at or after
+/// it, at whole-second precision; `modified`, a boolean, keeps only items
+/// that have or haven't changed since they were created. `view` picks a
+/// [`View`], left to the endpoint's default when absent. Unknown parameters
+/// are ignored; invalid values are rejected with 400 before the handler runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ListQuery {
+    pub pagination: Pagination,
+    /// Empty when sorting by id alone.
+    pub sort: Vec<SortKey>,
+    pub descending: bool,
+    pub q: Option<String>,
+    /// Unix seconds; items with an earlier `updated_at` are left out.
+    pub modified_since: Option<u64>,
+    pub modified: Option<bool>,
+    pub view: Option<View>,
+}
+
+type Rejection = (StatusCode, Json<ApiResponse<()>>);
+
+fn invalid(message: String) -> Rejection {
+    (StatusCode::BAD_REQUEST, ApiResponse::error(message))
+}
+
+/// Parses boolean parameter `name`, accepting `true`/`false`, `1`/`0` and
+/// `yes`/`no` in any case.
+fn bool_param(params: &HashMap<String, String>, name: &str) -> Result<Option<bool>, Rejection> {
+    params
+        .get(name)
+        .map(|raw| match raw.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" => Ok(true),
+            "false" | "0" | "no" => Ok(false),
+            _ => Err(invalid(format!(
+                "{name} must be one of true, false, 1, 0, yes, no, got {raw:?}"
+            ))),
+        })
+        .transpose()
+}
+
+/// Splits comma-separated parameter `name` into its trimmed entries, empty
+/// when it's absent. Empty entries, as in `a,,b`, are rejected.
+fn list_param<'a>(
+    params: &'a HashMap<String, String>,
+    name: &str,
+) -> Result<Vec<&'a str>, Rejection> {
+    let Some(raw) = params.get(name) else {
+        return Ok(Vec::new());
+    };
+    let entries: Vec<&str> = raw.split(',').map(str::trim).collect();
+    if entries.iter().any(|entry| entry.is_empty()) {
+        return Err(invalid(format!(
+            "{name} must be a comma-separated list without empty entries, got {raw:?}"
+        )));
+    }
+    Ok(entries)
+}
+
+impl ListQuery {
+    fn from_params(params: &HashMap<String, String>, max_offset: usize) -> Result<Self, Rejection> {
+        let mut query = Self {
+            pagination: Pagination::from_params(params, max_offset)?,
+            ..Self::default()
+        };
+
+        for key in list_param(params, "sort")? {
+            query.sort.push(match key {
+                "id" => SortKey::Id,
+                "name" => SortKey::Name,
+                "created_at" => SortKey::CreatedAt,
+                other => {
+                    return Err(invalid(format!(
+                        "sort must be one of id, name, created_at, got {other:?}"
+                    )))
+                }
+            });
+        }
+
+        if let Some(raw) = params.get("order") {
+            query.descending = match raw.as_str() {
+                "asc" => false,
+                "desc" => true,
+                other => return Err(invalid(format!("order must be asc or desc, got {other:?}"))),
+            };
+        }
+
+        query.q = params
+            .get("q")
+            .map(|q| q.trim().to_lowercase())
+            .filter(|q| !q.is_empty());
+
+        if let Some(raw) = params.get("modified_since") {
+            let since = OffsetDateTime::parse(raw.trim(), &Rfc3339).map_err(|_| {
+                invalid(format!(
+                    "modified_since must be an RFC 3339 timestamp, got {raw:?}"
+                ))
+            })?;
+            query.modified_since = Some(since.unix_timestamp().max(0) as u64);
+        }
+        query.modified = bool_param(params, "modified")?;
+
+        query.view = View::from_params(params)?;
+
+        Ok(query)
+    }
+
+    pub fn matches(&self, item: &Item) -> bool {
+        let text_matches = self.q.as_ref().is_none_or(|q| {
+            item.name.to_lowercase().contains(q) || item.description.to_lowercase().contains(q)
+        });
+        text_matches
+            && self
+                .modified_since
+                .is_none_or(|since| item.updated_at >= since)
+            && self
+                .modified
+                .is_none_or(|modified| item.is_modified() == modified)
+    }
+
+    /// Filters, sorts and pages `items`.
+    pub fn select<'a>(&self, items: impl IntoIterator<Item = &'a Item>) -> Page<&'a Item> {
+        let mut items: Vec<&Item> = items
+            .into_iter()
+            .filter(|item| self.matches(item))
+            .collect();
+
+        // Ties left by every key fall back to the id
+        items.sort_by(|a, b| {
+            self.sort
+                .iter()
+                .map(|key| match key {
+                    SortKey::Id => a.id.cmp(&b.id),
+                    SortKey::Name => a.name.cmp(&b.name),
+                    SortKey::CreatedAt => a.created_at.cmp(&b.created_at),
+                })
+                .fold(Ordering::Equal, Ordering::then)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        if self.descending {
+            items.reverse();
+        }
+
+        self.pagination.page(items)
+    }
+}
+
+async fn query_params(parts: &mut Parts) -> Result<HashMap<String, String>, Rejection> {
+    let Query(params) = Query::<HashMap<String, String>>::from_request_parts(parts, &())
+        .await
+        .map_err(|err| invalid(err.body_text()))?;
+    Ok(params)
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Pagination
+where
+    S: Send + Sync,
+    Arc<Config>: FromRef<S>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = Arc::<Config>::from_ref(state);
+        Self::from_params(&query_params(parts).await?, config.max_list_offset)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ListQuery
+where
+    S: Send + Sync,
+    Arc<Config>: FromRef<S>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = Arc::<Config>::from_ref(state);
+        Self::from_params(&query_params(parts).await?, config.max_list_offset)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestedView
+where
+    S: Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        View::from_params(&query_params(parts).await?).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::ItemId;
+    use crate::items::ItemBuilder;
+    use axum::http::Request;
+
+    async fn extract(query: &str) -> Result<ListQuery, (StatusCode, String)> {
+        let (mut parts, ()) = Request::get(format!("/items?{query}"))
+            .body(())
+            .unwrap()
+            .into_parts();
+        let config = Arc::new(Config {
+            max_list_offset: 10_000,
+            ..Config::default()
+        });
+        ListQuery::from_request_parts(&mut parts, &config)
+            .await
+            .map_err(|(status, Json(body))| (status, body.message))
+    }
+
+    #[tokio::test]
+    async fn parses_and_normalizes_list_parameters() {
+        assert_eq!(extract("").await.unwrap(), ListQuery::default());
+        assert_eq!(
+            extract("offset=5&limit=10&sort=name&order=desc&q=%20Widget%20&other=x")
+                .await
+                .unwrap(),
+            ListQuery {
+                pagination: Pagination {
+                    offset: 5,
+                    limit: Some(10),
+                    ..Pagination::default()
+                },
+                sort: vec![SortKey::Name],
+                descending: true,
+                q: Some("widget".to_string()),
+                modified_since: None,
+                modified: None,
+                view: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn accepts_common_boolean_spellings() {
+        for (raw, expected) in [
+            ("true", true),
+            ("TRUE", true),
+            ("1", true),
+            ("Yes", true),
+            ("false", false),
+            ("0", false),
+            ("no", false),
+        ] {
+            assert_eq!(
+                extract(&format!("modified={raw}")).await.unwrap().modified,
+                Some(expected),
+                "{raw}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn sorts_by_each_listed_key_in_turn() {
+        let query = extract("sort=created_at,%20name").await.unwrap();
+        assert_eq!(query.sort, [SortKey::CreatedAt, SortKey::Name]);
+
+        let items = [(1, "b", 20), (2, "a", 20), (3, "c", 10), (4, "a", 20)].map(
+            |(id, name, created_at)| {
+                ItemBuilder::new(id)
+                    .name(name)
+                    .created_at(created_at)
+                    .build()
+            },
+        );
+        let ids: Vec<&ItemId> = query
+            .select(&items)
+            .items
+            .iter()
+            .map(|item| &item.id)
+            .collect();
+        // Ties in both keys fall back to the id
+        assert_eq!(
+            ids,
+            [3, 2, 4, 1].map(ItemId::from).iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_parameters_with_400() {
+        for (query, expected) in [
+            ("offset=-1", "offset must not be negative, got -1"),
+            ("offset=ten", r#"offset must be an integer, got "ten""#),
+            ("offset=10001", "offset must be at most 10000, got 10001"),
+            (
+                "offset=10000000000000000000000",
+                "offset must be at most 10000, got 10000000000000000000000",
+            ),
+            ("limit=0", "limit must be between 1 and 1000, got 0"),
+            ("limit=1001", "limit must be between 1 and 1000, got 1001"),
+            (
+                "sort=price",
+                r#"sort must be one of id, name, created_at, got "price""#,
+            ),
+            (
+                "sort=name,,id",
+                r#"sort must be a comma-separated list without empty entries, got "name,,id""#,
+            ),
+            ("order=up", r#"order must be asc or desc, got "up""#),
+            (
+                "modified=maybe",
+                r#"modified must be one of true, false, 1, 0, yes, no, got "maybe""#,
+            ),
+            (
+                "view=summary",
+                r#"view must be compact or full, got "summary""#,
+            ),
+        ] {
+            assert_eq!(
+                extract(query).await,
+                Err((StatusCode::BAD_REQUEST, expected.to_string())),
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn pages_report_the_total_and_the_next_offset() {
+        let pagination = Pagination {
+            offset: 2,
+            limit: Some(3),
+            ..Pagination::default()
+        };
+        let page = pagination.page(1..=7);
+        assert_eq!(page.items, [3, 4, 5]);
+        assert_eq!(page.total, Some(7));
+        assert_eq!(page.next_offset, Some(5));
+        assert!(!page.truncated, "the client asked for this limit");
+
+        let last = Pagination {
+            offset: 5,
+            ..pagination
+        }
+        .page(1..=7);
+        assert_eq!(last.items, [6, 7]);
+        assert_eq!(last.next_offset, None);
+    }
+
+    #[test]
+    fn next_link_replaces_only_the_offset() {
+        let uri: Uri = "/items?q=blue%20widget&offset=10&limit=5".parse().unwrap();
+        assert_eq!(
+            next_link(&uri, 15),
+            r#"</items?q=blue%20widget&limit=5&offset=15>; rel="next""#
+        );
+        let uri: Uri = "/items/1/related".parse().unwrap();
+        assert_eq!(
+            next_link(&uri, 5),
+            r#"</items/1/related?offset=5>; rel="next""#
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{telemetry, ApiResponse};
+
+/// Number of recent request latencies used to estimate p99.
+const LATENCY_WINDOW: usize = 200;
+
+/// Upper bound on the shed rate so some traffic always gets through and
+/// latency samples keep flowing, which is what lets the shedder recover.
+const MAX_SHED_PERMILLE: u32 = 950;
+
+/// Adaptive load shedder.
+///
+/// Tracks the p99 of recent request latencies and, when it exceeds the
+/// configured budget, rejects a proportional share of new requests: a p99 at
+/// twice the budget sheds roughly half. As faster samples replace slow ones
+/// the shed rate falls back to zero.
+pub struct LoadShedder {
+    budget: Duration,
+    retry_after_secs: u64,
+    samples: Mutex<VecDeque<Duration>>,
+    shed_permille: AtomicU32,
+}
+
+impl LoadShedder {
+    pub fn new(budget: Duration, retry_after_secs: u64) -> Self {
+        Self {
+            budget,
+            retry_after_secs,
+            samples: Mutex::new(VecDeque::with_capacity(LATENCY_WINDOW)),
+            shed_permille: AtomicU32::new(0),
+        }
+    }
+
+    /// Current fraction of requests being shed, in `0.0..=1.0`.
+    pub fn shed_rate(&self) -> f64 {
+        f64::from(self.shed_permille.load(Ordering::Relaxed)) / 1000.0
+    }
+
+    /// Records a completed request's latency and recomputes the shed rate.
+    pub fn observe(&self, latency: Duration) {
+        let p99 = {
+            let mut samples = self.samples.lock().unwrap();
+            if samples.len() == LATENCY_WINDOW {
+                samples.pop_front();
+            }
+            samples.push_back(latency);
+
+            let mut sorted: Vec<Duration> = samples.iter().copied().collect();
+            sorted.sort_unstable();
+            sorted[(sorted.len() * 99 / 100).min(sorted.len() - 1)]
+        };
+
+        let permille = if p99 > self.budget {
+            let over = 1.0 - self.budget.as_secs_f64() / p99.as_secs_f64();
+            ((over * 1000.0) as u32).min(MAX_SHED_PERMILLE)
+        } else {
+            0
+        };
+
+        self.shed_permille.store(permille, Ordering::Relaxed);
+        telemetry::record(|| metrics::gauge!("http_load_shed_rate").set(self.shed_rate()));
+    }
+
+    fn should_shed(&self) -> bool {
+        let permille = self.shed_permille.load(Ordering::Relaxed);
+        permille > 0 && rand::random::<u32>() % 1000 < permille
+    }
+}
+
+/// Middleware rejecting requests with 503 while the shedder is active and
+/// feeding the latency of admitted requests back into it.
+pub async fn load_shed(
+    State(shedder): State<Arc<LoadShedder>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if shedder.should_shed() {
+        telemetry::record(|| metrics::counter!("http_requests_shed_total").increment(1));
+
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, shedder.retry_after_secs.to_string())],
+            ApiResponse::error("Service is overloaded, please retry later"),
+        )
+            .into_response();
+    }
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    shedder.observe(started.elapsed());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shed_rate_rises_under_high_latency_and_recovers() {
+        let shedder = LoadShedder::new(Duration::from_millis(100), 1);
+
+        for _ in 0..LATENCY_WINDOW {
+            shedder.observe(Duration::from_millis(20));
+        }
+        assert_eq!(shedder.shed_rate(), 0.0);
+
+        for _ in 0..LATENCY_WINDOW {
+            shedder.observe(Duration::from_millis(400));
+        }
+        let overloaded = shedder.shed_rate();
+        assert!(overloaded > 0.5, "shed rate was {overloaded}");
+
+        for _ in 0..LATENCY_WINDOW {
+            shedder.observe(Duration::from_millis(20));
+        }
+        assert_eq!(shedder.shed_rate(), 0.0);
+    }
+}
@@ -0,0 +1,159 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::telemetry;
+
+/// Initializes tracing, writing to `log_file` (`LOG_FILE`) when set and to
+/// stdout otherwise.
+pub fn init(log_file: Option<PathBuf>) {
+    match log_file {
+        Some(path) => tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(FallbackLog::open(&path))
+            .init(),
+        None => tracing_subscriber::fmt::init(),
+    }
+}
+
+/// Log writer that appends to a file and degrades to stderr if the file
+/// can't be opened or a write fails (disk full, permissions revoked).
+///
+/// Logging must never take the service down: failures are reported once on
+/// stderr, counted in `log_lines_dropped_total`, and every later line goes
+/// straight to stderr.
+#[derive(Clone)]
+pub struct FallbackLog {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+    warned: AtomicBool,
+    dropped: AtomicU64,
+}
+
+impl FallbackLog {
+    pub fn open(path: &Path) -> Self {
+        let inner = Inner {
+            path: path.to_path_buf(),
+            file: Mutex::new(None),
+            warned: AtomicBool::new(false),
+            dropped: AtomicU64::new(0),
+        };
+
+        match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => *inner.file.lock().unwrap() = Some(file),
+            Err(e) => inner.degrade(&e),
+        }
+
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+
+    /// Number of lines that could not be written to the log file.
+    #[cfg(test)]
+    pub fn dropped_lines(&self) -> u64 {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Inner {
+    fn degrade(&self, error: &io::Error) {
+        if !self.warned.swap(true, Ordering::Relaxed) {
+            // Can't log through tracing from inside its own writer
+            eprintln!(
+                "WARN log file {} is unwritable ({}); logging to stderr instead",
+                self.path.display(),
+                error
+            );
+        }
+    }
+
+    fn write_line(&self, buf: &[u8]) {
+        let mut file = self.file.lock().unwrap();
+
+        if let Some(f) = file.as_mut() {
+            match f.write_all(buf) {
+                Ok(()) => return,
+                Err(e) => {
+                    *file = None;
+                    self.degrade(&e);
+                }
+            }
+        }
+
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        telemetry::record(|| metrics::counter!("log_lines_dropped_total").increment(1));
+        let _ = io::stderr().write_all(buf);
+    }
+}
+
+impl Write for FallbackLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write_line(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for FallbackLog {
+    type Writer = FallbackLog;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{send, test_state};
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+
+    async fn serves_while_logging_to(log: FallbackLog) {
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(log.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        for _ in 0..3 {
+            tracing::info!("request incoming");
+            let response = send(
+                &test_state(),
+                Request::get("/items").body(Body::empty()).unwrap(),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        // Each request logged the line above plus its access log line
+        assert_eq!(log.dropped_lines(), 6);
+    }
+
+    #[tokio::test]
+    async fn keeps_serving_when_log_file_cannot_be_opened() {
+        let dir = std::env::temp_dir().join(format!("missing-log-dir-{}", std::process::id()));
+        serves_while_logging_to(FallbackLog::open(&dir.join("service.log"))).await;
+    }
+
+    // `/dev/full` accepts the open but fails every write with ENOSPC, which
+    // mimics a disk filling up under a running service.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn keeps_serving_when_log_writes_fail() {
+        serves_while_logging_to(FallbackLog::open(Path::new("/dev/full"))).await;
+    }
+}
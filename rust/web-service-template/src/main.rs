@@ -1,125 +1,990 @@
+mod admin;
+mod body_log;
+mod capacity;
+mod changes;
+mod clock;
+mod config;
+mod content_type;
+mod deadline;
+mod dedupe;
+mod error;
+mod expiry;
+mod extract;
+mod health;
+mod history;
+mod ids;
+mod ingest;
+mod items;
+mod json_stream;
+mod list_query;
+mod load_shed;
+mod logging;
+mod maintenance;
+mod persist;
+mod priority;
+mod quota;
+mod readiness;
+mod redact;
+mod registry;
+mod report;
+mod retry_budget;
+mod sampling;
+mod shutdown;
+mod similarity;
+mod stack;
+mod telemetry;
+#[cfg(test)]
+mod test_support;
+#[cfg(feature = "tls")]
+mod tls;
+mod uri_limit;
+mod versioning;
+mod worker;
+
 use axum::{
-    extract::Path,
-    http::StatusCode,
+    extract::{FromRef, Request},
+    http::{Method, StatusCode, Uri},
+    middleware,
     response::Json,
-    routing::{get},
-    Router,
+    routing::{get, post},
+    Router, ServiceExt,
 };
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
-use tower_http::cors::CorsLayer;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tower::Layer;
+use tower_http::normalize_path::{NormalizePath, NormalizePathLayer};
 use tracing::info;
 
-#[derive(Serialize, Deserialize, Clone)]
-struct Item {
-    id: u32,
-    name: String,
-    description: String,
-}
+use capacity::Capacity;
+use changes::ChangeFeed;
+use clock::{Clock, SystemClock};
+use config::{Config, RunMode};
+use dedupe::CreateDedupe;
+use expiry::{Expiry, PurgeWorker};
+use health::{HealthRegistry, StoreCheck};
+use history::ItemHistory;
+use ids::IdGenerator;
+use ingest::EventBuffer;
+use items::ItemStore;
+use load_shed::LoadShedder;
+use maintenance::Maintenance;
+use priority::PriorityLimiter;
+use quota::QuotaTracker;
+use readiness::InFlight;
+use report::RunStats;
+use retry_budget::RetryBudget;
+use telemetry::MetricsHandle;
+use versioning::ApiVersioning;
+use worker::{StoreReportWorker, Worker};
 
+// Wire field names are camelCase (behind the default `camel-case-api`
+// feature) while the Rust side stays snake_case.
 #[derive(Serialize)]
-struct ApiResponse<T> {
-    success: bool,
-    data: Option<T>,
-    message: String,
+#[cfg_attr(feature = "camel-case-api", serde(rename_all = "camelCase"))]
+pub(crate) struct ApiResponse<T> {
+    pub(crate) success: bool,
+    pub(crate) data: Option<T>,
+    pub(crate) message: String,
+}
+
+impl ApiResponse<()> {
+    /// Failure envelope with no payload.
+    pub(crate) fn error(message: impl Into<String>) -> Json<Self> {
+        Json(Self {
+            success: false,
+            data: None,
+            message: message.into(),
+        })
+    }
+}
+
+/// Shared application state handed to every handler.
+#[derive(Clone)]
+struct AppState {
+    config: Arc<Config>,
+    store: ItemStore,
+    ids: Arc<dyn IdGenerator>,
+    history: Arc<ItemHistory>,
+    capacity: Arc<Capacity>,
+    changes: Arc<ChangeFeed>,
+    load_shedder: Option<Arc<LoadShedder>>,
+    in_flight: Arc<InFlight>,
+    limiter: Option<Arc<PriorityLimiter>>,
+    quota: Option<Arc<QuotaTracker>>,
+    metrics: Option<MetricsHandle>,
+    health: Arc<HealthRegistry>,
+    maintenance: Maintenance,
+    expiry: Expiry,
+    dedupe: Arc<CreateDedupe>,
+    events: Arc<EventBuffer>,
+    versioning: Arc<ApiVersioning>,
+    stats: Arc<RunStats>,
+    /// Shared by everything that retries, so retries are capped service-wide.
+    retry_budget: Arc<RetryBudget>,
+    /// Cancelled once graceful shutdown begins; doubles as the draining flag.
+    shutdown: CancellationToken,
+    /// Background tasks shutdown waits for; spawn anything that buffers data
+    /// (flushers, dispatchers) here rather than with `tokio::spawn`.
+    tasks: TaskTracker,
+}
+
+impl AppState {
+    fn new(config: Config, metrics: Option<MetricsHandle>) -> Self {
+        Self::with_clock(config, metrics, Arc::new(SystemClock))
+    }
+
+    /// Like [`AppState::new`], with every wall-clock decision (timestamps,
+    /// TTL expiry) read from `clock`.
+    fn with_clock(config: Config, metrics: Option<MetricsHandle>, clock: Arc<dyn Clock>) -> Self {
+        let store = ItemStore::default();
+        let history = Arc::new(ItemHistory::new(config.item_history_limit));
+        let changes = Arc::new(ChangeFeed::new(
+            config.sse_max_subscribers,
+            config.sse_max_lag,
+        ));
+
+        let mut health = HealthRegistry::new(config.health_check_timeout, config.health_cache_ttl);
+        health.register(StoreCheck::new(store.clone()));
+
+        Self {
+            store,
+            ids: ids::generator(config.id_scheme),
+            history: history.clone(),
+            capacity: Arc::new(Capacity::new(&config, history, changes.clone())),
+            changes,
+            load_shedder: config.load_shed_latency_budget.map(|budget| {
+                Arc::new(LoadShedder::new(budget, config.load_shed_retry_after_secs))
+            }),
+            in_flight: Arc::new(InFlight::new(
+                config.ready_high_water,
+                config.ready_low_water,
+            )),
+            limiter: config.max_concurrent_requests.map(PriorityLimiter::new),
+            quota: config.daily_quota.map(|limit| {
+                Arc::new(QuotaTracker::new(
+                    limit,
+                    config.quota_reset_hour,
+                    config.quota_max_keys,
+                    clock.clone(),
+                ))
+            }),
+            metrics,
+            health: Arc::new(health),
+            maintenance: Maintenance::new(config.maintenance_mode),
+            expiry: Expiry::new(clock, config.item_ttl),
+            dedupe: Arc::new(CreateDedupe::new(
+                config.create_dedupe_window,
+                config.create_dedupe_key,
+            )),
+            events: Arc::new(EventBuffer::new(config.ingest_buffer_events)),
+            versioning: Arc::new(ApiVersioning::new(&config)),
+            stats: Arc::new(RunStats::default()),
+            retry_budget: Arc::new(RetryBudget::new(
+                config.retry_budget,
+                config.retry_budget_window,
+            )),
+            shutdown: CancellationToken::new(),
+            tasks: TaskTracker::new(),
+            config: Arc::new(config),
+        }
+    }
+}
+
+impl FromRef<AppState> for Arc<Config> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for ItemStore {
+    fn from_ref(state: &AppState) -> Self {
+        state.store.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn IdGenerator> {
+    fn from_ref(state: &AppState) -> Self {
+        state.ids.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ItemHistory> {
+    fn from_ref(state: &AppState) -> Self {
+        state.history.clone()
+    }
 }
 
-#[derive(Deserialize)]
-struct CreateItemRequest {
-    name: String,
-    description: String,
+impl FromRef<AppState> for Arc<Capacity> {
+    fn from_ref(state: &AppState) -> Self {
+        state.capacity.clone()
+    }
 }
 
-// In-memory storage for demo purposes
-type ItemStore = std::sync::Arc<tokio::sync::RwLock<HashMap<u32, Item>>>;
+impl FromRef<AppState> for Expiry {
+    fn from_ref(state: &AppState) -> Self {
+        state.expiry.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<CreateDedupe> {
+    fn from_ref(state: &AppState) -> Self {
+        state.dedupe.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<EventBuffer> {
+    fn from_ref(state: &AppState) -> Self {
+        state.events.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<MetricsHandle> {
+    fn from_ref(state: &AppState) -> Self {
+        state.metrics.clone()
+    }
+}
 
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
-
-    // Create in-memory store
-    let store = ItemStore::default();
-
-    // Build our application with routes
-    let app = Router::new()
-        .route("/", get(health_check))
-        .route("/health", get(health_check))
-        .route("/items", get(get_items).post(create_item))
-        .route("/items/:id", get(get_item))
-        .layer(CorsLayer::permissive())
-        .with_state(store);
-
-    let listener = TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    
-    info!("Server running on http://0.0.0.0:3000");
+    // Initialize tracing first so configuration warnings are visible
+    logging::init(std::env::var_os("LOG_FILE").map(Into::into));
+
+    let config = Config::from_env();
+    if let Err(errors) = config.validate() {
+        tracing::error!("{}", errors);
+        // EX_CONFIG from sysexits.h
+        std::process::exit(78);
+    }
+    let state = AppState::new(config.clone(), telemetry::install_recorder());
+    if let Some(path) = &config.snapshot_file {
+        match persist::restore(&state.store, state.ids.as_ref(), path).await {
+            Ok(Some(count)) => info!("Restored {} items from {}", count, path.display()),
+            Ok(None) => info!("No snapshot at {} yet, starting empty", path.display()),
+            // Starting empty would overwrite the snapshot on shutdown
+            Err(e) => {
+                tracing::error!("{}: {}", e, e.cause());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let tls = load_tls(&config);
+
+    let listener = TcpListener::bind(&config.bind_addr).await.unwrap();
+
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    info!("Server running on {}://{}", scheme, config.bind_addr);
     info!("Available endpoints:");
     info!("  GET  /         - Health check");
     info!("  GET  /health   - Health check");
-    info!("  GET  /items    - Get all items");
+    info!("  GET  /healthz/deep - Per-subsystem health checks");
+    info!("  GET  /readyz   - Readiness (503 when overloaded, draining or in maintenance)");
+    info!("  GET  /metrics  - Prometheus metrics");
+    info!(
+        "  GET  /items    - List items (?offset, limit, sort, order, q, modified_since, modified)"
+    );
     info!("  POST /items    - Create new item");
+    info!("  POST /items/batch-get - Get many items by id");
+    info!("  POST /items/bulk - Create several items at once");
+    info!("  GET  /items/export - Stream all items as NDJSON");
+    info!("  POST /items/import - Import items (?mode=merge|replace)");
+    info!("  GET  /items/stats - Aggregate item statistics");
+    info!("  GET  /items/changes - Item changes as server-sent events");
     info!("  GET  /items/:id - Get item by ID");
+    info!("  PUT  /items/:id - Create or replace the item at this ID");
+    info!("  PATCH /items/:id - Update some fields of an item");
+    info!("  GET  /items/:id/related - Items with similar names (?limit)");
+    info!("  GET  /items/:id/diff - Fields changed between two versions (?from, to)");
+    info!("  POST /ingest   - Ingest NDJSON events");
+    if config.admin_enabled {
+        info!("  GET  /admin/config - Effective configuration (secrets redacted)");
+        info!("  GET/PUT /admin/maintenance - Read or toggle (needs ADMIN_TOKEN) maintenance mode");
+        info!("  POST /admin/shutdown - Start graceful shutdown (needs ADMIN_TOKEN)");
+    }
+
+    let mut workers: Vec<(Arc<dyn Worker>, Duration)> = Vec::new();
+    if config.run_mode == RunMode::Combined {
+        info!(
+            "Combined mode: background worker runs every {:?}",
+            config.worker_interval
+        );
+        workers.push((
+            Arc::new(StoreReportWorker::new(state.store.clone())),
+            config.worker_interval,
+        ));
+    }
+    if let Some(ttl) = config.item_ttl {
+        info!(
+            "Items expire after {:?}, purged every {:?}",
+            ttl, config.item_purge_interval
+        );
+        workers.push((
+            Arc::new(PurgeWorker::new(
+                state.store.clone(),
+                state.expiry.clone(),
+                state.history.clone(),
+                state.changes.clone(),
+            )),
+            config.item_purge_interval,
+        ));
+    }
+
+    state.tasks.spawn(shutdown::listen_for_signals(
+        state.shutdown.clone(),
+        state.stats.clone(),
+    ));
+
+    if let Some(registrar) = registry::from_config(&config) {
+        let bind_addr = listener.local_addr().unwrap();
+        state.tasks.spawn(registry::run(
+            registrar,
+            registry::Registration::new(&config.service_name, bind_addr, scheme),
+            state.retry_budget.clone(),
+            state.shutdown.clone(),
+        ));
+    }
+
+    #[cfg(feature = "tls")]
+    if let Some(certs) = &tls {
+        state
+            .tasks
+            .spawn(tls::reload_on_sighup(certs.clone(), state.shutdown.clone()));
+    }
+
+    serve(listener, state, workers, tls).await.unwrap();
+}
+
+/// Certificates to serve HTTPS with.
+#[cfg(feature = "tls")]
+type Tls = Arc<tls::CertReloader>;
+
+/// Without the `tls` feature `Config::validate` rejects the TLS settings, so
+/// there is never anything to serve HTTPS with.
+#[cfg(not(feature = "tls"))]
+type Tls = std::convert::Infallible;
+
+/// Loads the configured certificate pair, exiting if it can't be used.
+#[cfg(feature = "tls")]
+fn load_tls(config: &Config) -> Option<Tls> {
+    let (cert_file, key_file) = config
+        .tls_cert_file
+        .as_ref()
+        .zip(config.tls_key_file.as_ref())?;
+    let policy = tls::TlsPolicy::new(&config.tls_min_version, &config.tls_ciphers)
+        .expect("Config::validate checked TLS_MIN_VERSION and TLS_CIPHERS");
+    match tls::CertReloader::load(cert_file, key_file, policy) {
+        Ok(certs) => Some(Arc::new(certs)),
+        Err(e) => {
+            tracing::error!("Failed to load TLS certificate: {}", e);
+            std::process::exit(78);
+        }
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+fn load_tls(_config: &Config) -> Option<Tls> {
+    None
+}
+
+/// Serves HTTP, or HTTPS when `tls` is given, until the state's shutdown
+/// token is cancelled, running each background worker alongside at its own
+/// period. All of them stop together on the same shutdown signal, and every
+/// task in the state's tracker gets `task_drain_timeout` to finish before
+/// this returns. The store is then saved to `SNAPSHOT_FILE`, if set and
+/// `PERSIST_ON_SHUTDOWN` is on; a save that fails or outlasts
+/// `SNAPSHOT_SAVE_TIMEOUT_MS` is logged and shutdown carries on.
+async fn serve(
+    listener: TcpListener,
+    state: AppState,
+    workers: Vec<(Arc<dyn Worker>, Duration)>,
+    tls: Option<Tls>,
+) -> std::io::Result<()> {
+    let shutdown = state.shutdown.clone();
+    let stats = state.stats.clone();
+    let report_file = state.config.shutdown_report_file.clone();
+    let drain_timeout = state.config.task_drain_timeout;
+    let snapshot_file = state.config.snapshot_file.clone();
+    let persist_on_shutdown = state.config.persist_on_shutdown;
+    let save_timeout = state.config.snapshot_save_timeout;
+    let (store, expiry) = (state.store.clone(), state.expiry.clone());
+    let tasks = state.tasks.clone();
+    for (worker, period) in workers {
+        tasks.spawn(worker::run(worker, period, shutdown.clone()));
+    }
+
+    let result = match tls {
+        #[cfg(feature = "tls")]
+        Some(certs) => tls::serve(listener, certs.acceptor(), app(state), shutdown.clone()).await,
+        #[cfg(not(feature = "tls"))]
+        Some(never) => match never {},
+        None => {
+            axum::serve(
+                listener,
+                ServiceExt::<Request>::into_make_service(app(state)),
+            )
+            .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+            .await
+        }
+    };
+
+    // Stop the workers too if the server exited on its own
+    shutdown.cancel();
+    shutdown::drain_tasks(&tasks, drain_timeout).await;
+
+    if let Some(path) = snapshot_file.as_ref().filter(|_| persist_on_shutdown) {
+        match tokio::time::timeout(save_timeout, persist::save(&store, &expiry, path)).await {
+            Ok(Ok(count)) => info!("Saved {} items to {}", count, path.display()),
+            Ok(Err(e)) => tracing::warn!("{}: {}", e, e.cause()),
+            Err(_) => tracing::warn!(
+                "Snapshot to {} not written after {:?}, exiting without it",
+                path.display(),
+                save_timeout
+            ),
+        }
+    }
+    report::emit(&stats.report(), report_file.as_deref());
+    info!("Server shutdown complete");
+    result
+}
+
+/// Request timeouts of the routes `REQUEST_TIMEOUT_MS` doesn't suit, by
+/// route template; `ROUTE_TIMEOUTS_MS` overrides them. An import reads its
+/// whole body within the timeout, while probes should fail fast rather than
+/// hang a load balancer's check. Exports stream past any timeout once they
+/// start, so theirs only bounds taking the snapshot.
+const ROUTE_TIMEOUTS: [(&str, Duration); 4] = [
+    ("/items/import", Duration::from_secs(300)),
+    ("/items/export", Duration::from_secs(120)),
+    ("/health", Duration::from_secs(2)),
+    ("/readyz", Duration::from_secs(2)),
+];
+
+/// Builds the service. Trailing slashes are trimmed before routing, so
+/// `/items/` is served exactly like `/items`; `/` itself is left alone.
+fn app(state: AppState) -> NormalizePath<Router> {
+    let mut api = Router::new()
+        .route("/items", get(items::get_items).post(items::create_item))
+        .route("/items/batch-get", post(items::batch_get_items))
+        .route("/items/bulk", post(items::bulk_create_items))
+        .route("/items/export", get(items::export_items))
+        .route("/items/import", post(items::import_items))
+        .route("/items/stats", get(items::item_stats))
+        .route("/items/changes", get(changes::item_changes))
+        .route(
+            "/items/:id",
+            get(items::get_item)
+                .put(items::put_item)
+                .patch(items::patch_item),
+        )
+        .route("/items/:id/related", get(items::get_related_items))
+        .route("/items/:id/diff", get(items::diff_item))
+        .route("/ingest", post(ingest::ingest_events))
+        .route_layer(middleware::from_fn(content_type::require_content_type));
+
+    // Only API routes count towards load-based readiness, so probes don't
+    // hold an overloaded instance out of rotation.
+    api = api.route_layer(middleware::from_fn_with_state(
+        state.in_flight.clone(),
+        readiness::track_in_flight,
+    ));
+
+    // Only API routes are shed; health and metrics must stay reachable so the
+    // instance isn't marked dead while it is merely busy.
+    if let Some(shedder) = state.load_shedder.clone() {
+        api = api.route_layer(middleware::from_fn_with_state(
+            shedder,
+            load_shed::load_shed,
+        ));
+    }
 
-    axum::serve(listener, app).await.unwrap();
-}
-
-async fn health_check() -> Json<ApiResponse<String>> {
-    Json(ApiResponse {
-        success: true,
-        data: Some("Web service is running".to_string()),
-        message: "OK".to_string(),
-    })
-}
-
-async fn get_items(
-    axum::extract::State(store): axum::extract::State<ItemStore>,
-) -> Json<ApiResponse<Vec<Item>>> {
-    let items = store.read().await;
-    let items_vec: Vec<Item> = items.values().cloned().collect();
-    
-    Json(ApiResponse {
-        success: true,
-        data: Some(items_vec),
-        message: "Items retrieved successfully".to_string(),
-    })
-}
-
-async fn get_item(
-    Path(id): Path<u32>,
-    axum::extract::State(store): axum::extract::State<ItemStore>,
-) -> Result<Json<ApiResponse<Item>>, StatusCode> {
-    let items = store.read().await;
-    
-    if let Some(item) = items.get(&id) {
-        Ok(Json(ApiResponse {
-            success: true,
-            data: Some(item.clone()),
-            message: "Item found".to_string(),
-        }))
-    } else {
-        Err(StatusCode::NOT_FOUND)
-    }
-}
-
-async fn create_item(
-    axum::extract::State(store): axum::extract::State<ItemStore>,
-    Json(payload): Json<CreateItemRequest>,
-) -> Result<Json<ApiResponse<Item>>, StatusCode> {
-    let mut items = store.write().await;
-    
-    let id = items.len() as u32 + 1;
-    let item = Item {
-        id,
-        name: payload.name,
-        description: payload.description,
+    let mut router = Router::new()
+        .route("/", get(health::health_check))
+        .route("/health", get(health::health_check))
+        .route("/healthz/deep", get(health::deep_health))
+        .route("/readyz", get(readiness::readyz))
+        .route("/metrics", get(telemetry::metrics_handler))
+        .merge(api);
+
+    if state.config.admin_enabled {
+        router = router.nest("/admin", admin::router(&state));
+    }
+
+    let router = router
+        .fallback(not_found)
+        .layer(stack::build_middleware(&state))
+        .with_state(state);
+
+    NormalizePathLayer::trim_trailing_slash().layer(router)
+}
+
+/// Keeps unknown routes inside the usual response envelope instead of an
+/// empty 404.
+async fn not_found(method: Method, uri: Uri) -> (StatusCode, Json<ApiResponse<()>>) {
+    (
+        StatusCode::NOT_FOUND,
+        ApiResponse::error(format!("Route not found: {method} {}", uri.path())),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{body_json, json_id, seed, send, test_state, test_state_with};
+    use axum::{
+        body::Body,
+        http::{header, Request},
     };
-    
-    items.insert(id, item.clone());
-    
-    Ok(Json(ApiResponse {
-        success: true,
-        data: Some(item),
-        message: "Item created successfully".to_string(),
-    }))
+
+    struct TickProbe(tokio::sync::mpsc::Sender<u64>);
+
+    #[async_trait::async_trait]
+    impl Worker for TickProbe {
+        async fn perform_work(&self, iteration: u64) -> Result<worker::Outcome, worker::WorkError> {
+            let _ = self.0.try_send(iteration);
+            Ok(worker::Outcome::Worked)
+        }
+    }
+
+    #[tokio::test]
+    async fn combined_mode_serves_http_and_ticks() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::time::timeout;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tick_tx, mut tick_rx) = tokio::sync::mpsc::channel(8);
+        let worker: Arc<dyn Worker> = Arc::new(TickProbe(tick_tx));
+        let state = test_state();
+        let shutdown = state.shutdown.clone();
+
+        let server = tokio::spawn(serve(
+            listener,
+            state,
+            vec![(worker, Duration::from_millis(20))],
+            None,
+        ));
+
+        let mut conn = tokio::net::TcpStream::connect(addr).await.unwrap();
+        conn.write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut raw = String::new();
+        conn.read_to_string(&mut raw).await.unwrap();
+        assert!(
+            raw.starts_with("HTTP/1.1 200"),
+            "unexpected response: {raw}"
+        );
+
+        let tick = timeout(Duration::from_secs(1), tick_rx.recv())
+            .await
+            .unwrap();
+        assert_eq!(tick, Some(1));
+
+        shutdown.cancel();
+        timeout(Duration::from_secs(1), server)
+            .await
+            .expect("server and worker should stop together")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_tracked_tasks_to_flush() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let state = test_state();
+        let shutdown = state.shutdown.clone();
+        let flushed = Arc::new(AtomicBool::new(false));
+        // Buffers until shutdown, then takes a while to write the buffer out
+        state.tasks.spawn({
+            let (shutdown, flushed) = (shutdown.clone(), flushed.clone());
+            async move {
+                shutdown.cancelled().await;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                flushed.store(true, Ordering::SeqCst);
+            }
+        });
+
+        let server = tokio::spawn(serve(listener, state, Vec::new(), None));
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+        assert!(flushed.load(Ordering::SeqCst), "returned before the flush");
+    }
+
+    #[tokio::test]
+    async fn items_survive_a_restart_through_the_snapshot() {
+        let path = std::env::temp_dir().join(format!("snapshot-{}.json", std::process::id()));
+        let config = Config {
+            snapshot_file: Some(path.clone()),
+            ..Config::default()
+        };
+        let state = test_state_with(config.clone());
+        for name in ["kept", "also kept"] {
+            let create = Request::post("/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(format!(
+                    r#"{{"name":"{name}","description":"d"}}"#
+                )))
+                .unwrap();
+            assert_eq!(send(&state, create).await.status(), StatusCode::CREATED);
+        }
+        let before = state.store.read().await.clone();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let shutdown = state.shutdown.clone();
+        let server = tokio::spawn(serve(listener, state, Vec::new(), None));
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+
+        let restarted = test_state_with(config);
+        let restored = persist::restore(&restarted.store, restarted.ids.as_ref(), &path).await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(restored.unwrap(), Some(2));
+        assert_eq!(*restarted.store.read().await, before);
+
+        // New ids continue after the restored ones
+        let create = Request::post("/items")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"name":"new","description":""}"#))
+            .unwrap();
+        let created = body_json(send(&restarted, create).await).await;
+        assert_eq!(created["data"]["id"], json_id(3));
+    }
+
+    #[tokio::test]
+    async fn a_failed_snapshot_save_does_not_block_shutdown() {
+        let state = test_state_with(Config {
+            snapshot_file: Some(std::env::temp_dir().join("no-such-dir/snapshot.json")),
+            ..Config::default()
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let shutdown = state.shutdown.clone();
+        let server = tokio::spawn(serve(listener, state, Vec::new(), None));
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn nothing_is_saved_unless_persisting_on_shutdown() {
+        let path = std::env::temp_dir().join(format!("unsaved-{}.json", std::process::id()));
+        let state = test_state_with(Config {
+            snapshot_file: Some(path.clone()),
+            persist_on_shutdown: false,
+            ..Config::default()
+        });
+        seed(&state, 2).await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let shutdown = state.shutdown.clone();
+        let server = tokio::spawn(serve(listener, state, Vec::new(), None));
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn a_stuck_snapshot_save_is_abandoned_at_the_deadline() {
+        let path = std::env::temp_dir().join(format!("stuck-{}.json", std::process::id()));
+        let partial = path.with_extension("json.partial");
+        // Writing to a FIFO nobody reads blocks forever
+        let made = std::process::Command::new("mkfifo")
+            .arg(&partial)
+            .status()
+            .unwrap();
+        assert!(made.success());
+        let state = test_state_with(Config {
+            snapshot_file: Some(path.clone()),
+            snapshot_save_timeout: Duration::from_millis(100),
+            ..Config::default()
+        });
+        seed(&state, 1).await;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let shutdown = state.shutdown.clone();
+        let server = tokio::spawn(serve(listener, state, Vec::new(), None));
+        shutdown.cancel();
+        let finished = tokio::time::timeout(Duration::from_secs(5), server).await;
+        // Let the writer thread finish, then clean up what it renamed
+        let _ = std::fs::read(&partial);
+        for _ in 0..100 {
+            if !partial.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let _ = std::fs::remove_file(&path);
+        finished
+            .expect("shutdown waited out the stuck save")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_missing_snapshot_restores_nothing() {
+        let state = test_state();
+        let path = std::env::temp_dir().join("no-such-snapshot.json");
+        assert_eq!(
+            persist::restore(&state.store, state.ids.as_ref(), &path)
+                .await
+                .unwrap(),
+            None
+        );
+        assert!(state.store.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn snapshot_errors_name_the_operation_and_file() {
+        use std::error::Error;
+
+        /// Every message in the error's chain, outermost first.
+        fn chain(error: &(dyn Error + 'static)) -> Vec<String> {
+            std::iter::successors(Some(error), |&e| e.source())
+                .map(ToString::to_string)
+                .collect()
+        }
+
+        let state = test_state();
+        let dir = std::env::temp_dir().join(format!("snapshot-errors-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let corrupt = dir.join("corrupt.json");
+        std::fs::write(&corrupt, "[{").unwrap();
+        let unwritable = dir.join("missing").join("items.json");
+
+        let restored = persist::restore(&state.store, state.ids.as_ref(), &corrupt)
+            .await
+            .unwrap_err();
+        let saved = persist::save(&state.store, &state.expiry, &unwritable)
+            .await
+            .unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let restored = chain(&restored);
+        assert_eq!(
+            restored[0],
+            format!("failed to restore snapshot {}", corrupt.display())
+        );
+        assert!(restored[1].contains("EOF while parsing"), "{restored:?}");
+        let saved = chain(&saved);
+        assert_eq!(
+            saved[0],
+            format!("failed to save snapshot {}", unwritable.display())
+        );
+        assert!(saved[1].contains("No such file or directory"), "{saved:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tasks_outliving_the_drain_timeout_are_abandoned() {
+        let tasks = TaskTracker::new();
+        tasks.spawn(std::future::pending::<()>());
+        tasks.spawn(tokio::time::sleep(Duration::from_secs(1)));
+
+        let started = tokio::time::Instant::now();
+        assert!(!shutdown::drain_tasks(&tasks, Duration::from_secs(5)).await);
+        assert_eq!(started.elapsed(), Duration::from_secs(5));
+        assert_eq!(tasks.len(), 1, "the finite task still finished");
+    }
+
+    #[test]
+    fn exports_and_imports_get_longer_than_the_default_timeout() {
+        let timeouts = deadline::RequestTimeouts::new(
+            &Config {
+                request_timeout: Duration::from_millis(100),
+                ..Config::default()
+            },
+            &ROUTE_TIMEOUTS,
+        );
+        for route in ["/items/export", "/items/import"] {
+            assert!(timeouts.for_route(Some(route)) >= Duration::from_secs(60));
+        }
+        for route in ["/items", "/items/:id"] {
+            assert_eq!(timeouts.for_route(Some(route)), Duration::from_millis(100));
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_during_shutdown_get_503_with_retry_after() {
+        let state = test_state_with(Config {
+            shutdown_retry_after_secs: 42,
+            ..Config::default()
+        });
+
+        let response = send(&state, Request::get("/items").body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        state.shutdown.cancel();
+
+        let response = send(&state, Request::get("/items").body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "42");
+        let body = body_json(response).await;
+        assert_eq!(body["success"], false);
+        assert_eq!(body["message"], Config::default().shutdown_message);
+    }
+
+    struct StaticCheck {
+        name: &'static str,
+        critical: bool,
+        result: health::Health,
+    }
+
+    #[async_trait::async_trait]
+    impl health::HealthCheck for StaticCheck {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn critical(&self) -> bool {
+            self.critical
+        }
+
+        async fn check(&self) -> health::Health {
+            self.result.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn deep_health_reports_each_subsystem_and_fails_on_critical() {
+        let mut state = test_state();
+        let mut registry = HealthRegistry::new(Duration::from_millis(100), Duration::ZERO);
+        registry.register(StaticCheck {
+            name: "cache",
+            critical: true,
+            result: health::Health::healthy("warm".to_string()),
+        });
+        registry.register(StaticCheck {
+            name: "database",
+            critical: true,
+            result: health::Health::unhealthy("connection refused".to_string()),
+        });
+        state.health = Arc::new(registry);
+
+        let response = send(
+            &state,
+            Request::get("/healthz/deep").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = body_json(response).await;
+        assert_eq!(body["data"]["status"], "unhealthy");
+        let subsystems = &body["data"]["subsystems"];
+        assert_eq!(subsystems["cache"]["status"], "healthy");
+        assert_eq!(subsystems["cache"]["detail"], "warm");
+        assert_eq!(subsystems["database"]["status"], "unhealthy");
+        assert_eq!(subsystems["database"]["detail"], "connection refused");
+    }
+
+    #[tokio::test]
+    async fn degraded_subsystems_answer_200_and_unhealthy_ones_503() {
+        use health::{Health, HEALTH_STATUS};
+
+        let probe = |checks: Vec<StaticCheck>| async move {
+            let mut state = test_state();
+            let mut registry = HealthRegistry::new(Duration::from_millis(100), Duration::ZERO);
+            for check in checks {
+                registry.register(check);
+            }
+            state.health = Arc::new(registry);
+            let request = Request::get("/healthz/deep").body(Body::empty()).unwrap();
+            send(&state, request).await
+        };
+
+        let cases = [
+            // A critical subsystem reporting itself degraded
+            (
+                StaticCheck {
+                    name: "database",
+                    critical: true,
+                    result: Health::degraded("replica lagging".to_string()),
+                },
+                StatusCode::OK,
+                "degraded",
+            ),
+            // A non-critical subsystem down only degrades the service
+            (
+                StaticCheck {
+                    name: "cache",
+                    critical: false,
+                    result: Health::unhealthy("connection refused".to_string()),
+                },
+                StatusCode::OK,
+                "degraded",
+            ),
+            (
+                StaticCheck {
+                    name: "database",
+                    critical: true,
+                    result: Health::unhealthy("connection refused".to_string()),
+                },
+                StatusCode::SERVICE_UNAVAILABLE,
+                "unhealthy",
+            ),
+        ];
+        for (check, code, status) in cases {
+            let name = check.name;
+            let response = probe(vec![check]).await;
+            assert_eq!(response.status(), code, "{name} {status}");
+            assert_eq!(response.headers()[&HEALTH_STATUS], status);
+            assert_eq!(body_json(response).await["data"]["status"], status);
+        }
+
+        // The other probes share the contract
+        let state = test_state();
+        for uri in ["/health", "/readyz"] {
+            let response = send(&state, Request::get(uri).body(Body::empty()).unwrap()).await;
+            assert_eq!(response.headers()[&HEALTH_STATUS], "healthy", "{uri}");
+            assert_eq!(body_json(response).await["data"]["status"], "healthy");
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_routes_get_the_json_envelope() {
+        let state = test_state();
+
+        let response = send(
+            &state,
+            Request::delete("/no/such/route?x=1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let body = body_json(response).await;
+        assert_eq!(body["success"], false);
+        assert_eq!(body["data"], serde_json::Value::Null);
+        assert_eq!(body["message"], "Route not found: DELETE /no/such/route");
+    }
+
+    #[tokio::test]
+    async fn trailing_slashes_are_served_like_the_canonical_route() {
+        let state = test_state();
+        crate::test_support::seed(&state, 2).await;
+
+        for (canonical, slashed) in [
+            ("/items", "/items/"),
+            ("/items/1", "/items/1/"),
+            ("/items/1/related", "/items/1/related//"),
+        ] {
+            let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+            let canonical = send(&state, get(canonical)).await;
+            let slashed = send(&state, get(slashed)).await;
+            assert_eq!(slashed.status(), StatusCode::OK);
+            assert_eq!(body_json(slashed).await, body_json(canonical).await);
+        }
+
+        let response = send(
+            &state,
+            Request::post("/items/")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"name":"slashed","description":""}"#))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
 }
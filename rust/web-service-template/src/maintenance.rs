@@ -0,0 +1,59 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::{ApiResponse, AppState};
+
+const MAINTENANCE_MESSAGE: &str = "Service is undergoing planned maintenance, please retry later";
+
+/// Runtime-togglable maintenance flag, seeded from `MAINTENANCE_MODE`.
+#[derive(Clone)]
+pub struct Maintenance(Arc<AtomicBool>);
+
+impl Maintenance {
+    pub fn new(enabled: bool) -> Self {
+        Self(Arc::new(AtomicBool::new(enabled)))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::Relaxed);
+    }
+}
+
+/// Paths that keep working during maintenance: probes and metrics so the
+/// instance isn't recycled, and admin so maintenance can be switched off.
+fn is_exempt(path: &str) -> bool {
+    path == "/health"
+        || path == "/metrics"
+        || path.starts_with("/healthz/")
+        || path == "/admin"
+        || path.starts_with("/admin/")
+}
+
+/// Middleware answering 503 with a maintenance message while maintenance
+/// mode is on.
+pub async fn maintenance_gate(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.maintenance.is_enabled() || is_exempt(request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, "60")],
+        ApiResponse::error(MAINTENANCE_MESSAGE),
+    )
+        .into_response()
+}
@@ -0,0 +1,109 @@
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+use crate::expiry::Expiry;
+use crate::ids::IdGenerator;
+use crate::items::{snapshot, Item, ItemStore};
+
+type Cause = Box<dyn Error + Send + Sync>;
+
+/// A snapshot [`save`] or [`restore`] that failed, naming the operation and
+/// the file; the I/O or JSON error behind it is its [`Error::source`].
+#[derive(Debug)]
+pub struct PersistError {
+    /// `save` or `restore`.
+    pub operation: &'static str,
+    pub path: PathBuf,
+    cause: Cause,
+}
+
+impl PersistError {
+    fn new(operation: &'static str, path: &Path, cause: impl Into<Cause>) -> Self {
+        Self {
+            operation,
+            path: path.to_owned(),
+            cause: cause.into(),
+        }
+    }
+
+    /// What went wrong, for log lines that print the error and its cause
+    /// together.
+    pub fn cause(&self) -> &(dyn Error + Send + Sync + 'static) {
+        self.cause.as_ref()
+    }
+}
+
+impl std::fmt::Display for PersistError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "failed to {} snapshot {}",
+            self.operation,
+            self.path.display()
+        )
+    }
+}
+
+impl Error for PersistError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.cause.as_ref())
+    }
+}
+
+/// Writes the live items in `store` to `path` as a JSON array, sorted by
+/// id, and returns how many were written.
+///
+/// The array goes to a temporary file next to `path` that is then renamed
+/// over it, so a write cut short leaves the previous snapshot in place. The
+/// file is written on its own thread, which doesn't keep the process alive:
+/// a caller that stops waiting for a stuck write can exit without it.
+pub async fn save(store: &ItemStore, expiry: &Expiry, path: &Path) -> Result<usize, PersistError> {
+    let failed = |cause: Cause| PersistError::new("save", path, cause);
+    let mut items = snapshot(store, expiry).await;
+    items.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+    let items: Vec<&Item> = items.iter().map(Arc::as_ref).collect();
+    let json = serde_json::to_vec(&items).map_err(|e| failed(e.into()))?;
+
+    let target = path.to_owned();
+    let mut partial = target.clone().into_os_string();
+    partial.push(".partial");
+    let (written, result) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = written
+            .send(std::fs::write(&partial, json).and_then(|()| std::fs::rename(&partial, target)));
+    });
+    result
+        .await
+        .map_err(|e| failed(e.into()))?
+        .map_err(|e| failed(e.into()))?;
+    Ok(items.len())
+}
+
+/// Loads the snapshot at `path` into `store`, replacing items with the same
+/// id, and returns how many were loaded; `None` if there is no snapshot
+/// yet. Restored ids are passed to `ids` so new items don't reuse them.
+/// Item history isn't saved, so each restored item starts over at version 1.
+pub async fn restore(
+    store: &ItemStore,
+    ids: &dyn IdGenerator,
+    path: &Path,
+) -> Result<Option<usize>, PersistError> {
+    let json = match std::fs::read(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(PersistError::new("restore", path, e)),
+    };
+    let restored: Vec<Item> =
+        serde_json::from_slice(&json).map_err(|e| PersistError::new("restore", path, e))?;
+
+    let count = restored.len();
+    let mut items = store.write().await;
+    for item in restored {
+        ids.observe(&item.id);
+        items.insert(item.id.clone(), Arc::new(item));
+    }
+    Ok(Some(count))
+}
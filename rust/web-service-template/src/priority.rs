@@ -0,0 +1,311 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// How urgently a request should be admitted when every slot is taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Bulk transfers that can wait: imports, exports, bulk creates, ingest.
+    Low = 0,
+    Normal = 1,
+    /// Probes, metrics and operator calls, which must not queue behind work.
+    High = 2,
+}
+
+/// Routes admitted ahead of everything else; `/admin/...` is too.
+const HIGH_PRIORITY_ROUTES: &[&str] = &["/", "/health", "/healthz/deep", "/readyz", "/metrics"];
+
+const LOW_PRIORITY_ROUTES: &[&str] = &["/items/import", "/items/export", "/items/bulk", "/ingest"];
+
+impl Priority {
+    /// Classifies a request by its route template.
+    pub fn of_route(route: &str) -> Self {
+        if HIGH_PRIORITY_ROUTES.contains(&route) || route.starts_with("/admin/") {
+            Self::High
+        } else if LOW_PRIORITY_ROUTES.contains(&route) {
+            Self::Low
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+/// Times a tier's oldest waiter may be passed over by other tiers before
+/// it is given the next slot.
+pub const MAX_BYPASSES: u32 = 8;
+
+/// Caps requests in flight (`MAX_CONCURRENT_REQUESTS`), queueing the rest by
+/// [`Priority`].
+///
+/// A freed slot goes to the oldest waiter of the highest waiting tier, so a
+/// health check arriving behind a queue of imports is admitted next. No tier
+/// is starved: each counts the admissions that passed it over, and once one
+/// reaches [`MAX_BYPASSES`] that tier is served next (the lowest first, if
+/// several have), so a waiter is passed over at most `MAX_BYPASSES + 2`
+/// times before its tier is served.
+pub struct PriorityLimiter {
+    max: usize,
+    state: Mutex<LimiterState>,
+}
+
+#[derive(Default)]
+struct LimiterState {
+    running: usize,
+    /// Waiters per tier, indexed by `Priority as usize`, oldest first.
+    waiting: [VecDeque<oneshot::Sender<()>>; 3],
+    /// Admissions that passed over each tier's oldest waiter.
+    bypasses: [u32; 3],
+}
+
+impl LimiterState {
+    /// Picks the tier to admit next, if anything is waiting.
+    fn next_tier(&mut self) -> Option<usize> {
+        let highest = (0..3).rev().find(|&tier| !self.waiting[tier].is_empty())?;
+        let next = (0..highest)
+            .find(|&tier| !self.waiting[tier].is_empty() && self.bypasses[tier] >= MAX_BYPASSES)
+            .unwrap_or(highest);
+        for tier in 0..3 {
+            if tier == next || self.waiting[tier].is_empty() {
+                self.bypasses[tier] = 0;
+            } else {
+                self.bypasses[tier] += 1;
+            }
+        }
+        Some(next)
+    }
+}
+
+impl PriorityLimiter {
+    pub fn new(max: usize) -> Arc<Self> {
+        Arc::new(Self {
+            max,
+            state: Mutex::default(),
+        })
+    }
+
+    /// Waits for a slot. Dropping the future gives up its place in the queue.
+    pub async fn acquire(self: &Arc<Self>, priority: Priority) -> Slot {
+        let admitted = {
+            let mut state = self.state.lock().unwrap();
+            if state.running < self.max {
+                state.running += 1;
+                None
+            } else {
+                let (admit, admitted) = oneshot::channel();
+                state.waiting[priority as usize].push_back(admit);
+                Some(admitted)
+            }
+        };
+        if let Some(admitted) = admitted {
+            let mut queued = Queued {
+                limiter: self,
+                admitted: Some(admitted),
+            };
+            // The sender is only dropped after handing over a slot or with
+            // the limiter itself, which outlives every `Slot`
+            let _ = queued.admitted.as_mut().unwrap().await;
+            queued.admitted = None;
+        }
+        Slot(self.clone())
+    }
+
+    /// Hands a freed slot to the next waiter, or frees it if none is left.
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        while let Some(tier) = state.next_tier() {
+            let admit = state.waiting[tier].pop_front().expect("tier has waiters");
+            // A waiter whose request was cancelled is skipped
+            if admit.send(()).is_ok() {
+                return;
+            }
+        }
+        state.running -= 1;
+    }
+}
+
+/// A place in the queue. If the waiting request is cancelled just after
+/// being handed a slot, dropping this passes the slot on.
+struct Queued<'a> {
+    limiter: &'a PriorityLimiter,
+    admitted: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        if let Some(mut admitted) = self.admitted.take() {
+            admitted.close();
+            if admitted.try_recv().is_ok() {
+                self.limiter.release();
+            }
+        }
+    }
+}
+
+/// A held slot, released on drop.
+pub struct Slot(Arc<PriorityLimiter>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+/// Middleware holding a [`PriorityLimiter`] slot for the rest of the
+/// request. Passes everything through when no limit is configured.
+pub async fn admit_by_priority(
+    State(limiter): State<Option<Arc<PriorityLimiter>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = limiter else {
+        return next.run(request).await;
+    };
+
+    let priority = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(Priority::Normal, |route| Priority::of_route(route.as_str()));
+    let _slot = limiter.acquire(priority).await;
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::{send, test_state_with};
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn routes_are_classified_into_tiers() {
+        assert_eq!(Priority::of_route("/health"), Priority::High);
+        assert_eq!(Priority::of_route("/admin/maintenance"), Priority::High);
+        assert_eq!(Priority::of_route("/items/:id"), Priority::Normal);
+        assert_eq!(Priority::of_route("/items/import"), Priority::Low);
+    }
+
+    #[tokio::test]
+    async fn health_checks_are_admitted_ahead_of_queued_bulk_work() {
+        let state = test_state_with(Config {
+            max_concurrent_requests: Some(1),
+            ..Config::default()
+        });
+        let limiter = state.limiter.clone().unwrap();
+        let held = limiter.acquire(Priority::Normal).await;
+
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+        let spawn = |label: &'static str, request: Request<Body>| {
+            let (state, done_tx) = (state.clone(), done_tx.clone());
+            tokio::spawn(async move {
+                let status = send(&state, request).await.status();
+                done_tx.send((label, status)).unwrap();
+            })
+        };
+        for _ in 0..2 {
+            spawn(
+                "bulk",
+                Request::post("/items/bulk")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from("[]"))
+                    .unwrap(),
+            );
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        spawn(
+            "health",
+            Request::get("/health").body(Body::empty()).unwrap(),
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(done_rx.try_recv().is_err(), "all queued while saturated");
+
+        drop(held);
+        let mut order = Vec::new();
+        for _ in 0..3 {
+            let (label, status) = done_rx.recv().await.unwrap();
+            assert_eq!(status, StatusCode::OK);
+            order.push(label);
+        }
+        assert_eq!(order, ["health", "bulk", "bulk"]);
+    }
+
+    #[tokio::test]
+    async fn low_priority_waiters_are_not_starved() {
+        let limiter = PriorityLimiter::new(1);
+        let held = limiter.acquire(Priority::Normal).await;
+
+        let (admitted_tx, mut admitted_rx) = mpsc::unbounded_channel();
+        let queue = |priority: Priority| {
+            let (limiter, admitted_tx) = (limiter.clone(), admitted_tx.clone());
+            tokio::spawn(async move {
+                let slot = limiter.acquire(priority).await;
+                admitted_tx.send(priority).unwrap();
+                drop(slot);
+            })
+        };
+        queue(Priority::Low);
+        tokio::task::yield_now().await;
+        for _ in 0..20 {
+            queue(Priority::High);
+        }
+        tokio::task::yield_now().await;
+
+        drop(held);
+        let mut order = Vec::new();
+        while order.len() < 21 {
+            order.push(admitted_rx.recv().await.unwrap());
+        }
+        let low_at = order.iter().position(|p| *p == Priority::Low).unwrap();
+        assert_eq!(low_at, MAX_BYPASSES as usize);
+    }
+
+    #[tokio::test]
+    async fn every_tier_is_served_when_all_are_saturated() {
+        let limiter = PriorityLimiter::new(1);
+        let held = limiter.acquire(Priority::Normal).await;
+
+        let (admitted_tx, mut admitted_rx) = mpsc::unbounded_channel();
+        for priority in [Priority::High, Priority::Normal, Priority::Low] {
+            for _ in 0..20 {
+                let (limiter, admitted_tx) = (limiter.clone(), admitted_tx.clone());
+                tokio::spawn(async move {
+                    let slot = limiter.acquire(priority).await;
+                    admitted_tx.send(priority).unwrap();
+                    drop(slot);
+                });
+            }
+        }
+        tokio::task::yield_now().await;
+
+        drop(held);
+        let mut order = Vec::new();
+        while order.len() < 60 {
+            order.push(admitted_rx.recv().await.unwrap());
+        }
+        // While a tier still has waiters, its turn comes within the bound
+        for priority in [Priority::High, Priority::Normal, Priority::Low] {
+            let last = order.iter().rposition(|p| *p == priority).unwrap();
+            let mut passed_over = 0;
+            for admitted in &order[..last] {
+                passed_over = if *admitted == priority {
+                    0
+                } else {
+                    passed_over + 1
+                };
+                assert!(
+                    passed_over <= MAX_BYPASSES + 2,
+                    "{priority:?} passed over {passed_over} times in {order:?}"
+                );
+            }
+        }
+    }
+}
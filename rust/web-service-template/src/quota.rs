@@ -0,0 +1,184 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::clock::{unix_secs, Clock};
+use crate::ApiResponse;
+
+/// Header identifying the client a request is counted against.
+pub static API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// Counts requests per API key against a daily limit (`DAILY_QUOTA`), all
+/// keys resetting together at `QUOTA_RESET_HOUR` UTC.
+///
+/// The key is whatever the client sends: nothing here checks it, so on its
+/// own this enforces nothing, as a client can send a new key with every
+/// request. Put it behind a layer that authenticates the key. To keep
+/// memory bounded regardless, at most `QUOTA_MAX_KEYS` keys are counted
+/// separately each day; keys first seen after that share a single quota.
+///
+/// Usage lives in memory only, so it starts over when the process restarts;
+/// there is no database to persist it to yet.
+pub struct QuotaTracker {
+    limit: u64,
+    /// Seconds past midnight UTC at which a quota day starts.
+    reset_offset: u64,
+    max_keys: usize,
+    clock: Arc<dyn Clock>,
+    state: Mutex<QuotaState>,
+}
+
+#[derive(Default)]
+struct QuotaState {
+    /// Start of the quota day `used` counts, in Unix seconds.
+    day_start: u64,
+    used: HashMap<String, u64>,
+    /// Shared usage of the keys that didn't fit in `used`.
+    overflow: u64,
+}
+
+impl QuotaTracker {
+    pub fn new(limit: u64, reset_hour: u8, max_keys: usize, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            limit,
+            reset_offset: u64::from(reset_hour) * 60 * 60,
+            max_keys,
+            clock,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Counts a request for `key`. Once the key's quota is used up, returns
+    /// the Unix time it resets at instead.
+    pub fn consume(&self, key: &str) -> Result<(), u64> {
+        let now = unix_secs(self.clock.as_ref());
+        let day_start =
+            now.saturating_sub(self.reset_offset) / DAY_SECS * DAY_SECS + self.reset_offset;
+
+        let mut state = self.state.lock().unwrap();
+        // A new day resets every key at once, which also drops keys that
+        // have gone quiet
+        if state.day_start != day_start {
+            state.day_start = day_start;
+            state.used.clear();
+            state.overflow = 0;
+        }
+        let state = &mut *state;
+        let used = if state.used.contains_key(key) || state.used.len() < self.max_keys {
+            state.used.entry(key.to_string()).or_default()
+        } else {
+            &mut state.overflow
+        };
+        if *used >= self.limit {
+            return Err(day_start + DAY_SECS);
+        }
+        *used += 1;
+        Ok(())
+    }
+}
+
+/// Middleware answering 429 once the request's API key has used up its
+/// daily quota. Requests without a key, or with no quota configured, pass
+/// through uncounted.
+pub async fn enforce_quota(
+    State(quota): State<Option<Arc<QuotaTracker>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = request
+        .headers()
+        .get(&API_KEY)
+        .and_then(|value| value.to_str().ok());
+    let (Some(quota), Some(key)) = (quota, key) else {
+        return next.run(request).await;
+    };
+
+    match quota.consume(key) {
+        Ok(()) => next.run(request).await,
+        Err(resets_at) => {
+            let retry_after = resets_at.saturating_sub(unix_secs(quota.clock.as_ref()));
+            let resets_at = OffsetDateTime::from_unix_timestamp(resets_at as i64)
+                .ok()
+                .and_then(|time| time.format(&Rfc3339).ok())
+                .unwrap_or_else(|| resets_at.to_string());
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                ApiResponse::error(format!("quota exceeded, resets at {resets_at}")),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::{body_json, send, test_state_with_clock, ManualClock};
+    use axum::body::Body;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn an_exhausted_quota_is_refused_until_the_reset_boundary() {
+        // 2023-11-14T22:13:20Z
+        let clock = Arc::new(ManualClock::default());
+        let state = test_state_with_clock(
+            Config {
+                daily_quota: Some(2),
+                quota_reset_hour: 6,
+                ..Config::default()
+            },
+            clock.clone(),
+        );
+        let get = |key: &'static str| {
+            let state = state.clone();
+            async move {
+                let request = Request::get("/items")
+                    .header(&API_KEY, key)
+                    .body(Body::empty())
+                    .unwrap();
+                send(&state, request).await
+            }
+        };
+
+        for _ in 0..2 {
+            assert_eq!(get("alice").await.status(), StatusCode::OK);
+        }
+        let refused = get("alice").await;
+        assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(refused.headers()[header::RETRY_AFTER], "28000");
+        assert_eq!(
+            body_json(refused).await["message"],
+            "quota exceeded, resets at 2023-11-15T06:00:00Z"
+        );
+        // Other keys have their own quota
+        assert_eq!(get("bob").await.status(), StatusCode::OK);
+
+        clock.advance(Duration::from_secs(27_999));
+        assert_eq!(get("alice").await.status(), StatusCode::TOO_MANY_REQUESTS);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(get("alice").await.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn keys_past_the_cap_share_one_quota() {
+        let quota = QuotaTracker::new(2, 0, 2, Arc::new(ManualClock::default()));
+        for key in ["alice", "bob", "carol", "dave"] {
+            assert!(quota.consume(key).is_ok(), "{key}");
+        }
+        assert!(quota.consume("erin").is_err(), "carol and dave used it up");
+        assert_eq!(quota.state.lock().unwrap().used.len(), 2);
+        // Keys counted before the cap keep their own quota
+        assert!(quota.consume("alice").is_ok());
+    }
+}
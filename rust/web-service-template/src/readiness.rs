@@ -0,0 +1,165 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::health::{probe_response, Health};
+use crate::{telemetry, AppState};
+
+/// Counts in-flight API requests and derives load-based readiness from them.
+///
+/// Once the count rises above the high-water mark the instance reports not
+/// ready, and it stays that way until the count has drained to the low-water
+/// mark. The gap between the two keeps readiness from flapping while the load
+/// balancer shifts traffic away. Without a high-water mark the count is still
+/// exported but never affects readiness.
+pub struct InFlight {
+    count: AtomicUsize,
+    overloaded: AtomicBool,
+    /// Serializes threshold transitions so they see a consistent count.
+    transition: Mutex<()>,
+    high_water: Option<usize>,
+    low_water: usize,
+}
+
+impl InFlight {
+    pub fn new(high_water: Option<usize>, low_water: Option<usize>) -> Self {
+        Self {
+            count: AtomicUsize::new(0),
+            overloaded: AtomicBool::new(false),
+            transition: Mutex::new(()),
+            high_water,
+            low_water: low_water.unwrap_or(high_water.unwrap_or(0) / 2),
+        }
+    }
+
+    /// Number of API requests currently being handled.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Whether the instance should receive traffic as far as load goes.
+    pub fn is_ready(&self) -> bool {
+        !self.overloaded.load(Ordering::Relaxed)
+    }
+
+    /// Counts a request as in flight until the returned guard is dropped.
+    pub fn enter(self: &Arc<Self>) -> InFlightGuard {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.update();
+        InFlightGuard(self.clone())
+    }
+
+    fn update(&self) {
+        let _transition = self.transition.lock().unwrap();
+        let count = self.count();
+        telemetry::record(|| metrics::gauge!("http_requests_in_flight").set(count as f64));
+
+        let Some(high_water) = self.high_water else {
+            return;
+        };
+        if count > high_water && !self.overloaded.swap(true, Ordering::Relaxed) {
+            tracing::warn!("{count} requests in flight, reporting not ready");
+        } else if count <= self.low_water && self.overloaded.swap(false, Ordering::Relaxed) {
+            tracing::info!("In-flight requests drained to {count}, ready again");
+        }
+    }
+}
+
+/// Keeps one request counted as in flight.
+pub struct InFlightGuard(Arc<InFlight>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.count.fetch_sub(1, Ordering::Relaxed);
+        self.0.update();
+    }
+}
+
+/// Middleware counting the requests it wraps as in flight.
+pub async fn track_in_flight(
+    State(in_flight): State<Arc<InFlight>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let _guard = in_flight.enter();
+    next.run(request).await
+}
+
+/// The `data` of `/readyz`.
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-api", serde(rename_all = "camelCase"))]
+pub struct Readiness {
+    #[serde(flatten)]
+    pub health: Health,
+    pub in_flight: usize,
+}
+
+/// Readiness probe: unhealthy (503) while overloaded, healthy otherwise,
+/// with the in-flight count either way. Draining and maintenance mode
+/// already answer 503 before this handler runs.
+pub async fn readyz(State(state): State<AppState>) -> Response {
+    let in_flight = state.in_flight.count();
+    let (health, message) = if state.in_flight.is_ready() {
+        (Health::healthy(None), "Ready")
+    } else {
+        (
+            Health::unhealthy(format!("{in_flight} requests in flight")),
+            "Overloaded",
+        )
+    };
+    probe_response(health.status, message, Readiness { health, in_flight })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::{send, test_state_with};
+    use axum::{body::Body, http::StatusCode};
+
+    #[test]
+    fn readiness_flips_above_the_high_mark_and_recovers_below_the_low_mark() {
+        let in_flight = Arc::new(InFlight::new(Some(4), Some(2)));
+
+        let mut guards: Vec<InFlightGuard> = (0..4).map(|_| in_flight.enter()).collect();
+        assert!(in_flight.is_ready(), "at the high mark is still ready");
+
+        guards.push(in_flight.enter());
+        assert_eq!(in_flight.count(), 5);
+        assert!(!in_flight.is_ready());
+
+        guards.truncate(3);
+        assert!(!in_flight.is_ready(), "stays unready between the marks");
+
+        guards.truncate(2);
+        assert!(in_flight.is_ready());
+
+        guards.clear();
+        assert_eq!(in_flight.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn readyz_reports_overload() {
+        let state = test_state_with(Config {
+            ready_high_water: Some(1),
+            ..Config::default()
+        });
+        let readyz = || Request::get("/readyz").body(Body::empty()).unwrap();
+
+        assert_eq!(send(&state, readyz()).await.status(), StatusCode::OK);
+
+        let guards = [state.in_flight.enter(), state.in_flight.enter()];
+        assert_eq!(
+            send(&state, readyz()).await.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        drop(guards);
+        assert_eq!(send(&state, readyz()).await.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,195 @@
+use axum::http::{HeaderMap, Uri};
+use std::collections::HashSet;
+
+use crate::config::Config;
+
+/// Replacement for every redacted value.
+pub const REDACTED: &str = "***";
+
+/// Headers carrying credentials, masked whatever `REDACT_HEADERS` says.
+const ALWAYS_REDACTED_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+];
+
+/// Masks secrets before anything request-derived is logged.
+///
+/// Headers are matched by name, case-insensitively: credential headers
+/// (`Authorization`, `Proxy-Authorization`, `Cookie`, `Set-Cookie`) always,
+/// plus those in `REDACT_HEADERS`.
+/// JSON fields (`REDACT_FIELDS`) are either a bare key, redacted wherever it
+/// appears, or a dotted path such as `user.credentials.pin`, redacted only
+/// at that position; keys match case-insensitively and arrays are looked
+/// through, so `items.token` covers every element of `items`. The bare keys
+/// also mask query parameters of the same name, as in `?api_key=...`.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    headers: HashSet<String>,
+    keys: HashSet<String>,
+    paths: Vec<Vec<String>>,
+}
+
+impl Redactor {
+    pub fn new(config: &Config) -> Self {
+        let lower = |s: &String| s.to_ascii_lowercase();
+        let (paths, keys): (Vec<&String>, Vec<&String>) = config
+            .redact_fields
+            .iter()
+            .partition(|field| field.contains('.'));
+        Self {
+            headers: ALWAYS_REDACTED_HEADERS
+                .iter()
+                .map(|name| name.to_string())
+                .chain(config.redact_headers.iter().map(lower))
+                .collect(),
+            keys: keys.into_iter().map(lower).collect(),
+            paths: paths
+                .into_iter()
+                .map(|path| {
+                    path.split('.')
+                        .map(|key| key.to_ascii_lowercase())
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// Renders headers as `name: value` pairs with sensitive values masked.
+    pub fn headers(&self, headers: &HeaderMap) -> String {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if self.headers.contains(name.as_str()) {
+                    REDACTED
+                } else {
+                    value.to_str().unwrap_or("<binary>")
+                };
+                format!("{name}: {value}")
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Renders `uri` with the values of sensitive query parameters masked.
+    pub fn uri(&self, uri: &Uri) -> String {
+        let Some(query) = uri.query() else {
+            return uri.to_string();
+        };
+        let query: Vec<String> = query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((key, _)) if self.keys.contains(&key.to_ascii_lowercase()) => {
+                    format!("{key}={REDACTED}")
+                }
+                _ => pair.to_string(),
+            })
+            .collect();
+        format!("{}?{}", uri.path(), query.join("&"))
+    }
+
+    /// Masks every configured field in `value`.
+    pub fn json(&self, value: &mut serde_json::Value) {
+        self.walk(value, &mut Vec::new());
+    }
+
+    fn walk(&self, value: &mut serde_json::Value, path: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    path.push(key.to_ascii_lowercase());
+                    if self.keys.contains(&path[path.len() - 1]) || self.paths.contains(path) {
+                        *value = serde_json::Value::from(REDACTED);
+                    } else {
+                        self.walk(value, path);
+                    }
+                    path.pop();
+                }
+            }
+            serde_json::Value::Array(values) => {
+                for value in values {
+                    self.walk(value, path);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{header, HeaderValue};
+
+    #[test]
+    fn masks_bare_keys_anywhere_and_paths_only_in_place() {
+        let redactor = Redactor::new(&Config {
+            redact_fields: vec!["password".to_string(), "card.pin".to_string()],
+            ..Config::default()
+        });
+        let mut value = serde_json::json!({
+            "Password": "a",
+            "user": {"password": "b", "pin": "1234"},
+            "cards": [{"pin": "5678"}],
+            "card": {"pin": "0000", "last4": "4242"},
+        });
+        redactor.json(&mut value);
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "Password": "***",
+                "user": {"password": "***", "pin": "1234"},
+                "cards": [{"pin": "5678"}],
+                "card": {"pin": "***", "last4": "4242"},
+            })
+        );
+    }
+
+    #[test]
+    fn masks_configured_headers() {
+        let redactor = Redactor::new(&Config::default());
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer s3cret"),
+        );
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+
+        assert_eq!(
+            redactor.headers(&headers),
+            "authorization: ***, accept: application/json"
+        );
+    }
+
+    #[test]
+    fn masks_sensitive_query_parameters() {
+        let redactor = Redactor::new(&Config::default());
+        let uri: Uri = "/items?API_KEY=s3cret&q=widget&token=t0k".parse().unwrap();
+        assert_eq!(redactor.uri(&uri), "/items?API_KEY=***&q=widget&token=***");
+        let uri: Uri = "/items/1".parse().unwrap();
+        assert_eq!(redactor.uri(&uri), "/items/1");
+    }
+
+    #[test]
+    fn configured_headers_add_to_the_credential_headers() {
+        let redactor = Redactor::new(&Config {
+            redact_headers: vec!["X-Session".to_string()],
+            ..Config::default()
+        });
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            (header::AUTHORIZATION, "Bearer s3cret"),
+            (header::PROXY_AUTHORIZATION, "Basic s3cret"),
+            (header::COOKIE, "session=s3cret"),
+            (header::SET_COOKIE, "session=s3cret"),
+        ] {
+            headers.insert(name, HeaderValue::from_static(value));
+        }
+        headers.insert("x-session", HeaderValue::from_static("s3cret"));
+
+        let rendered = redactor.headers(&headers);
+        assert!(!rendered.contains("s3cret"), "{rendered}");
+        assert_eq!(rendered.matches(REDACTED).count(), 5);
+    }
+}
@@ -0,0 +1,350 @@
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::retry_budget::RetryBudget;
+use crate::worker::WorkError;
+
+/// First wait between failed registration attempts; doubled after each one.
+const REGISTER_RETRY_BASE: Duration = Duration::from_millis(500);
+/// Longest wait between failed registration attempts.
+const REGISTER_RETRY_MAX: Duration = Duration::from_secs(30);
+
+/// How this instance is advertised to a service registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+    /// Service name, shared by every instance (`SERVICE_NAME`).
+    pub name: String,
+    /// Unique to this instance: the name and the port.
+    pub id: String,
+    /// Empty when listening on every interface, letting the registry use
+    /// its own idea of the host's address.
+    pub address: String,
+    pub port: u16,
+    pub health_url: String,
+    /// Set for HTTPS checks: they go to an IP address, which the served
+    /// certificate rarely names, and only need to know the service answers.
+    pub tls_skip_verify: bool,
+}
+
+impl Registration {
+    /// Advertises `bind_addr`, served over `scheme` (`http` or `https`).
+    /// When that is a wildcard address the health check goes to loopback,
+    /// which is where a node-local registry agent (the usual Consul setup)
+    /// reaches the service.
+    pub fn new(name: &str, bind_addr: SocketAddr, scheme: &str) -> Self {
+        let ip = bind_addr.ip();
+        let (address, check_host) = if ip.is_unspecified() {
+            (String::new(), "127.0.0.1".to_string())
+        } else {
+            (ip.to_string(), ip.to_string())
+        };
+        let port = bind_addr.port();
+        Self {
+            name: name.to_string(),
+            id: format!("{name}-{port}"),
+            address,
+            port,
+            health_url: format!("{scheme}://{check_host}:{port}/health"),
+            tls_skip_verify: scheme == "https",
+        }
+    }
+}
+
+/// A service registry the instance announces itself to while it serves.
+#[async_trait]
+pub trait ServiceRegistrar: Send + Sync + 'static {
+    async fn register(&self, registration: &Registration) -> Result<(), WorkError>;
+
+    async fn deregister(&self, registration: &Registration) -> Result<(), WorkError>;
+}
+
+/// The registrar for `REGISTRY_URL`, if one is set. `Config::validate`
+/// rejects the URL in builds without a registry client.
+pub fn from_config(config: &Config) -> Option<Arc<dyn ServiceRegistrar>> {
+    #[cfg(feature = "consul")]
+    if let Some(url) = &config.registry_url {
+        return Some(Arc::new(Consul::from_url(url)));
+    }
+    #[cfg(not(feature = "consul"))]
+    let _ = config;
+    None
+}
+
+/// Registers with `registrar`, retrying with backoff until it works or
+/// `shutdown` is cancelled, and deregisters once shutdown begins so the
+/// registry stops routing here while requests drain.
+///
+/// Each retry takes a token from `budget`; while it is spent the attempt is
+/// skipped and tried again after the next backoff.
+///
+/// Run it as a background task: serving never waits for the registry.
+pub async fn run(
+    registrar: Arc<dyn ServiceRegistrar>,
+    registration: Registration,
+    budget: Arc<RetryBudget>,
+    shutdown: CancellationToken,
+) {
+    let mut wait = REGISTER_RETRY_BASE;
+    let mut first = true;
+    loop {
+        if std::mem::take(&mut first) || budget.try_acquire() {
+            match registrar.register(&registration).await {
+                Ok(()) => break,
+                Err(e) => warn!(
+                    "Registering {} failed ({}), retrying in {:?}",
+                    registration.id, e, wait
+                ),
+            }
+        } else {
+            warn!(
+                "Retry budget spent, registering {} again in {:?}",
+                registration.id, wait
+            );
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => wait = (wait * 2).min(REGISTER_RETRY_MAX),
+            _ = shutdown.cancelled() => return,
+        }
+    }
+    info!("Registered {} with the service registry", registration.id);
+
+    shutdown.cancelled().await;
+    match registrar.deregister(&registration).await {
+        Ok(()) => info!("Deregistered {}", registration.id),
+        Err(e) => warn!("Deregistering {} failed: {}", registration.id, e),
+    }
+}
+
+/// Registers with the local Consul agent's HTTP API, with an HTTP(S) health
+/// check on `/health`.
+#[cfg(feature = "consul")]
+pub struct Consul {
+    addr: String,
+}
+
+#[cfg(feature = "consul")]
+impl Consul {
+    /// Accepts `http://host:port`, the agent's address.
+    pub fn from_url(url: &str) -> Self {
+        let addr = url.strip_prefix("http://").unwrap_or(url);
+        Self {
+            addr: addr.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Sends one `PUT` and fails unless the agent answers 2xx.
+    async fn put(&self, path: &str, body: &str) -> Result<(), WorkError> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let request = format!(
+            "PUT {path} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            self.addr,
+            body.len()
+        );
+        let exchange = async {
+            let mut stream = tokio::net::TcpStream::connect(&self.addr).await?;
+            stream.write_all(request.as_bytes()).await?;
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).await?;
+            Ok::<_, std::io::Error>(response)
+        };
+        let response = tokio::time::timeout(Duration::from_secs(5), exchange)
+            .await
+            .map_err(|_| "timed out")??;
+        let response = String::from_utf8_lossy(&response);
+        let status = response.lines().next().unwrap_or_default();
+        match status.split(' ').nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(format!("Consul answered {status:?}").into()),
+        }
+    }
+}
+
+#[cfg(feature = "consul")]
+#[async_trait]
+impl ServiceRegistrar for Consul {
+    async fn register(&self, registration: &Registration) -> Result<(), WorkError> {
+        let body = serde_json::json!({
+            "ID": registration.id,
+            "Name": registration.name,
+            "Address": registration.address,
+            "Port": registration.port,
+            "Check": {
+                "HTTP": registration.health_url,
+                "TLSSkipVerify": registration.tls_skip_verify,
+                "Interval": "10s",
+                "DeregisterCriticalServiceAfter": "1m",
+            },
+        });
+        self.put("/v1/agent/service/register", &body.to_string())
+            .await
+    }
+
+    async fn deregister(&self, registration: &Registration) -> Result<(), WorkError> {
+        self.put(
+            &format!("/v1/agent/service/deregister/{}", registration.id),
+            "",
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Records every call, failing the first `failures` registrations.
+    #[derive(Default)]
+    struct MockRegistry {
+        calls: Mutex<Vec<&'static str>>,
+        failures: Mutex<u32>,
+    }
+
+    #[async_trait]
+    impl ServiceRegistrar for MockRegistry {
+        async fn register(&self, _registration: &Registration) -> Result<(), WorkError> {
+            self.calls.lock().unwrap().push("register");
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err("registry unavailable".into());
+            }
+            Ok(())
+        }
+
+        async fn deregister(&self, _registration: &Registration) -> Result<(), WorkError> {
+            self.calls.lock().unwrap().push("deregister");
+            Ok(())
+        }
+    }
+
+    fn budget(max_retries: u32) -> Arc<RetryBudget> {
+        Arc::new(RetryBudget::new(max_retries, Duration::from_secs(60)))
+    }
+
+    fn registration() -> Registration {
+        Registration::new("items", "0.0.0.0:3000".parse().unwrap(), "http")
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn registers_with_retries_and_deregisters_on_shutdown() {
+        let registry = Arc::new(MockRegistry::default());
+        *registry.failures.lock().unwrap() = 2;
+        let shutdown = CancellationToken::new();
+        let task = tokio::spawn(run(
+            registry.clone(),
+            registration(),
+            budget(20),
+            shutdown.clone(),
+        ));
+
+        // 0.5s, then 1s after the two failures
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(*registry.calls.lock().unwrap(), ["register"; 3]);
+
+        shutdown.cancel();
+        task.await.unwrap();
+        assert_eq!(
+            *registry.calls.lock().unwrap(),
+            ["register", "register", "register", "deregister"]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_before_registering_skips_deregistration() {
+        let registry = Arc::new(MockRegistry::default());
+        *registry.failures.lock().unwrap() = u32::MAX;
+        let shutdown = CancellationToken::new();
+        let task = tokio::spawn(run(
+            registry.clone(),
+            registration(),
+            budget(20),
+            shutdown.clone(),
+        ));
+
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        shutdown.cancel();
+        task.await.unwrap();
+        let calls = registry.calls.lock().unwrap();
+        assert!(!calls.is_empty());
+        assert!(calls.iter().all(|call| *call == "register"), "{calls:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_stop_while_the_budget_is_spent() {
+        let registry = Arc::new(MockRegistry::default());
+        *registry.failures.lock().unwrap() = u32::MAX;
+        let shutdown = CancellationToken::new();
+        let task = tokio::spawn(run(
+            registry.clone(),
+            registration(),
+            budget(1),
+            shutdown.clone(),
+        ));
+
+        // Backoff alone would have made five attempts by now
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        shutdown.cancel();
+        task.await.unwrap();
+        assert_eq!(*registry.calls.lock().unwrap(), ["register"; 2]);
+    }
+
+    #[test]
+    fn wildcard_binds_are_checked_over_loopback() {
+        let wildcard = registration();
+        assert_eq!(
+            (wildcard.id.as_str(), wildcard.address.as_str()),
+            ("items-3000", "")
+        );
+        assert_eq!(wildcard.health_url, "http://127.0.0.1:3000/health");
+
+        let specific = Registration::new("items", "10.0.0.5:8080".parse().unwrap(), "http");
+        assert_eq!(specific.address, "10.0.0.5");
+        assert_eq!(specific.health_url, "http://10.0.0.5:8080/health");
+    }
+
+    #[test]
+    fn https_services_are_checked_over_https() {
+        let registration = Registration::new("items", "0.0.0.0:3443".parse().unwrap(), "https");
+        assert_eq!(registration.health_url, "https://127.0.0.1:3443/health");
+        assert!(registration.tls_skip_verify);
+        assert!(!self::registration().tls_skip_verify);
+    }
+
+    #[cfg(feature = "consul")]
+    #[tokio::test]
+    async fn consul_is_sent_register_and_deregister_requests() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let consul = Consul::from_url(&format!("http://{}/", listener.local_addr().unwrap()));
+        let agent = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for _ in 0..2 {
+                let (mut conn, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let read = conn.read(&mut buf).await.unwrap();
+                requests.push(String::from_utf8_lossy(&buf[..read]).into_owned());
+                conn.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                    .await
+                    .unwrap();
+            }
+            requests
+        });
+
+        consul.register(&registration()).await.unwrap();
+        consul.deregister(&registration()).await.unwrap();
+        let requests = agent.await.unwrap();
+        assert!(requests[0].starts_with("PUT /v1/agent/service/register "));
+        assert!(requests[0].contains(r#""HTTP":"http://127.0.0.1:3000/health""#));
+        assert!(requests[0].contains(r#""TLSSkipVerify":false"#));
+        assert!(requests[1].starts_with("PUT /v1/agent/service/deregister/items-3000 "));
+    }
+}
@@ -0,0 +1,167 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// Process-lifetime counters summarised in the shutdown report.
+pub struct RunStats {
+    started: Instant,
+    requests: AtomicU64,
+    client_errors: AtomicU64,
+    server_errors: AtomicU64,
+    trigger: OnceLock<String>,
+}
+
+impl Default for RunStats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: AtomicU64::new(0),
+            client_errors: AtomicU64::new(0),
+            server_errors: AtomicU64::new(0),
+            trigger: OnceLock::new(),
+        }
+    }
+}
+
+/// Summary logged (and optionally written to `SHUTDOWN_REPORT_FILE`) once
+/// the server has drained.
+#[derive(Serialize, Debug)]
+#[cfg_attr(feature = "camel-case-api", serde(rename_all = "camelCase"))]
+pub struct ShutdownReport {
+    pub uptime_secs: u64,
+    pub requests: u64,
+    /// 4xx responses.
+    pub client_errors: u64,
+    /// 5xx responses.
+    pub server_errors: u64,
+    pub trigger: String,
+}
+
+impl RunStats {
+    /// Records what started the shutdown; only the first trigger is kept.
+    pub fn record_trigger(&self, trigger: impl Into<String>) {
+        let _ = self.trigger.set(trigger.into());
+    }
+
+    pub fn report(&self) -> ShutdownReport {
+        ShutdownReport {
+            uptime_secs: self.started.elapsed().as_secs(),
+            requests: self.requests.load(Ordering::Relaxed),
+            client_errors: self.client_errors.load(Ordering::Relaxed),
+            server_errors: self.server_errors.load(Ordering::Relaxed),
+            trigger: self
+                .trigger
+                .get()
+                .cloned()
+                .unwrap_or_else(|| "server exited".to_string()),
+        }
+    }
+}
+
+/// Middleware counting every response, and 4xx and 5xx responses
+/// separately.
+pub async fn count_requests(
+    State(stats): State<Arc<RunStats>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    stats.requests.fetch_add(1, Ordering::Relaxed);
+    if response.status().is_client_error() {
+        stats.client_errors.fetch_add(1, Ordering::Relaxed);
+    } else if response.status().is_server_error() {
+        stats.server_errors.fetch_add(1, Ordering::Relaxed);
+    }
+    response
+}
+
+/// Logs the report and, when `path` is set, writes it there as JSON.
+/// Failing to write is logged but never fails the shutdown.
+pub fn emit(report: &ShutdownReport, path: Option<&Path>) {
+    info!(
+        uptime_secs = report.uptime_secs,
+        requests = report.requests,
+        client_errors = report.client_errors,
+        server_errors = report.server_errors,
+        trigger = %report.trigger,
+        "Shutdown report"
+    );
+
+    if let Some(path) = path {
+        let written = serde_json::to_vec_pretty(report)
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(path, json));
+        if let Err(e) = written {
+            warn!(
+                "Failed to write shutdown report to {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{send, test_state, CapturedLogs};
+    use axum::{body::Body, http::Request};
+
+    #[tokio::test]
+    async fn report_counts_requests_errors_and_trigger() {
+        let state = test_state();
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        send(&state, get("/items")).await;
+        send(&state, get("/nope")).await;
+        state.maintenance.set(true);
+        send(&state, get("/items")).await;
+        state.stats.record_trigger("SIGTERM");
+        state.stats.record_trigger("POST /admin/shutdown");
+
+        let report = state.stats.report();
+        assert_eq!(report.requests, 3);
+        assert_eq!(report.client_errors, 1);
+        assert_eq!(report.server_errors, 1);
+        assert_eq!(report.trigger, "SIGTERM");
+
+        let path =
+            std::env::temp_dir().join(format!("shutdown-report-{}.json", std::process::id()));
+        emit(&report, Some(&path));
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written["trigger"], "SIGTERM");
+        assert_eq!(written.as_object().unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn the_report_is_logged_as_one_line() {
+        let state = test_state();
+        send(&state, Request::get("/items").body(Body::empty()).unwrap()).await;
+        state.stats.record_trigger("SIGINT");
+
+        let logs = CapturedLogs::default();
+        {
+            let _guard = logs.install();
+            emit(&state.stats.report(), None);
+        }
+        let logs = logs.contents();
+        let lines: Vec<&str> = logs.lines().collect();
+        assert_eq!(lines.len(), 1, "{logs}");
+        assert!(
+            lines[0].contains(
+                "Shutdown report uptime_secs=0 requests=1 client_errors=0 server_errors=0 trigger=SIGINT"
+            ),
+            "{logs}"
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+use tracing::warn;
+
+use crate::telemetry;
+
+/// Delay before the first retry; doubled for each further attempt.
+const BASE_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Service-wide cap on retries, shared by every component that retries
+/// (service registration today; downstream calls, webhooks and storage as
+/// they are added).
+///
+/// A token bucket holding at most `max_retries` tokens, refilled evenly over
+/// `window`. Each retry takes a token; when none are left the retry is
+/// skipped and the original failure returned, so retries can't multiply
+/// load on a dependency that is already struggling. First attempts are never
+/// limited.
+pub struct RetryBudget {
+    capacity: f64,
+    refill_per_sec: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RetryBudget {
+    pub fn new(max_retries: u32, window: Duration) -> Self {
+        let capacity = f64::from(max_retries);
+        Self {
+            capacity,
+            refill_per_sec: capacity / window.as_secs_f64().max(f64::EPSILON),
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes one retry token, or returns `false` if the budget is spent.
+    pub fn try_acquire(&self) -> bool {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        bucket.refilled_at = now;
+
+        let acquired = bucket.tokens >= 1.0;
+        if acquired {
+            bucket.tokens -= 1.0;
+        } else {
+            telemetry::record(|| metrics::counter!("retry_budget_exhausted_total").increment(1));
+        }
+        let tokens = bucket.tokens;
+        telemetry::record(|| metrics::gauge!("retry_budget_tokens").set(tokens));
+        acquired
+    }
+
+    /// Runs `op` up to `max_attempts` times with exponential backoff,
+    /// retrying only while the budget allows. Returns the last error once
+    /// attempts or budget run out.
+    #[allow(dead_code)] // Call helper for downstream clients; registration paces its own retries
+    pub async fn retry<T, E, F, Fut>(&self, max_attempts: u32, mut op: F) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: std::fmt::Display,
+    {
+        let mut attempt = 1;
+        loop {
+            let err = match op().await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            if attempt >= max_attempts {
+                return Err(err);
+            }
+            if !self.try_acquire() {
+                warn!("Retry budget exhausted, not retrying: {}", err);
+                return Err(err);
+            }
+            sleep(BASE_BACKOFF * 2u32.pow(attempt - 1)).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    async fn always_failing(budget: &RetryBudget, calls: &AtomicU32) -> Result<(), String> {
+        budget
+            .retry(3, || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>("unavailable".to_string())
+            })
+            .await
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn exhausted_budget_fails_fast_without_retrying() {
+        let budget = RetryBudget::new(3, Duration::from_secs(60));
+        let calls = AtomicU32::new(0);
+
+        // Two retries each: the first operation spends two tokens, the second
+        // gets one retry before the bucket runs dry
+        always_failing(&budget, &calls).await.unwrap_err();
+        assert_eq!(calls.swap(0, Ordering::SeqCst), 3);
+        always_failing(&budget, &calls).await.unwrap_err();
+        assert_eq!(calls.swap(0, Ordering::SeqCst), 2);
+
+        let started = Instant::now();
+        assert_eq!(
+            always_failing(&budget, &calls).await.unwrap_err(),
+            "unavailable"
+        );
+        assert_eq!(
+            calls.swap(0, Ordering::SeqCst),
+            1,
+            "no retries once exhausted"
+        );
+        assert_eq!(started.elapsed(), Duration::ZERO, "no backoff either");
+
+        // Tokens come back as the window passes
+        tokio::time::advance(Duration::from_secs(40)).await;
+        always_failing(&budget, &calls).await.unwrap_err();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn success_after_a_retry_is_returned() {
+        let budget = RetryBudget::new(1, Duration::from_secs(1));
+        let calls = AtomicU32::new(0);
+
+        let result = budget
+            .retry(3, || async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err("flaky"),
+                    n => Ok(n),
+                }
+            })
+            .await;
+        assert_eq!(result, Ok(1));
+    }
+}
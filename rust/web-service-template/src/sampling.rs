@@ -0,0 +1,105 @@
+use axum::http::Response;
+use std::time::Duration;
+use tower_http::trace::{DefaultOnResponse, OnResponse};
+use tracing::{Level, Span};
+
+use crate::stack::REQUEST_ID;
+
+/// Whether the request with this id is traced at `rate` (`TRACE_SAMPLE_RATE`).
+///
+/// The decision is a hash of the request id rather than a coin flip, so
+/// every service the id is passed to (as `x-request-id`) makes the same call
+/// and a sampled request can be followed end to end.
+pub fn is_sampled(request_id: &str, rate: f64) -> bool {
+    if rate >= 1.0 {
+        return true;
+    }
+    // FNV-1a, which unlike `DefaultHasher` is the same in every build
+    let hash = request_id
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    (hash as f64 / u64::MAX as f64) < rate
+}
+
+/// Logs the end of sampled requests like [`DefaultOnResponse`] at INFO, and
+/// of unsampled ones (which have no span) only when they failed with a 5xx,
+/// so errors are never sampled away.
+#[derive(Clone, Copy)]
+pub struct SampledOnResponse;
+
+impl<B> OnResponse<B> for SampledOnResponse {
+    fn on_response(self, response: &Response<B>, latency: Duration, span: &Span) {
+        if !span.is_none() {
+            DefaultOnResponse::new()
+                .level(Level::INFO)
+                .on_response(response, latency, span);
+        } else if response.status().is_server_error() {
+            let request_id = response
+                .headers()
+                .get(&REQUEST_ID)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            tracing::info!(
+                %request_id,
+                status = response.status().as_u16(),
+                latency = ?latency,
+                "finished processing request"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::{send, test_state_with, CapturedLogs};
+    use axum::{body::Body, http::Request, http::StatusCode};
+
+    #[test]
+    fn the_decision_depends_only_on_the_request_id() {
+        let ids: Vec<String> = (0..10_000).map(|n| format!("request-{n}")).collect();
+        let sampled = ids.iter().filter(|id| is_sampled(id, 0.1)).count();
+        assert!((800..1200).contains(&sampled), "{sampled} of 10000");
+
+        for id in &ids[..100] {
+            assert_eq!(is_sampled(id, 0.1), is_sampled(id, 0.1));
+        }
+        assert!(ids.iter().all(|id| is_sampled(id, 1.0)));
+        assert!(!ids.iter().any(|id| is_sampled(id, 0.0)));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn unsampled_requests_are_logged_only_when_they_fail() {
+        let state = test_state_with(Config {
+            trace_sample_rate: 0.0,
+            ..Config::default()
+        });
+        let logs = CapturedLogs::default();
+        let _guard = logs.install();
+        let get = || Request::get("/items").body(Body::empty()).unwrap();
+
+        let response = send(&state, get()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(
+            !logs.contents().contains("finished processing request"),
+            "{}",
+            logs.contents()
+        );
+
+        // Draining answers 503
+        state.shutdown.cancel();
+        let response = send(&state, get()).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let request_id = response.headers()[&REQUEST_ID].to_str().unwrap();
+        let output = logs.contents();
+        assert!(output.contains("finished processing request"), "{output}");
+        assert!(
+            output.contains(&format!("request_id={request_id}")),
+            "{output}"
+        );
+        assert!(output.contains("status=503"), "{output}");
+    }
+}
@@ -0,0 +1,87 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::{info, warn};
+
+use crate::report::RunStats;
+use crate::{ApiResponse, AppState};
+
+/// Waits for Ctrl+C or SIGTERM and then cancels `shutdown`, which every
+/// long-running part of the service (HTTP server, background worker) watches.
+/// The signal is recorded as the shutdown trigger in `stats`.
+pub async fn listen_for_signals(shutdown: CancellationToken, stats: Arc<RunStats>) {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    let signal = tokio::select! {
+        _ = ctrl_c => "SIGINT",
+        _ = terminate => "SIGTERM",
+        _ = shutdown.cancelled() => return,
+    };
+
+    info!("Received {}, shutting down...", signal);
+    stats.record_trigger(signal);
+    shutdown.cancel();
+}
+
+/// Stops `tasks` from taking new tasks and waits up to `timeout` for the
+/// running ones to finish, so buffered data is flushed before the process
+/// exits. Tasks still running after that are abandoned with a warning.
+/// Returns whether every task finished.
+pub async fn drain_tasks(tasks: &TaskTracker, timeout: Duration) -> bool {
+    tasks.close();
+    if tokio::time::timeout(timeout, tasks.wait()).await.is_ok() {
+        return true;
+    }
+    warn!(
+        "{} background tasks still running after {:?}, abandoning them",
+        tasks.len(),
+        timeout
+    );
+    false
+}
+
+/// Middleware answering with 503 and the configured shutdown message once
+/// graceful shutdown has begun. New connections are already refused at that
+/// point; this covers requests arriving on kept-alive connections while
+/// in-flight work drains.
+pub async fn reject_while_draining(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.shutdown.is_cancelled() {
+        return next.run(request).await;
+    }
+
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(
+            header::RETRY_AFTER,
+            state.config.shutdown_retry_after_secs.to_string(),
+        )],
+        ApiResponse::error(state.config.shutdown_message.clone()),
+    )
+        .into_response()
+}
@@ -0,0 +1,84 @@
+//! Name similarity used to rank related items.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// How close two names are: more shared words first, then a smaller edit
+/// distance. Comparison is case-insensitive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Similarity {
+    shared_words: usize,
+    distance: usize,
+}
+
+impl Similarity {
+    pub fn between(a: &str, b: &str) -> Self {
+        let (a, b) = (a.to_lowercase(), b.to_lowercase());
+        let words = |s: &str| -> HashSet<String> {
+            s.split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        Self {
+            shared_words: words(&a).intersection(&words(&b)).count(),
+            distance: levenshtein(&a, &b),
+        }
+    }
+}
+
+/// Orders more similar names first.
+impl Ord for Similarity {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .shared_words
+            .cmp(&self.shared_words)
+            .then(self.distance.cmp(&other.distance))
+    }
+}
+
+impl PartialOrd for Similarity {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Number of single-character insertions, deletions and substitutions
+/// needed to turn `a` into `b`.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levenshtein_counts_edits() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("abc", ""), 3);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("widget", "widget"), 0);
+    }
+
+    #[test]
+    fn shared_words_outrank_edit_distance() {
+        let target = "Blue Widget";
+        let shares_word = Similarity::between(target, "Widget Pro Max");
+        let close_spelling = Similarity::between(target, "Bleu Wodget");
+        assert!(shares_word < close_spelling, "more similar sorts first");
+        assert!(Similarity::between(target, "blue widget") < shares_word);
+    }
+}
@@ -0,0 +1,220 @@
+use axum::{
+    body::Body, extract::Request, http::HeaderName, middleware, response::IntoResponse,
+    routing::Route,
+};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tower::{Layer, Service, ServiceBuilder};
+use tower_http::{
+    compression::CompressionLayer,
+    cors::CorsLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tracing::Span;
+
+use crate::body_log::{self, BodyLogging};
+use crate::deadline::{self, RequestTimeouts};
+use crate::redact::Redactor;
+use crate::sampling::{self, SampledOnResponse};
+use crate::{
+    maintenance, priority, quota, report, shutdown, telemetry, uri_limit, versioning, AppState,
+    ROUTE_TIMEOUTS,
+};
+
+/// Header carrying the request id, taken from the client or generated.
+pub static REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// The middleware wrapped around every route, listed outermost first, which
+/// is the order a request passes through them:
+///
+/// 1. Request id: assigned before anything else so every later layer, and
+///    every log line, can refer to it.
+/// 2. Access log: outside everything that can answer early (CORS preflight,
+///    timeouts, 503s while draining) so those responses are logged too.
+///    Only a `TRACE_SAMPLE_RATE` share of requests, picked by request id,
+///    get a span and a log line, except that 5xx responses always do. The
+///    span's URI goes through the [`Redactor`], so secrets passed as query
+///    parameters stay out of the log.
+/// 3. Request id on the response, so clients can quote it.
+/// 4. CORS, so even rejections below carry the headers browsers need to
+///    read them.
+/// 5. Compression, applied to every response body produced further in. Only
+///    gzip is built in; a client that doesn't accept it (`identity`, other
+///    codings, `gzip;q=0`) gets the body uncompressed and without a
+///    `Content-Encoding` header, never an error.
+/// 6. API version headers, on every response including rejections.
+/// 7. Shutdown report counts and 8. Prometheus metrics, outside the guards
+///    below so timed-out and rejected requests are counted.
+/// 9. Request deadline, around everything that does real work.
+/// 10. URI length, draining and maintenance guards: cheap rejections, in
+///     that order so an oversized URI is a 414 whatever the service state.
+/// 11. Daily quota, after the guards so rejected requests aren't charged.
+/// 12. Priority admission, inside the deadline so time spent queued counts
+///     against it, and after the guards so rejected requests never queue.
+/// 13. Body logging, innermost so it sees the uncompressed bodies the
+///     handler read and wrote.
+///
+/// Route-specific layers (content type, in-flight tracking, load shedding)
+/// stay on their routes in `app`.
+pub fn build_middleware(
+    state: &AppState,
+) -> ServiceBuilder<
+    impl Layer<
+            Route,
+            Service = impl Service<
+                Request,
+                Response = impl IntoResponse + 'static,
+                Error = impl Into<Infallible> + 'static,
+                Future = impl Send + 'static,
+            > + Clone
+                          + Send
+                          + 'static,
+        > + Clone
+        + Send
+        + 'static,
+> {
+    let sample_rate = state.config.trace_sample_rate;
+    let redactor = Redactor::new(&state.config);
+
+    ServiceBuilder::new()
+        .layer(SetRequestIdLayer::new(REQUEST_ID.clone(), MakeRequestUuid))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(move |request: &Request<Body>| {
+                    let request_id = request
+                        .headers()
+                        .get(&REQUEST_ID)
+                        .and_then(|value| value.to_str().ok())
+                        .unwrap_or_default();
+                    if !sampling::is_sampled(request_id, sample_rate) {
+                        return Span::none();
+                    }
+                    tracing::info_span!(
+                        "request",
+                        method = %request.method(),
+                        uri = %redactor.uri(request.uri()),
+                        %request_id,
+                    )
+                })
+                .on_response(SampledOnResponse),
+        )
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID.clone()))
+        .layer(CorsLayer::permissive())
+        .layer(CompressionLayer::new())
+        .layer(middleware::from_fn_with_state(
+            state.versioning.clone(),
+            versioning::stamp_version_headers,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.stats.clone(),
+            report::count_requests,
+        ))
+        .layer(middleware::from_fn(telemetry::track_metrics))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(RequestTimeouts::new(&state.config, &ROUTE_TIMEOUTS)),
+            deadline::enforce_request_timeout,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.config.max_uri_bytes,
+            uri_limit::reject_long_uri,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            shutdown::reject_while_draining,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            maintenance::maintenance_gate,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.quota.clone(),
+            quota::enforce_quota,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.limiter.clone(),
+            priority::admit_by_priority,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(BodyLogging::new(&state.config)),
+            body_log::log_bodies,
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{body_json, seed, send, test_state, CapturedLogs};
+    use axum::http::{header, StatusCode};
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn every_layer_applies_to_a_request() {
+        let state = test_state();
+        seed(&state, 20).await;
+        let logs = CapturedLogs::default();
+        let _guard = logs.install();
+
+        let response = send(
+            &state,
+            Request::get("/items")
+                .header(header::ACCEPT_ENCODING, "gzip")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert!(response.headers().contains_key("api-version"));
+        let request_id = response.headers()[&REQUEST_ID].to_str().unwrap();
+        assert_eq!(request_id.len(), 36, "a generated UUID: {request_id}");
+
+        let output = logs.contents();
+        assert!(output.contains("finished processing request"), "{output}");
+        assert!(
+            output.contains(&format!("request_id={request_id}")),
+            "{output}"
+        );
+
+        // A client-supplied id is kept rather than replaced
+        let response = send(
+            &state,
+            Request::get("/health")
+                .header(&REQUEST_ID, "trace-me")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response.headers()[&REQUEST_ID], "trace-me");
+    }
+
+    #[tokio::test]
+    async fn codings_the_client_does_not_accept_fall_back_to_identity() {
+        let state = test_state();
+        seed(&state, 20).await;
+
+        for accept_encoding in ["identity", "deflate", "br;q=1, gzip;q=0", "gzip;q=0"] {
+            let response = send(
+                &state,
+                Request::get("/items")
+                    .header(header::ACCEPT_ENCODING, accept_encoding)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK, "{accept_encoding}");
+            assert!(
+                !response.headers().contains_key(header::CONTENT_ENCODING),
+                "{accept_encoding}"
+            );
+            // Still a plain JSON body
+            assert_eq!(
+                body_json(response).await["data"]["items"]
+                    .as_array()
+                    .unwrap()
+                    .len(),
+                20,
+                "{accept_encoding}"
+            );
+        }
+    }
+}
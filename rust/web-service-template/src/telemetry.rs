@@ -0,0 +1,251 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+#[cfg(feature = "metrics")]
+use metrics_exporter_prometheus::PrometheusBuilder;
+use std::cell::Cell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Once;
+use std::time::Instant;
+
+/// Route label for requests that matched no route. Using the raw path would
+/// let arbitrary URLs create unbounded metric series.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Handler names by method and route template, used as the `handler` label.
+/// Add an entry when registering a new route in `app`.
+const HANDLERS: &[(Method, &str, &str)] = &[
+    (Method::GET, "/", "health_check"),
+    (Method::GET, "/health", "health_check"),
+    (Method::GET, "/healthz/deep", "deep_health"),
+    (Method::GET, "/readyz", "readyz"),
+    (Method::GET, "/metrics", "metrics"),
+    (Method::GET, "/items", "get_items"),
+    (Method::POST, "/items", "create_item"),
+    (Method::GET, "/items/export", "export_items"),
+    (Method::POST, "/items/batch-get", "batch_get_items"),
+    (Method::POST, "/items/bulk", "bulk_create_items"),
+    (Method::POST, "/items/import", "import_items"),
+    (Method::GET, "/items/stats", "item_stats"),
+    (Method::GET, "/items/changes", "item_changes"),
+    (Method::GET, "/items/:id", "get_item"),
+    (Method::PUT, "/items/:id", "put_item"),
+    (Method::PATCH, "/items/:id", "patch_item"),
+    (Method::GET, "/items/:id/related", "get_related_items"),
+    (Method::GET, "/items/:id/diff", "diff_item"),
+    (Method::POST, "/ingest", "ingest_events"),
+    (Method::GET, "/admin/config", "admin_config"),
+    (Method::GET, "/admin/maintenance", "get_maintenance"),
+    (Method::PUT, "/admin/maintenance", "set_maintenance"),
+    (Method::POST, "/admin/shutdown", "admin_shutdown"),
+];
+
+fn handler_name(method: &Method, route: &str) -> &'static str {
+    HANDLERS
+        .iter()
+        .find(|(m, r, _)| m == method && *r == route)
+        .map_or("unknown", |(_, _, name)| name)
+}
+
+thread_local! {
+    static RECORDING_DISABLED: Cell<bool> = const { Cell::new(false) };
+}
+
+static RECORDING_FAILED_WARNING: Once = Once::new();
+
+/// Runs metric calls best-effort, so a misbehaving recorder can never fail
+/// the request or task that is recording.
+///
+/// A panic from the recorder is caught and logged once at warn; after that,
+/// recording is skipped on that thread. Each thread gives up after its first
+/// failure, so a broken recorder costs at most one panic per runtime thread.
+/// Without any recorder installed the `metrics` macros are already no-ops.
+pub fn record(f: impl FnOnce()) {
+    if RECORDING_DISABLED.get() {
+        return;
+    }
+    if catch_unwind(AssertUnwindSafe(f)).is_err() {
+        RECORDING_DISABLED.set(true);
+        RECORDING_FAILED_WARNING.call_once(|| {
+            tracing::warn!("Metrics recorder failed; skipping metrics from now on");
+        });
+    }
+}
+
+/// Renders the `/metrics` endpoint.
+#[cfg(feature = "metrics")]
+pub type MetricsHandle = metrics_exporter_prometheus::PrometheusHandle;
+
+/// Without the `metrics` feature there is no exporter, so no handle can
+/// exist and `/metrics` always answers 503.
+#[cfg(not(feature = "metrics"))]
+#[derive(Clone)]
+pub enum MetricsHandle {}
+
+#[cfg(not(feature = "metrics"))]
+impl MetricsHandle {
+    fn render(&self) -> String {
+        match *self {}
+    }
+}
+
+/// Installs the global Prometheus recorder, returning a handle used to render
+/// the `/metrics` endpoint.
+#[cfg(feature = "metrics")]
+pub fn install_recorder() -> Option<MetricsHandle> {
+    match PrometheusBuilder::new().install_recorder() {
+        Ok(handle) => Some(handle),
+        Err(e) => {
+            tracing::warn!("Failed to install metrics recorder: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+pub fn install_recorder() -> Option<MetricsHandle> {
+    None
+}
+
+pub async fn metrics_handler(State(handle): State<Option<MetricsHandle>>) -> impl IntoResponse {
+    match handle {
+        Some(handle) => (StatusCode::OK, handle.render()),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "metrics recorder not installed".to_string(),
+        ),
+    }
+}
+
+/// Middleware recording request count and latency per route template
+/// (`/items/:id`, not `/items/123`) and handler name.
+pub async fn track_metrics(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED_ROUTE.to_string(), |path| {
+            path.as_str().to_string()
+        });
+    let handler = handler_name(&method, &route);
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let elapsed = started.elapsed().as_secs_f64();
+
+    let status = response.status().as_u16().to_string();
+    record(|| {
+        let labels = [
+            ("method", method.to_string()),
+            ("route", route),
+            ("handler", handler.to_string()),
+        ];
+        metrics::histogram!("http_request_duration_seconds", &labels).record(elapsed);
+
+        let mut labels = labels.to_vec();
+        labels.push(("status", status));
+        metrics::counter!("http_requests_total", &labels).increment(1);
+    });
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{send, test_state};
+    use axum::body::Body;
+    use metrics::{
+        Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+    };
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn dynamic_ids_share_one_route_series() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let _guard = metrics::set_default_local_recorder(&recorder);
+
+        let state = test_state();
+        crate::test_support::seed(&state, 3).await;
+        for id in 1..=3 {
+            let uri = format!("/items/{id}");
+            send(&state, Request::get(uri).body(Body::empty()).unwrap()).await;
+        }
+        send(
+            &state,
+            Request::get("/no/such/route").body(Body::empty()).unwrap(),
+        )
+        .await;
+
+        let rendered = handle.render();
+        let series: Vec<&str> = rendered
+            .lines()
+            .filter(|line| line.starts_with("http_requests_total{"))
+            .collect();
+
+        assert!(
+            series.contains(
+                &r#"http_requests_total{method="GET",route="/items/:id",handler="get_item",status="200"} 3"#
+            ),
+            "{rendered}"
+        );
+        assert!(series.iter().all(|line| !line.contains("/items/1")));
+        assert!(series
+            .iter()
+            .any(|line| line.contains(r#"route="unmatched""#)));
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    #[tokio::test]
+    async fn metrics_are_unavailable_without_the_exporter() {
+        let state = test_state();
+        let response = send(
+            &state,
+            Request::get("/metrics").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn requests_succeed_without_a_recorder() {
+        let state = test_state();
+        let response = send(&state, Request::get("/items").body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    /// Recorder whose every registration panics.
+    struct BrokenRecorder;
+
+    impl Recorder for BrokenRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, _: &Key, _: &Metadata<'_>) -> Counter {
+            panic!("broken recorder")
+        }
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            panic!("broken recorder")
+        }
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            panic!("broken recorder")
+        }
+    }
+
+    #[tokio::test]
+    async fn failing_recorder_never_fails_requests() {
+        let _guard = metrics::set_default_local_recorder(&BrokenRecorder);
+        let state = test_state();
+
+        for _ in 0..3 {
+            let response = send(&state, Request::get("/items").body(Body::empty()).unwrap()).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        assert!(RECORDING_DISABLED.get());
+    }
+}
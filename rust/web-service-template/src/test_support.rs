@@ -0,0 +1,120 @@
+//! Shared helpers for router-level tests.
+
+use axum::{
+    body::{to_bytes, Body},
+    http::Request,
+    response::Response,
+};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tower::ServiceExt;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::ids::ItemId;
+use crate::items::ItemBuilder;
+use crate::{app, AppState};
+
+pub fn test_state() -> AppState {
+    test_state_with(Config::default())
+}
+
+pub fn test_state_with(config: Config) -> AppState {
+    AppState::new(config, None)
+}
+
+/// State whose wall clock is `clock`, e.g. a [`ManualClock`].
+pub fn test_state_with_clock(config: Config, clock: Arc<dyn Clock>) -> AppState {
+    AppState::with_clock(config, None, clock)
+}
+
+pub async fn send(state: &AppState, request: Request<Body>) -> Response {
+    app(state.clone()).oneshot(request).await.unwrap()
+}
+
+pub async fn body_json(response: Response) -> serde_json::Value {
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+/// A sequential item id as responses write it: a number, or a string with
+/// the `string-ids` feature.
+pub fn json_id(id: u32) -> serde_json::Value {
+    serde_json::to_value(ItemId::from(id)).unwrap()
+}
+
+/// Inserts items `1..=count` named `item-<id>`, created now.
+pub async fn seed(state: &AppState, count: u32) {
+    let created_at = state.expiry.now_secs();
+    let mut items = state.store.write().await;
+    for id in 1..=count {
+        state.ids.observe(&id.into());
+        items.insert(
+            id.into(),
+            ItemBuilder::new(id).created_at(created_at).build().into(),
+        );
+    }
+}
+
+/// Wall clock that only moves when told to.
+pub struct ManualClock(Mutex<SystemTime>);
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self(Mutex::new(UNIX_EPOCH + Duration::from_secs(1_700_000_000)))
+    }
+}
+
+impl ManualClock {
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Collects everything logged through tracing while installed, for
+/// asserting on log output. Use with a `current_thread` runtime so the
+/// thread-local subscriber sees every event.
+#[derive(Clone, Default)]
+pub struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedLogs {
+    pub fn install(&self) -> tracing::subscriber::DefaultGuard {
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .with_writer(self.clone())
+            .finish();
+        tracing::subscriber::set_default(subscriber)
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for CapturedLogs {
+    type Writer = CapturedLogs;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
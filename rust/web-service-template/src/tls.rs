@@ -0,0 +1,449 @@
+use axum::{body::Body, extract::Request, Router};
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use rustls::crypto::CryptoProvider;
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::{ServerConfig, SupportedProtocolVersion};
+use std::fmt;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
+use tower_http::normalize_path::NormalizePath;
+use tracing::{debug, info, warn};
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+/// The protocol versions and cipher suites handshakes may use, from
+/// `TLS_MIN_VERSION` and `TLS_CIPHERS`.
+#[derive(Debug, Clone)]
+pub struct TlsPolicy {
+    versions: Vec<&'static SupportedProtocolVersion>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl Default for TlsPolicy {
+    fn default() -> Self {
+        Self {
+            versions: rustls::DEFAULT_VERSIONS.to_vec(),
+            provider: provider(),
+        }
+    }
+}
+
+impl TlsPolicy {
+    /// Rejects cipher names rustls doesn't know, and allowlists that leave
+    /// no suite for any of the allowed versions, which could never complete
+    /// a handshake.
+    pub fn new(min_version: &str, ciphers: &[String]) -> Result<Self, String> {
+        let versions = match min_version {
+            "1.2" => vec![&rustls::version::TLS13, &rustls::version::TLS12],
+            "1.3" => vec![&rustls::version::TLS13],
+            other => return Err(format!("unsupported minimum TLS version {other:?}")),
+        };
+
+        let mut provider = rustls::crypto::ring::default_provider();
+        if !ciphers.is_empty() {
+            let known: Vec<&str> = provider
+                .cipher_suites
+                .iter()
+                .filter_map(|suite| suite.suite().as_str())
+                .collect();
+            if let Some(unknown) = ciphers.iter().find(|c| !known.contains(&c.as_str())) {
+                return Err(format!(
+                    "unknown cipher suite {unknown:?}, expected one of {}",
+                    known.join(", ")
+                ));
+            }
+            provider.cipher_suites.retain(|suite| {
+                suite
+                    .suite()
+                    .as_str()
+                    .is_some_and(|name| ciphers.iter().any(|c| c == name))
+            });
+        }
+        if !provider
+            .cipher_suites
+            .iter()
+            .any(|suite| versions.contains(&suite.version()))
+        {
+            return Err(format!(
+                "none of the allowed cipher suites work with TLS {min_version} or later"
+            ));
+        }
+
+        Ok(Self {
+            versions,
+            provider: Arc::new(provider),
+        })
+    }
+}
+
+/// The server certificate and key, re-read from disk on [`reload`].
+///
+/// Handshakes pick up whatever pair is current when they start, so a reload
+/// never disturbs established connections. A pair that fails to load is
+/// rejected and the previous one stays in use.
+///
+/// [`reload`]: CertReloader::reload
+pub struct CertReloader {
+    cert_file: PathBuf,
+    key_file: PathBuf,
+    current: RwLock<Arc<CertifiedKey>>,
+    policy: TlsPolicy,
+}
+
+impl CertReloader {
+    pub fn load(cert_file: &Path, key_file: &Path, policy: TlsPolicy) -> Result<Self, String> {
+        Ok(Self {
+            current: RwLock::new(Arc::new(load_pair(cert_file, key_file)?)),
+            cert_file: cert_file.to_path_buf(),
+            key_file: key_file.to_path_buf(),
+            policy,
+        })
+    }
+
+    /// Re-reads the certificate and key, swapping them in only if they
+    /// load and match each other.
+    pub fn reload(&self) -> Result<(), String> {
+        let pair = load_pair(&self.cert_file, &self.key_file)?;
+        *self.current.write().unwrap() = Arc::new(pair);
+        info!("Reloaded TLS certificate from {}", self.cert_file.display());
+        Ok(())
+    }
+
+    pub fn acceptor(self: &Arc<Self>) -> TlsAcceptor {
+        let mut config = ServerConfig::builder_with_provider(self.policy.provider.clone())
+            .with_protocol_versions(&self.policy.versions)
+            .expect("TlsPolicy::new checked the versions against the suites")
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone());
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        TlsAcceptor::from(Arc::new(config))
+    }
+}
+
+impl fmt::Debug for CertReloader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertReloader")
+            .field("cert_file", &self.cert_file)
+            .field("key_file", &self.key_file)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ResolvesServerCert for CertReloader {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+fn load_pair(cert_file: &Path, key_file: &Path) -> Result<CertifiedKey, String> {
+    let open = |path: &Path| {
+        std::fs::File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| format!("cannot open {}: {e}", path.display()))
+    };
+
+    let certs = rustls_pemfile::certs(&mut open(cert_file)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid certificate in {}: {e}", cert_file.display()))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate found in {}", cert_file.display()));
+    }
+    let key = rustls_pemfile::private_key(&mut open(key_file)?)
+        .map_err(|e| format!("invalid private key in {}: {e}", key_file.display()))?
+        .ok_or_else(|| format!("no private key found in {}", key_file.display()))?;
+    let key = provider()
+        .key_provider
+        .load_private_key(key)
+        .map_err(|e| format!("unusable private key in {}: {e}", key_file.display()))?;
+
+    let pair = CertifiedKey::new(certs, key);
+    pair.keys_match()
+        .map_err(|e| format!("certificate and key do not match: {e}"))?;
+    Ok(pair)
+}
+
+/// Reloads the certificate on every SIGHUP until `shutdown` is cancelled.
+pub async fn reload_on_sighup(certs: Arc<CertReloader>, shutdown: CancellationToken) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangups = signal(SignalKind::hangup()).expect("failed to install SIGHUP handler");
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => return,
+                _ = hangups.recv() => {}
+            }
+            info!("Received SIGHUP, reloading TLS certificate");
+            if let Err(e) = certs.reload() {
+                warn!("Keeping the current TLS certificate: {}", e);
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = (certs, shutdown);
+}
+
+/// Serves `app` over TLS until `shutdown` is cancelled, then waits for open
+/// connections to finish their in-flight requests.
+pub async fn serve(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    app: NormalizePath<Router>,
+    shutdown: CancellationToken,
+) -> io::Result<()> {
+    let graceful = GracefulShutdown::new();
+    let builder = auto::Builder::new(TokioExecutor::new());
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            },
+        };
+
+        let acceptor = acceptor.clone();
+        let service = app
+            .clone()
+            .map_request(|request: Request<Incoming>| request.map(Body::new));
+        let builder = builder.clone();
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    debug!("TLS handshake with {} failed: {}", peer, e);
+                    return;
+                }
+            };
+            let connection = builder
+                .serve_connection_with_upgrades(
+                    TokioIo::new(stream),
+                    TowerToHyperService::new(service),
+                )
+                .into_owned();
+            if let Err(e) = watcher.watch(connection).await {
+                debug!("Connection from {} ended with an error: {}", peer, e);
+            }
+        });
+    }
+
+    graceful.shutdown().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+    use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+    use rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+    use std::net::SocketAddr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    use crate::test_support::test_state;
+
+    /// Accepts any server certificate; the test inspects it instead.
+    #[derive(Debug)]
+    struct AcceptAnyCert(Arc<CryptoProvider>);
+
+    impl ServerCertVerifier for AcceptAnyCert {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls12_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls13_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+
+    /// Writes a fresh self-signed pair, returning the certificate's DER.
+    fn write_pair(dir: &Path) -> Vec<u8> {
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        std::fs::write(dir.join("cert.pem"), generated.cert.pem()).unwrap();
+        std::fs::write(dir.join("key.pem"), generated.key_pair.serialize_pem()).unwrap();
+        generated.cert.der().to_vec()
+    }
+
+    /// Performs a new handshake and a request, returning the certificate
+    /// the server presented and the response's status line.
+    async fn handshake(addr: SocketAddr) -> (Vec<u8>, String) {
+        try_handshake(addr, rustls::DEFAULT_VERSIONS).await.unwrap()
+    }
+
+    /// Like [`handshake`], offering only `versions`; the error is the failed
+    /// handshake's.
+    async fn try_handshake(
+        addr: SocketAddr,
+        versions: &[&'static SupportedProtocolVersion],
+    ) -> io::Result<(Vec<u8>, String)> {
+        let config = ClientConfig::builder_with_provider(provider())
+            .with_protocol_versions(versions)
+            .unwrap()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider())))
+            .with_no_client_auth();
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await?;
+        let cert = stream.get_ref().1.peer_certificates().unwrap()[0].to_vec();
+
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response).await;
+        Ok((
+            cert,
+            response.lines().next().unwrap_or_default().to_string(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn reload_serves_the_new_certificate_to_new_handshakes() {
+        let dir = std::env::temp_dir().join(format!("tls-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = write_pair(&dir);
+        let certs = Arc::new(
+            CertReloader::load(
+                &dir.join("cert.pem"),
+                &dir.join("key.pem"),
+                TlsPolicy::default(),
+            )
+            .unwrap(),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shutdown = CancellationToken::new();
+        let server = tokio::spawn(serve(
+            listener,
+            certs.acceptor(),
+            crate::app(test_state()),
+            shutdown.clone(),
+        ));
+
+        assert_eq!(
+            handshake(addr).await,
+            (first.clone(), "HTTP/1.1 200 OK".to_string())
+        );
+
+        let second = write_pair(&dir);
+        certs.reload().unwrap();
+        assert_eq!(handshake(addr).await.0, second);
+
+        // A broken pair is rejected and the current one kept
+        std::fs::write(dir.join("cert.pem"), "not a certificate").unwrap();
+        assert!(certs.reload().is_err());
+        assert_eq!(handshake(addr).await.0, second);
+
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn handshakes_below_the_minimum_version_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("tls-min-version-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_pair(&dir);
+        let policy = TlsPolicy::new("1.3", &[]).unwrap();
+        let certs = Arc::new(
+            CertReloader::load(&dir.join("cert.pem"), &dir.join("key.pem"), policy).unwrap(),
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shutdown = CancellationToken::new();
+        let server = tokio::spawn(serve(
+            listener,
+            certs.acceptor(),
+            crate::app(test_state()),
+            shutdown.clone(),
+        ));
+
+        assert!(try_handshake(addr, &[&rustls::version::TLS12])
+            .await
+            .is_err());
+        let (_, status) = try_handshake(addr, &[&rustls::version::TLS13])
+            .await
+            .unwrap();
+        assert_eq!(status, "HTTP/1.1 200 OK");
+
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn policies_that_cannot_complete_a_handshake_are_refused() {
+        let tls12_only = ["TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256".to_string()];
+        assert!(TlsPolicy::new("1.2", &tls12_only).is_ok());
+        assert_eq!(
+            TlsPolicy::new("1.3", &tls12_only).unwrap_err(),
+            "none of the allowed cipher suites work with TLS 1.3 or later"
+        );
+
+        let unknown = TlsPolicy::new("1.2", &["TLS_RSA_WITH_RC4_128_MD5".to_string()]);
+        assert!(unknown
+            .unwrap_err()
+            .starts_with(r#"unknown cipher suite "TLS_RSA_WITH_RC4_128_MD5", expected one of "#),);
+        assert!(TlsPolicy::new("1.1", &[]).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::ApiResponse;
+
+/// Middleware rejecting requests whose path and query exceed `max_bytes`
+/// with 414 URI Too Long, before any handler or extractor sees them.
+pub async fn reject_long_uri(
+    State(max_bytes): State<usize>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let len = request
+        .uri()
+        .path_and_query()
+        .map_or(0, |pq| pq.as_str().len());
+
+    if len > max_bytes {
+        return (
+            StatusCode::URI_TOO_LONG,
+            ApiResponse::error(format!(
+                "Request URI is {len} bytes, exceeding the {max_bytes} byte limit"
+            )),
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::test_support::{body_json, send, test_state_with};
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+
+    #[tokio::test]
+    async fn over_limit_query_string_gets_414() {
+        let state = test_state_with(Config {
+            max_uri_bytes: 64,
+            ..Config::default()
+        });
+
+        let ok = send(
+            &state,
+            Request::get("/items?q=short").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(ok.status(), StatusCode::OK);
+
+        let uri = format!("/items?q={}", "x".repeat(64));
+        let response = send(&state, Request::get(uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::URI_TOO_LONG);
+
+        let body = body_json(response).await;
+        assert_eq!(body["success"], false);
+        assert!(body["message"].as_str().unwrap().contains("64 byte limit"));
+    }
+}
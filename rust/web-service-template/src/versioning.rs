@@ -0,0 +1,96 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::config::Config;
+
+static API_VERSION: HeaderName = HeaderName::from_static("api-version");
+static DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+static SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// Version and deprecation headers, resolved from config once at startup.
+pub struct ApiVersioning {
+    version: HeaderValue,
+    deprecated_routes: HashSet<String>,
+    sunset: Option<HeaderValue>,
+}
+
+impl ApiVersioning {
+    /// Expects a config that passed [`Config::validate`]; invalid header
+    /// values are dropped.
+    pub fn new(config: &Config) -> Self {
+        Self {
+            version: HeaderValue::from_str(&config.api_version)
+                .unwrap_or(HeaderValue::from_static("unknown")),
+            deprecated_routes: config.deprecated_routes.iter().cloned().collect(),
+            sunset: config
+                .api_sunset
+                .as_deref()
+                .and_then(|sunset| HeaderValue::from_str(sunset).ok()),
+        }
+    }
+}
+
+/// Middleware stamping every response with `API-Version`, and responses from
+/// deprecated route templates with `Deprecation: true` plus `Sunset` when
+/// one is configured.
+pub async fn stamp_version_headers(
+    State(versioning): State<Arc<ApiVersioning>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let deprecated = request
+        .extensions()
+        .get::<MatchedPath>()
+        .is_some_and(|path| versioning.deprecated_routes.contains(path.as_str()));
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(API_VERSION.clone(), versioning.version.clone());
+    if deprecated {
+        headers.insert(DEPRECATION.clone(), HeaderValue::from_static("true"));
+        if let Some(sunset) = &versioning.sunset {
+            headers.insert(SUNSET.clone(), sunset.clone());
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{send, test_state_with};
+    use axum::{body::Body, http::StatusCode};
+
+    #[tokio::test]
+    async fn deprecated_routes_carry_deprecation_and_sunset() {
+        let state = test_state_with(Config {
+            api_version: "2".to_string(),
+            deprecated_routes: vec!["/items/export".to_string()],
+            api_sunset: Some("Wed, 01 Jul 2026 00:00:00 GMT".to_string()),
+            ..Config::default()
+        });
+
+        let response = send(
+            &state,
+            Request::get("/items/export").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[&API_VERSION], "2");
+        assert_eq!(headers[&DEPRECATION], "true");
+        assert_eq!(headers[&SUNSET], "Wed, 01 Jul 2026 00:00:00 GMT");
+
+        let response = send(&state, Request::get("/items").body(Body::empty()).unwrap()).await;
+        let headers = response.headers();
+        assert_eq!(headers[&API_VERSION], "2");
+        assert!(!headers.contains_key(&DEPRECATION));
+        assert!(!headers.contains_key(&SUNSET));
+    }
+}
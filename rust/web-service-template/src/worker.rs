@@ -0,0 +1,74 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::ItemStore;
+
+pub type WorkError = Box<dyn std::error::Error + Send + Sync>;
+
+/// What a successful iteration amounted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Something was processed.
+    Worked,
+    /// There was nothing to do.
+    Idle,
+}
+
+/// Periodic background work run alongside the HTTP server in combined mode.
+///
+/// Shares `perform_work` and [`Outcome`] with the daemon template's
+/// `Worker` trait, so a daemon worker's logic carries over, but not its
+/// `start` and `perform_work_with_progress` hooks: [`run`] never calls
+/// them. A ported worker does its setup before it is handed to `run` and
+/// drops its progress reports.
+#[async_trait]
+pub trait Worker: Send + Sync + 'static {
+    async fn perform_work(&self, iteration: u64) -> Result<Outcome, WorkError>;
+}
+
+/// Example worker that reports on the shared item store.
+pub struct StoreReportWorker {
+    store: ItemStore,
+}
+
+impl StoreReportWorker {
+    pub fn new(store: ItemStore) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl Worker for StoreReportWorker {
+    async fn perform_work(&self, iteration: u64) -> Result<Outcome, WorkError> {
+        let count = self.store.read().await.len();
+        info!("Worker tick #{}: {} items in store", iteration, count);
+        Ok(Outcome::Worked)
+    }
+}
+
+/// Runs `worker` every `period` until `shutdown` is cancelled.
+pub async fn run(worker: Arc<dyn Worker>, period: Duration, shutdown: CancellationToken) {
+    let mut tick_interval = interval(period);
+    let mut counter = 0;
+
+    info!("Background worker is running...");
+
+    loop {
+        tokio::select! {
+            _ = tick_interval.tick() => {
+                counter += 1;
+                if let Err(e) = worker.perform_work(counter).await {
+                    error!("Work failed: {}", e);
+                }
+            }
+            _ = shutdown.cancelled() => {
+                info!("Shutdown signal received, stopping background worker...");
+                break;
+            }
+        }
+    }
+}